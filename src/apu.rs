@@ -1,21 +1,261 @@
-use crate::bus::{BusAddr, ByteReadable, ByteWritable};
+mod channel;
+mod dmc;
+mod envelope;
+mod frame_counter;
+mod length_counter;
+mod mixer;
+mod noise;
+mod pulse;
+mod sampler;
+mod triangle;
 
-pub struct APU;
+pub use self::channel::Channel;
+pub(crate) use self::pulse::Pulse;
+use self::{
+    dmc::DMC,
+    frame_counter::{FrameCounter, FrameEvent},
+    mixer::ChannelLevels,
+    noise::Noise,
+    pulse::SweepNegate,
+    sampler::Sampler,
+    triangle::Triangle,
+};
+use crate::{
+    bus::{BusAddr, ByteReadable, ByteWritable},
+    region::Region,
+};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+pub struct APU {
+    region: Region,
+    pulse1: Pulse,
+    pulse2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: DMC,
+    frame_counter: FrameCounter,
+    cycle: u64,
+    sampler: Sampler,
+    expansion_level: f32,
+    muted: [bool; Channel::ALL.len()],
+    soloed: [bool; Channel::ALL.len()],
+    stem_samplers: Option<Vec<Sampler>>,
+}
 
 impl APU {
     pub fn new() -> Self {
-        Self
+        Self::with_settings(Region::NTSC, DEFAULT_SAMPLE_RATE)
+    }
+
+    pub fn with_region(region: Region) -> Self {
+        Self::with_settings(region, DEFAULT_SAMPLE_RATE)
+    }
+
+    pub fn with_settings(region: Region, sample_rate: u32) -> Self {
+        Self {
+            region,
+            pulse1: Pulse::new(SweepNegate::OnesComplement),
+            pulse2: Pulse::new(SweepNegate::TwosComplement),
+            triangle: Triangle::new(),
+            noise: Noise::new(region),
+            dmc: DMC::new(region),
+            frame_counter: FrameCounter::new(region),
+            cycle: 0,
+            sampler: Sampler::new(region.cpu_clock_rate(), sample_rate),
+            expansion_level: 0.0,
+            muted: [false; Channel::ALL.len()],
+            soloed: [false; Channel::ALL.len()],
+            stem_samplers: None,
+        }
+    }
+
+    /// Advances the APU by a single CPU cycle.
+    pub fn tick(&mut self) {
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.cycle % 2 == 1 {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+
+        let event = self.frame_counter.clock();
+        self.handle_frame_event(event);
+
+        let levels = self.channel_levels();
+        if let Some(stem_samplers) = self.stem_samplers.as_mut() {
+            for (channel, sampler) in Channel::ALL.iter().zip(stem_samplers.iter_mut()) {
+                sampler.push(mixer::mix(levels.isolate(*channel)));
+            }
+        }
+        self.sampler.push(mixer::mix(self.audible_levels(levels)));
+        self.cycle += 1;
+    }
+
+    /// Sets the current expansion audio output of the cartridge, already
+    /// scaled relative to the 2A03 channels.
+    pub fn set_expansion_level(&mut self, level: f32) {
+        self.expansion_level = level;
+    }
+
+    pub fn set_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel.index()] = muted;
+    }
+
+    pub fn is_muted(&self, channel: Channel) -> bool {
+        self.muted[channel.index()]
+    }
+
+    /// While any channel is soloed, only soloed channels reach the mixed
+    /// output. Muting still applies to soloed channels.
+    pub fn set_soloed(&mut self, channel: Channel, soloed: bool) {
+        self.soloed[channel.index()] = soloed;
+    }
+
+    pub fn is_soloed(&self, channel: Channel) -> bool {
+        self.soloed[channel.index()]
+    }
+
+    /// Starts rendering every channel into its own sample stream in addition
+    /// to the mixed output. Stems ignore mute and solo settings.
+    pub fn enable_stems(&mut self) {
+        if self.stem_samplers.is_none() {
+            let sample_rate = self.sampler.sample_rate();
+            self.stem_samplers = Some(
+                Channel::ALL
+                    .iter()
+                    .map(|_| Sampler::new(self.region.cpu_clock_rate(), sample_rate))
+                    .collect(),
+            );
+        }
+    }
+
+    /// Drains the stem generated so far for `channel`. Empty unless
+    /// `enable_stems` has been called.
+    pub fn take_stem_samples(&mut self, channel: Channel) -> Vec<f32> {
+        match self.stem_samplers.as_mut() {
+            Some(stem_samplers) => stem_samplers[channel.index()].take_samples(),
+            None => Vec::new(),
+        }
+    }
+
+    /// State of the APU's IRQ line (frame counter or DMC).
+    pub fn irq(&self) -> bool {
+        self.frame_counter.irq() || self.dmc.irq()
+    }
+
+    /// Address the DMC wants read from the CPU bus, if any. The bus answers
+    /// with `dmc_fill_sample_buffer`.
+    pub fn dmc_pending_fetch(&self) -> Option<BusAddr> {
+        self.dmc.pending_fetch()
+    }
+
+    pub fn dmc_fill_sample_buffer(&mut self, value: u8) {
+        self.dmc.fill_sample_buffer(value);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sampler.sample_rate()
+    }
+
+    /// Drains the audio generated so far as mono samples in -1.0..=1.0.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.sampler.take_samples()
+    }
+
+    fn channel_levels(&self) -> ChannelLevels {
+        ChannelLevels {
+            pulse1: self.pulse1.output(),
+            pulse2: self.pulse2.output(),
+            triangle: self.triangle.output(),
+            noise: self.noise.output(),
+            dmc: self.dmc.output(),
+            expansion: self.expansion_level,
+        }
+    }
+
+    fn audible_levels(&self, mut levels: ChannelLevels) -> ChannelLevels {
+        let any_soloed = self.soloed.iter().any(|soloed| *soloed);
+        for channel in Channel::ALL {
+            if self.is_muted(channel) || (any_soloed && !self.is_soloed(channel)) {
+                levels.silence(channel);
+            }
+        }
+        levels
+    }
+
+    fn handle_frame_event(&mut self, event: FrameEvent) {
+        if event.quarter_frame {
+            self.pulse1.clock_quarter_frame();
+            self.pulse2.clock_quarter_frame();
+            self.triangle.clock_quarter_frame();
+            self.noise.clock_quarter_frame();
+        }
+        if event.half_frame {
+            self.pulse1.clock_half_frame();
+            self.pulse2.clock_half_frame();
+            self.triangle.clock_half_frame();
+            self.noise.clock_half_frame();
+        }
     }
 }
 
 impl ByteReadable for APU {
-    fn read_byte(&self, _addr: BusAddr) -> u8 {
-        todo!()
+    fn read_byte(&mut self, addr: BusAddr) -> u8 {
+        match addr {
+            0x4015 => {
+                let mut status = 0;
+                if self.pulse1.is_active() {
+                    status |= 0x01;
+                }
+                if self.pulse2.is_active() {
+                    status |= 0x02;
+                }
+                if self.triangle.is_active() {
+                    status |= 0x04;
+                }
+                if self.noise.is_active() {
+                    status |= 0x08;
+                }
+                if self.dmc.is_active() {
+                    status |= 0x10;
+                }
+                if self.frame_counter.irq() {
+                    status |= 0x40;
+                }
+                if self.dmc.irq() {
+                    status |= 0x80;
+                }
+                self.frame_counter.clear_irq();
+
+                status
+            }
+            _ => 0,
+        }
     }
 }
 
 impl ByteWritable for APU {
-    fn write_byte(&mut self, _addr: BusAddr, _value: u8) {
-        todo!()
+    fn write_byte(&mut self, addr: BusAddr, value: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write_register(addr - 0x4000, value),
+            0x4004..=0x4007 => self.pulse2.write_register(addr - 0x4004, value),
+            0x4008..=0x400B => self.triangle.write_register(addr - 0x4008, value),
+            0x400C..=0x400F => self.noise.write_register(addr - 0x400C, value),
+            0x4010..=0x4013 => self.dmc.write_register(addr - 0x4010, value),
+            0x4015 => {
+                self.pulse1.set_enabled(value & 0x01 != 0);
+                self.pulse2.set_enabled(value & 0x02 != 0);
+                self.triangle.set_enabled(value & 0x04 != 0);
+                self.noise.set_enabled(value & 0x08 != 0);
+                self.dmc.set_enabled(value & 0x10 != 0);
+            }
+            0x4017 => {
+                let event = self.frame_counter.write_control(value);
+                self.handle_frame_event(event);
+            }
+            _ => { /* Unused or test registers */ }
+        }
     }
 }
@@ -0,0 +1,126 @@
+use crate::region::Region;
+
+/// Sequencer step positions in CPU cycles.
+#[derive(Debug)]
+struct StepTable {
+    step_1: u32,
+    step_2: u32,
+    step_3: u32,
+    step_4: u32,
+    four_step_length: u32,
+    step_5: u32,
+    five_step_length: u32,
+}
+
+const NTSC_STEPS: StepTable = StepTable {
+    step_1: 7457,
+    step_2: 14913,
+    step_3: 22371,
+    step_4: 29829,
+    four_step_length: 29830,
+    step_5: 37281,
+    five_step_length: 37282,
+};
+
+const PAL_STEPS: StepTable = StepTable {
+    step_1: 8313,
+    step_2: 16627,
+    step_3: 24939,
+    step_4: 33253,
+    four_step_length: 33254,
+    step_5: 41565,
+    five_step_length: 41566,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameEvent {
+    pub quarter_frame: bool,
+    pub half_frame: bool,
+}
+
+#[derive(Debug)]
+pub struct FrameCounter {
+    steps: &'static StepTable,
+    five_step_mode: bool,
+    irq_inhibit: bool,
+    irq: bool,
+    cycle: u32,
+}
+
+impl FrameCounter {
+    pub fn new(region: Region) -> Self {
+        Self {
+            steps: match region {
+                Region::NTSC => &NTSC_STEPS,
+                Region::PAL => &PAL_STEPS,
+            },
+            five_step_mode: false,
+            irq_inhibit: false,
+            irq: false,
+            cycle: 0,
+        }
+    }
+
+    /// Handles a write to $4017. Selecting the 5-step mode clocks the
+    /// quarter and half frame units immediately.
+    pub fn write_control(&mut self, value: u8) -> FrameEvent {
+        self.five_step_mode = value & 0x80 != 0;
+        self.irq_inhibit = value & 0x40 != 0;
+        if self.irq_inhibit {
+            self.irq = false;
+        }
+        self.cycle = 0;
+
+        FrameEvent {
+            quarter_frame: self.five_step_mode,
+            half_frame: self.five_step_mode,
+        }
+    }
+
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    pub fn clear_irq(&mut self) {
+        self.irq = false;
+    }
+
+    pub fn clock(&mut self) -> FrameEvent {
+        self.cycle += 1;
+
+        let steps = self.steps;
+        let mut event = FrameEvent::default();
+        match self.cycle {
+            cycle if cycle == steps.step_1 || cycle == steps.step_3 => {
+                event.quarter_frame = true;
+            }
+            cycle if cycle == steps.step_2 => {
+                event.quarter_frame = true;
+                event.half_frame = true;
+            }
+            cycle if cycle == steps.step_4 && !self.five_step_mode => {
+                event.quarter_frame = true;
+                event.half_frame = true;
+                if !self.irq_inhibit {
+                    self.irq = true;
+                }
+            }
+            cycle if cycle == steps.step_5 && self.five_step_mode => {
+                event.quarter_frame = true;
+                event.half_frame = true;
+            }
+            _ => {}
+        }
+
+        let length = if self.five_step_mode {
+            steps.five_step_length
+        } else {
+            steps.four_step_length
+        };
+        if self.cycle >= length {
+            self.cycle = 0;
+        }
+
+        event
+    }
+}
@@ -0,0 +1,60 @@
+use super::channel::Channel;
+
+/// Raw channel levels as produced by the individual APU units.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChannelLevels {
+    pub pulse1: u8,
+    pub pulse2: u8,
+    pub triangle: u8,
+    pub noise: u8,
+    pub dmc: u8,
+    /// Already normalized to the same scale as the `mix` output.
+    pub expansion: f32,
+}
+
+impl ChannelLevels {
+    pub fn silence(&mut self, channel: Channel) {
+        match channel {
+            Channel::Pulse1 => self.pulse1 = 0,
+            Channel::Pulse2 => self.pulse2 = 0,
+            Channel::Triangle => self.triangle = 0,
+            Channel::Noise => self.noise = 0,
+            Channel::DMC => self.dmc = 0,
+            Channel::Expansion => self.expansion = 0.0,
+        }
+    }
+
+    /// Copy of these levels with every channel but `channel` silenced.
+    pub fn isolate(&self, channel: Channel) -> Self {
+        let mut levels = *self;
+        for other in Channel::ALL {
+            if other != channel {
+                levels.silence(other);
+            }
+        }
+        levels
+    }
+}
+
+/// Combines the channel levels using the non-linear DAC approximation from
+/// the NESdev wiki. The result is in the range 0.0..=1.0 before expansion
+/// audio is added.
+pub fn mix(levels: ChannelLevels) -> f32 {
+    let pulse_sum = (levels.pulse1 + levels.pulse2) as f32;
+    let pulse_out = if pulse_sum == 0.0 {
+        0.0
+    } else {
+        95.88 / (8128.0 / pulse_sum + 100.0)
+    };
+
+    let tnd_sum = levels.triangle as f32 / 8227.0
+        + levels.noise as f32 / 12241.0
+        + levels.dmc as f32 / 22638.0;
+    let tnd_out = if tnd_sum == 0.0 {
+        0.0
+    } else {
+        159.79 / (1.0 / tnd_sum + 100.0)
+    };
+
+    pulse_out + tnd_out + levels.expansion
+}
@@ -0,0 +1,94 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+use crate::region::Region;
+
+const NTSC_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+#[derive(Debug)]
+pub struct Noise {
+    envelope: Envelope,
+    length_counter: LengthCounter,
+    period_table: &'static [u16; 16],
+    short_mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+}
+
+impl Noise {
+    pub fn new(region: Region) -> Self {
+        let period_table = match region {
+            Region::NTSC => &NTSC_PERIOD_TABLE,
+            Region::PAL => &PAL_PERIOD_TABLE,
+        };
+
+        Self {
+            envelope: Envelope::new(),
+            length_counter: LengthCounter::new(),
+            period_table,
+            short_mode: false,
+            timer_period: period_table[0],
+            timer: 0,
+            shift_register: 1,
+        }
+    }
+
+    pub fn write_register(&mut self, index: u16, value: u8) {
+        match index {
+            0 => {
+                self.length_counter.set_halt(value & 0x20 != 0);
+                self.envelope.write_control(value);
+            }
+            1 => { /* Unused */ }
+            2 => {
+                self.short_mode = value & 0x80 != 0;
+                self.timer_period = self.period_table[(value & 0x0F) as usize];
+            }
+            3 => {
+                self.length_counter.load(value >> 3);
+                self.envelope.restart();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length_counter.set_enabled(enabled);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.length_counter.is_active()
+    }
+
+    /// Clocked every CPU cycle; the period table is already in CPU cycles.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register & 1) ^ ((self.shift_register >> tap) & 1);
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length_counter.is_active() || self.shift_register & 1 != 0 {
+            0
+        } else {
+            self.envelope.volume()
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::f32::consts::PI;
+
+/// First order filter as described in the NESdev wiki "APU Mixer" page.
+#[derive(Debug)]
+struct Filter {
+    kind: FilterKind,
+    alpha: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+impl Filter {
+    fn new(kind: FilterKind, sample_rate: u32, cutoff: f32) -> Self {
+        let dt = 1.0 / sample_rate as f32;
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let alpha = match kind {
+            FilterKind::HighPass => rc / (rc + dt),
+            FilterKind::LowPass => dt / (rc + dt),
+        };
+
+        Self {
+            kind,
+            alpha,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = match self.kind {
+            FilterKind::HighPass => {
+                self.alpha * (self.previous_output + input - self.previous_input)
+            }
+            FilterKind::LowPass => {
+                self.previous_output + self.alpha * (input - self.previous_output)
+            }
+        };
+        self.previous_input = input;
+        self.previous_output = output;
+
+        output
+    }
+}
+
+/// Downsamples the per-CPU-cycle mixer output to `sample_rate` by averaging
+/// every cycle that falls into a sample period, then runs the result through
+/// the console's output filter chain.
+///
+/// Only integer arithmetic decides where sample boundaries are, so the same
+/// input always yields bit-identical output.
+#[derive(Debug)]
+pub struct Sampler {
+    clock_rate: u32,
+    sample_rate: u32,
+    phase: u32,
+    accumulator: f32,
+    accumulated_cycles: u32,
+    filters: [Filter; 3],
+    samples: Vec<f32>,
+}
+
+impl Sampler {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            sample_rate,
+            phase: 0,
+            accumulator: 0.0,
+            accumulated_cycles: 0,
+            filters: [
+                Filter::new(FilterKind::HighPass, sample_rate, 90.0),
+                Filter::new(FilterKind::HighPass, sample_rate, 440.0),
+                Filter::new(FilterKind::LowPass, sample_rate, 14_000.0),
+            ],
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn push(&mut self, level: f32) {
+        self.accumulator += level;
+        self.accumulated_cycles += 1;
+
+        self.phase += self.sample_rate;
+        if self.phase >= self.clock_rate {
+            self.phase -= self.clock_rate;
+
+            let mut sample = self.accumulator / self.accumulated_cycles as f32;
+            for filter in self.filters.iter_mut() {
+                sample = filter.process(sample);
+            }
+            self.samples.push(sample);
+
+            self.accumulator = 0.0;
+            self.accumulated_cycles = 0;
+        }
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
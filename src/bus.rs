@@ -1,41 +1,123 @@
-use crate::{apu::APU, dma::DMA, ines::ProgramROM, pad::Pad, ppu::PPU, ram::RAM};
+use crate::{
+    apu::APU,
+    dma::{DMA, OAM_DMA_TRANSFER_BYTES},
+    fds::FDSAdapter,
+    mapper::Mapper,
+    nsf::NSFMemory,
+    pad::Pad,
+    ppu::PPU,
+    ram::RAM,
+};
 
-const MEMORY_MAX_ADDRESS: usize = 0xFFFF;
+const WRAM_SIZE: u16 = 0x0800;
+const WRAM_MIRROR_END_ADDR: u16 = 0x1FFF;
+const PPU_REGISTERS_START_ADDR: u16 = 0x2000;
+const PPU_MIRROR_REGISTERS_END_ADDR: u16 = 0x3FFF;
+const PPU_REGISTERS_SIZE: u16 = 0x0008;
 
-const WRAM_START_ADDR: usize = 0x0000;
-const WRAM_END_ADDR: usize = 0x07FF;
-const WRAM_MIRROR_START_ADDR: usize = 0x0800;
-const WRAM_MIRROR_END_ADDR: usize = 0x1FFF;
-const PPU_REGISTERS_START_ADDR: usize = 0x2000;
-const PPU_REGISTERS_END_ADDR: usize = 0x2007;
-const PPU_MIRROR_REGISTERS_START_ADDR: usize = 0x2008;
-const PPU_MIRROR_REGISTERS_END_ADDR: usize = 0x3FFF;
+const CARTRIDGE_SPACE_START_ADDR: u16 = 0x4020;
 
-const PROGRAM_ROM_SIZE: usize = 0x8000;
+const PPU_OAM_DATA_REGISTER: BusAddr = 0x0004;
+
+/// CPU cycles lost to a DMC sample fetch. When the fetch lands in the middle
+/// of an OAM DMA the two units share the bus and the fetch only costs the
+/// DMA two extra cycles.
+const DMC_DMA_STALL_CYCLES: usize = 4;
+const DMC_DMA_STALL_CYCLES_DURING_OAM_DMA: usize = 2;
 
 pub type BusAddr = u16;
 
 pub trait ByteReadable {
-    fn read_byte(&self, addr: BusAddr) -> u8;
+    fn read_byte(&mut self, addr: BusAddr) -> u8;
 }
 
 pub trait ByteWritable {
     fn write_byte(&mut self, addr: BusAddr, value: u8);
 }
 
+/// What is plugged into the cartridge slot. NSF players and the FDS RAM
+/// adapter are kept apart from game cartridges so that their own controls
+/// stay reachable.
+enum Cartridge<'a> {
+    Mapper(&'a mut dyn Mapper),
+    NSF(&'a mut NSFMemory),
+    FDS(&'a mut FDSAdapter),
+}
+
+impl<'a> Cartridge<'a> {
+    fn mapper(&self) -> &dyn Mapper {
+        match self {
+            Cartridge::Mapper(mapper) => *mapper,
+            Cartridge::NSF(nsf_memory) => *nsf_memory,
+            Cartridge::FDS(fds_adapter) => *fds_adapter,
+        }
+    }
+
+    fn mapper_mut(&mut self) -> &mut dyn Mapper {
+        match self {
+            Cartridge::Mapper(mapper) => *mapper,
+            Cartridge::NSF(nsf_memory) => *nsf_memory,
+            Cartridge::FDS(fds_adapter) => *fds_adapter,
+        }
+    }
+}
+
 pub struct Bus<'a> {
     wram: &'a mut RAM,
-    program_rom: &'a ProgramROM,
+    cartridge: Cartridge<'a>,
     ppu: &'a mut PPU,
     apu: &'a mut APU,
     pad: &'a mut Pad,
     dma: &'a mut DMA,
+    oam_dma_active: bool,
 }
 
 impl<'a> Bus<'a> {
     pub fn new(
         wram: &'a mut RAM,
-        program_rom: &'a ProgramROM,
+        mapper: &'a mut dyn Mapper,
+        ppu: &'a mut PPU,
+        apu: &'a mut APU,
+        pad: &'a mut Pad,
+        dma: &'a mut DMA,
+    ) -> Bus<'a> {
+        Bus {
+            wram,
+            cartridge: Cartridge::Mapper(mapper),
+            ppu,
+            apu,
+            pad,
+            dma,
+            oam_dma_active: false,
+        }
+    }
+
+    /// Builds a bus for playing an NSF, with the NSF player's memory in
+    /// place of a cartridge.
+    pub fn new_nsf(
+        wram: &'a mut RAM,
+        nsf_memory: &'a mut NSFMemory,
+        ppu: &'a mut PPU,
+        apu: &'a mut APU,
+        pad: &'a mut Pad,
+        dma: &'a mut DMA,
+    ) -> Bus<'a> {
+        Bus {
+            wram,
+            cartridge: Cartridge::NSF(nsf_memory),
+            ppu,
+            apu,
+            pad,
+            dma,
+            oam_dma_active: false,
+        }
+    }
+
+    /// Builds a bus for the Famicom Disk System, with the RAM adapter in
+    /// the cartridge slot.
+    pub fn new_fds(
+        wram: &'a mut RAM,
+        fds_adapter: &'a mut FDSAdapter,
         ppu: &'a mut PPU,
         apu: &'a mut APU,
         pad: &'a mut Pad,
@@ -43,56 +125,152 @@ impl<'a> Bus<'a> {
     ) -> Bus<'a> {
         Bus {
             wram,
-            program_rom,
+            cartridge: Cartridge::FDS(fds_adapter),
             ppu,
             apu,
             pad,
             dma,
+            oam_dma_active: false,
+        }
+    }
+
+    pub fn nsf_memory_mut(&mut self) -> Option<&mut NSFMemory> {
+        match &mut self.cartridge {
+            Cartridge::NSF(nsf_memory) => Some(nsf_memory),
+            _ => None,
         }
     }
+
+    pub fn fds_adapter_mut(&mut self) -> Option<&mut FDSAdapter> {
+        match &mut self.cartridge {
+            Cartridge::FDS(fds_adapter) => Some(fds_adapter),
+            _ => None,
+        }
+    }
+
+    /// Advances the rest of the system by the given number of CPU cycles.
+    /// Returns the number of cycles that actually elapsed, which is larger
+    /// when DMC sample fetches stalled the CPU along the way.
+    pub fn tick(&mut self, cpu_cycles: usize) -> usize {
+        let mut remaining = cpu_cycles;
+        let mut elapsed = 0;
+        while remaining > 0 {
+            let mapper = self.cartridge.mapper_mut();
+            for _ in 0..self.ppu.dots_for_cpu_cycle() {
+                self.ppu.tick(mapper);
+            }
+            self.apu.tick();
+            mapper.tick();
+            self.apu.set_expansion_level(mapper.audio_level());
+            remaining -= 1;
+            elapsed += 1;
+
+            if let Some(addr) = self.apu.dmc_pending_fetch() {
+                let value = self.read_byte(addr);
+                self.apu.dmc_fill_sample_buffer(value);
+                remaining += if self.oam_dma_active {
+                    DMC_DMA_STALL_CYCLES_DURING_OAM_DMA
+                } else {
+                    DMC_DMA_STALL_CYCLES
+                };
+            }
+        }
+
+        elapsed
+    }
+
+    /// Carries out an OAM DMA requested through $4014, if any: 256 bytes
+    /// from page $XX00 are written to OAMDATA while the CPU is halted for
+    /// 513 cycles, or 514 when the transfer starts on an odd CPU cycle.
+    /// Returns the number of cycles the CPU was halted.
+    pub fn run_oam_dma(&mut self, odd_cycle: bool) -> usize {
+        let page = match self.dma.take_pending_page() {
+            Some(page) => page,
+            None => return 0,
+        };
+
+        self.oam_dma_active = true;
+        // One cycle to halt the CPU, plus one to align to a read cycle.
+        let mut elapsed = self.tick(if odd_cycle { 2 } else { 1 });
+        for offset in 0..OAM_DMA_TRANSFER_BYTES {
+            let value = self.read_byte((page as BusAddr) << 8 | offset);
+            elapsed += self.tick(1);
+            self.ppu
+                .write_register(self.cartridge.mapper_mut(), PPU_OAM_DATA_REGISTER, value);
+            elapsed += self.tick(1);
+        }
+        self.oam_dma_active = false;
+
+        elapsed
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.ppu.frame_count()
+    }
+
+    /// Returns whether the PPU raised an NMI since the last call.
+    pub fn take_nmi(&mut self) -> bool {
+        self.ppu.take_nmi()
+    }
+
+    /// State of the IRQ line shared by the APU and the cartridge.
+    pub fn irq(&self) -> bool {
+        self.apu.irq() || self.cartridge.mapper().irq()
+    }
+
+    /// The cartridge's battery-backed memory, if it has any.
+    pub fn save_data(&self) -> Option<&[u8]> {
+        self.cartridge.mapper().save_data()
+    }
+
+    pub fn apu_mut(&mut self) -> &mut APU {
+        self.apu
+    }
+
+    pub fn pad_mut(&mut self) -> &mut Pad {
+        self.pad
+    }
 }
 
 impl<'a> ByteReadable for Bus<'a> {
-    fn read_byte(&self, addr: BusAddr) -> u8 {
-        if addr < 0x800 {
-            self.wram.read_byte(addr)
-        } else if addr < 0x2000 {
-            self.wram.read_byte(addr - 0x0800)
-        } else if addr < 0x4000 {
-            self.ppu.read_byte((addr - 0x2000) % 8)
-        } else if addr == 0x4016 {
+    fn read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr <= WRAM_MIRROR_END_ADDR {
+            self.wram.read_byte(addr % WRAM_SIZE)
+        } else if addr <= PPU_MIRROR_REGISTERS_END_ADDR {
+            self.ppu.read_register(
+                self.cartridge.mapper_mut(),
+                (addr - PPU_REGISTERS_START_ADDR) % PPU_REGISTERS_SIZE,
+            )
+        } else if addr == 0x4015 {
+            self.apu.read_byte(addr)
+        } else if addr == 0x4016 || addr == 0x4017 {
+            self.pad.sense_light(self.ppu);
             self.pad.read_byte(addr)
-        } else if addr >= 0xC000 {
-            if self.program_rom.data.len() <= 0x4000 {
-                self.program_rom.read_byte(addr - 0xC000)
-            } else {
-                self.program_rom.read_byte(addr - 0x8000)
-            }
-        } else if addr >= 0x8000 {
-            self.program_rom.read_byte(addr - 0x8000)
-        } else {
-            // 一旦拡張ROM, RAMは仕様されていない前提とする
+        } else if addr < CARTRIDGE_SPACE_START_ADDR {
             0
+        } else {
+            self.cartridge.mapper_mut().cpu_read_byte(addr)
         }
     }
 }
 
 impl<'a> ByteWritable for Bus<'a> {
     fn write_byte(&mut self, addr: BusAddr, value: u8) {
-        if addr < 0x800 {
-            self.wram.write_byte(addr, value)
-        } else if addr < 0x2000 {
-            self.wram.write_byte(addr - 0x8000, value)
-        } else if addr < 0x2008 {
-            self.ppu.write_byte(addr - 0x2000, value)
+        if addr <= WRAM_MIRROR_END_ADDR {
+            self.wram.write_byte(addr % WRAM_SIZE, value)
+        } else if addr <= PPU_MIRROR_REGISTERS_END_ADDR {
+            let register = (addr - PPU_REGISTERS_START_ADDR) % PPU_REGISTERS_SIZE;
+            let mapper = self.cartridge.mapper_mut();
+            mapper.ppu_register_write(register, value);
+            self.ppu.write_register(mapper, register, value)
         } else if addr == 0x4014 {
             self.dma.write_byte(addr, value)
         } else if addr == 0x4016 {
             self.pad.write_byte(addr, value)
-        } else if 0x4000 <= addr && addr < 0x4020 {
+        } else if addr < CARTRIDGE_SPACE_START_ADDR {
             self.apu.write_byte(addr, value)
         } else {
-            // 一旦拡張ROM, RAMは仕様されていない前提とする
+            self.cartridge.mapper_mut().cpu_write_byte(addr, value)
         }
     }
 }
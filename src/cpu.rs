@@ -6,9 +6,15 @@ use registers::{ProgramCounter, Registers};
 
 use self::instruction::{AddressingMode, InstructionType, OpCode, OpCodeDecoder, Operand};
 
+const STACK_BASE_ADDR: BusAddr = 0x0100;
+const INTERRUPT_CYCLES: usize = 7;
+
 pub struct CPU<'a> {
     registers: Registers,
     bus: &'a mut Bus<'a>,
+    trace: bool,
+    extra_cycles: usize,
+    cycles: u64,
 }
 
 impl<'a> CPU<'a> {
@@ -16,25 +22,95 @@ impl<'a> CPU<'a> {
         Self {
             registers: Registers::new(),
             bus,
+            trace: true,
+            extra_cycles: 0,
+            cycles: 0,
         }
     }
 
+    pub fn bus_mut(&mut self) -> &mut Bus<'a> {
+        self.bus
+    }
+
+    /// Enables or disables logging every executed instruction to stderr.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
     pub fn boot(&mut self) {
         self.registers.reset();
-        self.registers.set_pc(self.reset_interrupt_pc());
+        self.registers.p.set_irq_disable(true);
+        let pc = self.reset_interrupt_pc();
+        self.registers.set_pc(pc);
     }
 
     pub fn reset(&mut self) {
         self.registers.reset();
-        self.registers.set_pc(self.reset_interrupt_pc());
+        self.registers.p.set_irq_disable(true);
+        let pc = self.reset_interrupt_pc();
+        self.registers.set_pc(pc);
     }
 
     pub fn run_single_cycle(&mut self) -> Result<(), String> {
         let tmp_pc = self.registers.pc;
         let opcode = self.fetch_opcode()?;
         let operand = self.fetch_operand(opcode.addressing_mode.clone());
-        eprintln!("[0x{:02X}]: {:?} {:?}", tmp_pc, opcode, operand);
+        if self.trace {
+            eprintln!("[0x{:02X}]: {:?} {:?}", tmp_pc, opcode, operand);
+        }
+        self.extra_cycles = 0;
         self.execute(opcode.clone(), operand);
+        self.idle(opcode.cycles() + self.extra_cycles);
+
+        let odd_cycle = self.cycles % 2 == 1;
+        self.cycles += self.bus.run_oam_dma(odd_cycle) as u64;
+
+        if self.bus.take_nmi() {
+            self.interrupt_nmi();
+        } else if self.bus.irq() && !self.registers.p.irq_disable() {
+            self.interrupt_irq();
+        }
+
+        Ok(())
+    }
+
+    /// Lets the rest of the system run for `cycles` CPU cycles without
+    /// executing instructions.
+    pub fn idle(&mut self, cycles: usize) {
+        self.cycles += self.bus.tick(cycles) as u64;
+    }
+
+    /// Number of CPU cycles elapsed since power on.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn pc(&self) -> ProgramCounter {
+        self.registers.pc
+    }
+
+    /// Sets up a JSR to `addr` with the given A and X, as if it had been
+    /// called from just before `return_addr`. Once the subroutine executes
+    /// its RTS, the PC equals `return_addr`.
+    pub fn call_subroutine(
+        &mut self,
+        addr: ProgramCounter,
+        return_addr: ProgramCounter,
+        a: u8,
+        x: u8,
+    ) {
+        self.push_word(return_addr.wrapping_sub(1));
+        self.registers.a = a;
+        self.registers.x = x;
+        self.registers.set_pc(addr);
+    }
+
+    /// Runs instructions until the PPU finishes the current frame.
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        let frame = self.bus.frame_count();
+        while self.bus.frame_count() == frame {
+            self.run_single_cycle()?;
+        }
 
         Ok(())
     }
@@ -95,14 +171,9 @@ impl<'a> CPU<'a> {
             }
             AddressingMode::Implied => None,
             AddressingMode::Relative => {
-                let offset = self.fetch() as u16;
-                let next_pc = self.registers.pc + 1;
-                let addr: u16;
-                if offset < 0x80 {
-                    addr = next_pc + offset;
-                } else {
-                    addr = (next_pc + offset) - 256;
-                }
+                let offset = self.fetch() as i8;
+                let next_pc = self.registers.pc;
+                let addr = next_pc.wrapping_add(offset as u16);
 
                 Some(Operand::Address(addr))
             }
@@ -113,13 +184,13 @@ impl<'a> CPU<'a> {
                 let next_addr_byte = self.bus.read_byte((base_addr + 1) & 0xFF) as u16;
                 let addr = base_addr_byte + (next_addr_byte << 8);
 
-                Some(Operand::Address(addr as u16))
+                Some(Operand::Address(addr))
             }
             AddressingMode::IndirectIndexed => {
                 let lower_half_addr = self.fetch() as u16;
                 let next_byte = self.bus.read_byte((lower_half_addr + 1) & 0xFF) as u16;
                 let base_addr = (self.bus.read_byte(lower_half_addr) as u16) + (next_byte << 8);
-                let addr = base_addr + (self.registers.y as u16);
+                let addr = base_addr.wrapping_add(self.registers.y as u16);
 
                 Some(Operand::Address(addr))
             }
@@ -157,7 +228,7 @@ impl<'a> CPU<'a> {
                     self.registers.a = self.bus.read_byte(addr);
                 }
                 self.registers.p.set_negative(is_negative(self.registers.a));
-                self.registers.p.set_negative(is_zero(self.registers.a));
+                self.registers.p.set_zero(is_zero(self.registers.a));
             }
             InstructionType::LDX => {
                 if op_code.addressing_mode == AddressingMode::Immediate {
@@ -167,7 +238,7 @@ impl<'a> CPU<'a> {
                     self.registers.x = self.bus.read_byte(addr);
                 }
                 self.registers.p.set_negative(is_negative(self.registers.x));
-                self.registers.p.set_negative(is_zero(self.registers.x));
+                self.registers.p.set_zero(is_zero(self.registers.x));
             }
             InstructionType::LDY => {
                 if op_code.addressing_mode == AddressingMode::Immediate {
@@ -177,7 +248,7 @@ impl<'a> CPU<'a> {
                     self.registers.y = self.bus.read_byte(addr);
                 }
                 self.registers.p.set_negative(is_negative(self.registers.y));
-                self.registers.p.set_negative(is_zero(self.registers.y));
+                self.registers.p.set_zero(is_zero(self.registers.y));
             }
             /* Store */
             InstructionType::STA => {
@@ -282,27 +353,27 @@ impl<'a> CPU<'a> {
                 self.registers.p.set_negative(is_negative(self.registers.x));
             }
             InstructionType::TXS => {
-                self.registers.x = self.registers.s;
+                self.registers.s = self.registers.x;
                 // Not Changing flags
             }
             // Increment & Decrement
             InstructionType::INX => {
-                self.registers.x = self.registers.x.saturating_add(1);
+                self.registers.x = self.registers.x.wrapping_add(1);
                 self.registers.p.set_zero(is_zero(self.registers.x));
                 self.registers.p.set_negative(is_negative(self.registers.x));
             }
             InstructionType::INY => {
-                self.registers.y = self.registers.y.saturating_add(1);
+                self.registers.y = self.registers.y.wrapping_add(1);
                 self.registers.p.set_zero(is_zero(self.registers.y));
                 self.registers.p.set_negative(is_negative(self.registers.y));
             }
             InstructionType::DEX => {
-                self.registers.x = self.registers.x.saturating_sub(1);
+                self.registers.x = self.registers.x.wrapping_sub(1);
                 self.registers.p.set_zero(is_zero(self.registers.x));
                 self.registers.p.set_negative(is_negative(self.registers.x));
             }
             InstructionType::DEY => {
-                self.registers.y = self.registers.y.saturating_sub(1);
+                self.registers.y = self.registers.y.wrapping_sub(1);
                 self.registers.p.set_zero(is_zero(self.registers.y));
                 self.registers.p.set_negative(is_negative(self.registers.y));
             }
@@ -359,24 +430,205 @@ impl<'a> CPU<'a> {
                 if self.registers.p.carry() {
                     operated += 1;
                 }
-                let overflow = !(((a ^ data) & 0x80) != 0) && ((a ^ operated) & 0x80) != 0;
+                let overflow = ((a ^ data) & 0x80) == 0 && ((a ^ operated) & 0x80) != 0;
                 self.registers.a = (operated & 0xFF) as u8;
                 self.registers.p.set_overflow(overflow);
                 self.registers.p.set_carry(operated > 0xFF);
                 self.registers.p.set_negative(is_negative(self.registers.a));
                 self.registers.p.set_zero(is_zero(self.registers.a));
             }
+            InstructionType::SBC => {
+                let data = !self.load_operand_data(&op_code.addressing_mode, operand) as u16;
+                let a = self.registers.a as u16;
+                let mut operated = data + a;
+                if self.registers.p.carry() {
+                    operated += 1;
+                }
+                let overflow = ((a ^ data) & 0x80) == 0 && ((a ^ operated) & 0x80) != 0;
+                self.registers.a = (operated & 0xFF) as u8;
+                self.registers.p.set_overflow(overflow);
+                self.registers.p.set_carry(operated > 0xFF);
+                self.registers.p.set_negative(is_negative(self.registers.a));
+                self.registers.p.set_zero(is_zero(self.registers.a));
+            }
+            /* Compare */
+            InstructionType::CMP => {
+                let data = self.load_operand_data(&op_code.addressing_mode, operand);
+                self.compare(self.registers.a, data);
+            }
+            InstructionType::CPX => {
+                let data = self.load_operand_data(&op_code.addressing_mode, operand);
+                self.compare(self.registers.x, data);
+            }
+            InstructionType::CPY => {
+                let data = self.load_operand_data(&op_code.addressing_mode, operand);
+                self.compare(self.registers.y, data);
+            }
+            InstructionType::BIT => {
+                let data = self.load_operand_data(&op_code.addressing_mode, operand);
+                self.registers.p.set_zero(is_zero(data & self.registers.a));
+                self.registers.p.set_negative(is_negative(data));
+                self.registers.p.set_overflow(data & 0x40 != 0);
+            }
+            /* Shift & Rotate */
+            InstructionType::ASL => {
+                self.read_modify_write(operand, |value, _| (value << 1, value & 0x80 != 0));
+            }
+            InstructionType::LSR => {
+                self.read_modify_write(operand, |value, _| (value >> 1, value & 0x01 != 0));
+            }
+            InstructionType::ROL => {
+                self.read_modify_write(operand, |value, carry| {
+                    ((value << 1) | carry as u8, value & 0x80 != 0)
+                });
+            }
+            InstructionType::ROR => {
+                self.read_modify_write(operand, |value, carry| {
+                    ((value >> 1) | ((carry as u8) << 7), value & 0x01 != 0)
+                });
+            }
+            /* Memory increment & decrement */
+            InstructionType::INC => {
+                let addr = operand.unwrap().unwrap_addr();
+                let value = self.bus.read_byte(addr);
+                self.bus.write_byte(addr, value);
+                let result = value.wrapping_add(1);
+                self.bus.write_byte(addr, result);
+                self.registers.p.set_zero(is_zero(result));
+                self.registers.p.set_negative(is_negative(result));
+            }
+            InstructionType::DEC => {
+                let addr = operand.unwrap().unwrap_addr();
+                let value = self.bus.read_byte(addr);
+                self.bus.write_byte(addr, value);
+                let result = value.wrapping_sub(1);
+                self.bus.write_byte(addr, result);
+                self.registers.p.set_zero(is_zero(result));
+                self.registers.p.set_negative(is_negative(result));
+            }
+            /* Stack */
+            InstructionType::PHA => {
+                self.push(self.registers.a);
+            }
+            InstructionType::PHP => {
+                let status = self.registers.p.to_byte(true);
+                self.push(status);
+            }
+            InstructionType::PLA => {
+                self.registers.a = self.pop();
+                self.registers.p.set_zero(is_zero(self.registers.a));
+                self.registers.p.set_negative(is_negative(self.registers.a));
+            }
+            InstructionType::PLP => {
+                let status = self.pop();
+                self.registers.p.set_byte(status);
+            }
             // Jump
             InstructionType::JMP => {
                 self.registers.pc = operand.unwrap().unwrap_addr();
             }
-            unhandled => {
-                panic!("Not yet supported: {:?}", unhandled);
+            InstructionType::JSR => {
+                let return_addr = self.registers.pc.wrapping_sub(1);
+                self.push_word(return_addr);
+                self.registers.pc = operand.unwrap().unwrap_addr();
+            }
+            InstructionType::RTS => {
+                let return_addr = self.pop_word();
+                self.registers.pc = return_addr.wrapping_add(1);
+            }
+            InstructionType::BRK => {
+                let return_addr = self.registers.pc.wrapping_add(1);
+                self.push_word(return_addr);
+                let status = self.registers.p.to_byte(true);
+                self.push(status);
+                self.registers.p.set_irq_disable(true);
+                self.registers.pc = self.brk_interrupt_pc();
+            }
+            InstructionType::RTI => {
+                let status = self.pop();
+                self.registers.p.set_byte(status);
+                self.registers.pc = self.pop_word();
             }
         }
     }
 
+    fn load_operand_data(
+        &mut self,
+        addressing_mode: &AddressingMode,
+        operand: Option<Operand>,
+    ) -> u8 {
+        if *addressing_mode == AddressingMode::Immediate {
+            operand.unwrap().unwrap_immediate()
+        } else {
+            self.bus.read_byte(operand.unwrap().unwrap_addr())
+        }
+    }
+
+    fn compare(&mut self, register: u8, data: u8) {
+        let result = register.wrapping_sub(data);
+        self.registers.p.set_carry(register >= data);
+        self.registers.p.set_zero(is_zero(result));
+        self.registers.p.set_negative(is_negative(result));
+    }
+
+    /// Applies a shift or rotate to the accumulator (no operand) or to memory.
+    /// `operation` receives the value and the current carry and returns the
+    /// result and the new carry.
+    fn read_modify_write<F>(&mut self, operand: Option<Operand>, operation: F)
+    where
+        F: Fn(u8, bool) -> (u8, bool),
+    {
+        let carry = self.registers.p.carry();
+        let result = match operand {
+            None => {
+                let (result, new_carry) = operation(self.registers.a, carry);
+                self.registers.a = result;
+                self.registers.p.set_carry(new_carry);
+                result
+            }
+            Some(operand) => {
+                let addr = operand.unwrap_addr();
+                let value = self.bus.read_byte(addr);
+                // Like the 6502, write the unmodified value back first.
+                self.bus.write_byte(addr, value);
+                let (result, new_carry) = operation(value, carry);
+                self.bus.write_byte(addr, result);
+                self.registers.p.set_carry(new_carry);
+                result
+            }
+        };
+        self.registers.p.set_zero(is_zero(result));
+        self.registers.p.set_negative(is_negative(result));
+    }
+
+    fn push(&mut self, value: u8) {
+        self.bus
+            .write_byte(STACK_BASE_ADDR | self.registers.s as u16, value);
+        self.registers.s = self.registers.s.wrapping_sub(1);
+    }
+
+    fn pop(&mut self) -> u8 {
+        self.registers.s = self.registers.s.wrapping_add(1);
+        self.bus
+            .read_byte(STACK_BASE_ADDR | self.registers.s as u16)
+    }
+
+    fn push_word(&mut self, value: u16) {
+        self.push((value >> 8) as u8);
+        self.push((value & 0xFF) as u8);
+    }
+
+    fn pop_word(&mut self) -> u16 {
+        let lower_byte = self.pop() as u16;
+        let upper_byte = self.pop() as u16;
+        (upper_byte << 8) | lower_byte
+    }
+
     fn branch(&mut self, new_pc: ProgramCounter) {
+        self.extra_cycles += 1;
+        if self.registers.pc & 0xFF00 != new_pc & 0xFF00 {
+            self.extra_cycles += 1;
+        }
         self.registers.set_pc(new_pc);
     }
 
@@ -391,24 +643,44 @@ impl<'a> CPU<'a> {
         }
     }
 
-    fn nmi_interrupt_pc(&self) -> ProgramCounter {
+    fn interrupt_nmi(&mut self) {
+        self.push_word(self.registers.pc);
+        let status = self.registers.p.to_byte(false);
+        self.push(status);
+        self.registers.p.set_irq_disable(true);
+        self.registers.pc = self.nmi_interrupt_pc();
+        self.idle(INTERRUPT_CYCLES);
+    }
+
+    /// IRQs are level triggered: the handler must acknowledge the source
+    /// before returning, or it runs again right away.
+    fn interrupt_irq(&mut self) {
+        self.push_word(self.registers.pc);
+        let status = self.registers.p.to_byte(false);
+        self.push(status);
+        self.registers.p.set_irq_disable(true);
+        self.registers.pc = self.irq_interrupt_pc();
+        self.idle(INTERRUPT_CYCLES);
+    }
+
+    fn nmi_interrupt_pc(&mut self) -> ProgramCounter {
         self.read_interrupt_pc(0xFFFA, 0xFFFB)
     }
 
-    fn reset_interrupt_pc(&self) -> ProgramCounter {
+    fn reset_interrupt_pc(&mut self) -> ProgramCounter {
         self.read_interrupt_pc(0xFFFC, 0xFFFD)
     }
 
-    fn irq_interrupt_pc(&self) -> ProgramCounter {
+    fn irq_interrupt_pc(&mut self) -> ProgramCounter {
         self.read_interrupt_pc(0xFFFE, 0xFFFF)
     }
 
-    fn brk_interrupt_pc(&self) -> ProgramCounter {
+    fn brk_interrupt_pc(&mut self) -> ProgramCounter {
         self.read_interrupt_pc(0xFFFE, 0xFFFF)
     }
 
     fn read_interrupt_pc(
-        &self,
+        &mut self,
         lower_byte_addr: BusAddr,
         upper_byte_addr: BusAddr,
     ) -> ProgramCounter {
@@ -428,3 +700,174 @@ fn is_negative(value: u8) -> bool {
 fn is_zero(value: u8) -> bool {
     value == 0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mapper, Mirroring, Pad, APU, DMA, PPU, RAM};
+
+    const PROGRAM_ADDR: u16 = 0x8000;
+    const BRK_HANDLER_ADDR: u16 = 0x9000;
+
+    /// 32KB of PRG-ROM starting with `program`, with the reset vector
+    /// pointing at it and the IRQ/BRK vector at `BRK_HANDLER_ADDR`.
+    struct TestCartridge {
+        prg_rom: Vec<u8>,
+    }
+
+    impl TestCartridge {
+        fn new(program: &[u8], brk_handler: &[u8]) -> TestCartridge {
+            let mut prg_rom = vec![0xEA; 0x8000];
+            prg_rom[..program.len()].copy_from_slice(program);
+            let handler = (BRK_HANDLER_ADDR - PROGRAM_ADDR) as usize;
+            prg_rom[handler..handler + brk_handler.len()].copy_from_slice(brk_handler);
+            prg_rom[0x7FFC..].copy_from_slice(&[0x00, 0x80, 0x00, 0x90]);
+            TestCartridge { prg_rom }
+        }
+    }
+
+    impl Mapper for TestCartridge {
+        fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+            if addr >= PROGRAM_ADDR {
+                self.prg_rom[(addr - PROGRAM_ADDR) as usize]
+            } else {
+                0
+            }
+        }
+
+        fn cpu_write_byte(&mut self, _addr: BusAddr, _value: u8) {}
+
+        fn ppu_read_byte(&mut self, _addr: BusAddr) -> u8 {
+            0
+        }
+
+        fn ppu_write_byte(&mut self, _addr: BusAddr, _value: u8) {}
+
+        fn mirroring(&self) -> Mirroring {
+            Mirroring::Horizontal
+        }
+    }
+
+    /// Boots a CPU on `program` with an empty stack at $01FF, then runs
+    /// `test` on it.
+    fn with_cpu(program: &[u8], brk_handler: &[u8], test: impl FnOnce(&mut CPU)) {
+        let mut cartridge = TestCartridge::new(program, brk_handler);
+        let mut ram = RAM::new();
+        let mut ppu = PPU::new();
+        let mut apu = APU::new();
+        let mut pad = Pad::new();
+        let mut dma = DMA::new();
+        let mut bus = Bus::new(
+            &mut ram,
+            &mut cartridge,
+            &mut ppu,
+            &mut apu,
+            &mut pad,
+            &mut dma,
+        );
+        let mut cpu = CPU::new(&mut bus);
+        cpu.boot();
+        cpu.registers.s = 0xFF;
+        test(&mut cpu);
+    }
+
+    fn step(cpu: &mut CPU, instructions: usize) {
+        for _ in 0..instructions {
+            cpu.run_single_cycle().unwrap();
+        }
+    }
+
+    /// N, V, Z and C.
+    fn flags(cpu: &CPU) -> (bool, bool, bool, bool) {
+        let p = &cpu.registers.p;
+        (p.negative(), p.overflow(), p.zero(), p.carry())
+    }
+
+    #[test]
+    fn sbc_borrows_and_sets_overflow() {
+        // LDA #$50; SEC; SBC #$B0
+        with_cpu(&[0xA9, 0x50, 0x38, 0xE9, 0xB0], &[], |cpu| {
+            step(cpu, 3);
+            assert_eq!(cpu.registers.a, 0xA0);
+            assert_eq!(flags(cpu), (true, true, false, false));
+        });
+    }
+
+    #[test]
+    fn sbc_to_zero_keeps_carry() {
+        // LDA #$05; SEC; SBC #$05
+        with_cpu(&[0xA9, 0x05, 0x38, 0xE9, 0x05], &[], |cpu| {
+            step(cpu, 3);
+            assert_eq!(cpu.registers.a, 0x00);
+            assert_eq!(flags(cpu), (false, false, true, true));
+        });
+    }
+
+    #[test]
+    fn sbc_without_carry_subtracts_one_more() {
+        // LDA #$00; CLC; SBC #$00
+        with_cpu(&[0xA9, 0x00, 0x18, 0xE9, 0x00], &[], |cpu| {
+            step(cpu, 3);
+            assert_eq!(cpu.registers.a, 0xFF);
+            assert_eq!(flags(cpu), (true, false, false, false));
+        });
+    }
+
+    #[test]
+    fn cmp_sets_carry_zero_and_negative() {
+        // LDA #$40; CMP #$30; CMP #$40; CMP #$50
+        with_cpu(
+            &[0xA9, 0x40, 0xC9, 0x30, 0xC9, 0x40, 0xC9, 0x50],
+            &[],
+            |cpu| {
+                step(cpu, 2);
+                assert_eq!(flags(cpu), (false, false, false, true));
+                step(cpu, 1);
+                assert_eq!(flags(cpu), (false, false, true, true));
+                step(cpu, 1);
+                assert_eq!(flags(cpu), (true, false, false, false));
+                assert_eq!(cpu.registers.a, 0x40);
+            },
+        );
+    }
+
+    #[test]
+    fn jsr_pushes_return_address_minus_one_and_rts_pops_it() {
+        // JSR $8010, with RTS at $8010.
+        let mut program = vec![0x20, 0x10, 0x80];
+        program.resize(0x10, 0xEA);
+        program.push(0x60);
+        with_cpu(&program, &[], |cpu| {
+            step(cpu, 1);
+            assert_eq!(cpu.pc(), 0x8010);
+            assert_eq!(cpu.registers.s, 0xFD);
+            assert_eq!(cpu.bus.read_byte(0x01FF), 0x80);
+            assert_eq!(cpu.bus.read_byte(0x01FE), 0x02);
+
+            step(cpu, 1);
+            assert_eq!(cpu.pc(), 0x8003);
+            assert_eq!(cpu.registers.s, 0xFF);
+        });
+    }
+
+    #[test]
+    fn brk_pushes_pc_and_status_and_rti_restores_them() {
+        // SEC; BRK, with RTI at the BRK handler.
+        with_cpu(&[0x38, 0x00], &[0x40], |cpu| {
+            step(cpu, 2);
+            assert_eq!(cpu.pc(), BRK_HANDLER_ADDR);
+            assert_eq!(cpu.registers.s, 0xFC);
+            assert_eq!(cpu.bus.read_byte(0x01FF), 0x80);
+            assert_eq!(cpu.bus.read_byte(0x01FE), 0x03);
+            // Bits 5 and 4 (break) are set, along with I and C.
+            assert_eq!(cpu.bus.read_byte(0x01FD), 0x35);
+            assert!(cpu.registers.p.irq_disable());
+
+            cpu.registers.p.set_carry(false);
+            step(cpu, 1);
+            assert_eq!(cpu.pc(), 0x8003);
+            assert_eq!(cpu.registers.s, 0xFF);
+            assert!(cpu.registers.p.carry());
+        });
+    }
+}
@@ -41,7 +41,6 @@ pub enum InstructionType {
     CLI,
     SEI,
     CLD,
-    CED,
     CLV,
     LDA,
     LDX,
@@ -79,26 +78,6 @@ pub enum AddressingMode {
     AbsoluteIndirect,
 }
 
-impl AddressingMode {
-    pub fn number_of_operands(&self) -> usize {
-        match *self {
-            AddressingMode::Accumulator => 1,
-            AddressingMode::Immediate => 1,
-            AddressingMode::Absolute => 2,
-            AddressingMode::ZeroPage => 1,
-            AddressingMode::ZeroPageX => 1,
-            AddressingMode::ZeroPageY => 1,
-            AddressingMode::AbsoluteX => 2,
-            AddressingMode::AbsoluteY => 2,
-            AddressingMode::Implied => 0,
-            AddressingMode::Relative => 1,
-            AddressingMode::IndexedIndirect => 1,
-            AddressingMode::IndirectIndexed => 1,
-            AddressingMode::AbsoluteIndirect => 2,
-        }
-    }
-}
-
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OpCode {
     pub instruction_type: InstructionType,
@@ -113,8 +92,35 @@ impl OpCode {
         }
     }
 
-    pub fn number_of_operands(&self) -> usize {
-        self.addressing_mode.number_of_operands()
+    /// Base cycle count. Taken branches add their penalty separately, and
+    /// page-crossing penalties on indexed reads are not modelled yet.
+    pub fn cycles(&self) -> usize {
+        use AddressingMode::*;
+        use InstructionType::*;
+
+        let read_modify_write = matches!(self.instruction_type, ASL | LSR | ROL | ROR | INC | DEC);
+        match (&self.instruction_type, &self.addressing_mode) {
+            (BRK, _) => 7,
+            (JSR, _) | (RTS, _) | (RTI, _) => 6,
+            (PHA, _) | (PHP, _) => 3,
+            (PLA, _) | (PLP, _) => 4,
+            (JMP, Absolute) => 3,
+            (JMP, _) => 5,
+            (_, Implied) | (_, Accumulator) | (_, Immediate) | (_, Relative) => 2,
+            (_, ZeroPage) if read_modify_write => 5,
+            (_, ZeroPage) => 3,
+            (_, ZeroPageX) | (_, ZeroPageY) if read_modify_write => 6,
+            (_, ZeroPageX) | (_, ZeroPageY) => 4,
+            (_, Absolute) if read_modify_write => 6,
+            (_, Absolute) => 4,
+            (_, AbsoluteX) | (_, AbsoluteY) if read_modify_write => 7,
+            (STA, AbsoluteX) | (STA, AbsoluteY) => 5,
+            (_, AbsoluteX) | (_, AbsoluteY) => 4,
+            (_, IndexedIndirect) => 6,
+            (STA, IndirectIndexed) => 6,
+            (_, IndirectIndexed) => 5,
+            (_, AbsoluteIndirect) => 5,
+        }
     }
 }
 
@@ -127,7 +133,7 @@ pub enum Operand {
 impl Operand {
     pub fn unwrap_addr(&self) -> u16 {
         if let Operand::Address(addr) = *self {
-            return addr;
+            addr
         } else {
             panic!("Expected Operand::Address");
         }
@@ -135,7 +141,7 @@ impl Operand {
 
     pub fn unwrap_immediate(&self) -> u8 {
         if let Operand::Immediate(data) = *self {
-            return data;
+            data
         } else {
             panic!("Expected Operand::Immediate");
         }
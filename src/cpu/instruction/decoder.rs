@@ -39,7 +39,7 @@ static DECODE_TABLE: Lazy<HashMap<u8, OpCode>> = Lazy::new(|| {
         (0xE1, InstructionType::SBC, AddressingMode::IndexedIndirect),
         (0xF1, InstructionType::SBC, AddressingMode::IndirectIndexed),
         // 0xX2
-        (0xA2, InstructionType::LDA, AddressingMode::Immediate),
+        (0xA2, InstructionType::LDX, AddressingMode::Immediate),
         // 0xX4
         (0x24, InstructionType::BIT, AddressingMode::ZeroPage),
         (0x84, InstructionType::STY, AddressingMode::ZeroPage),
@@ -108,7 +108,7 @@ static DECODE_TABLE: Lazy<HashMap<u8, OpCode>> = Lazy::new(|| {
         (0x59, InstructionType::EOR, AddressingMode::AbsoluteY),
         (0x69, InstructionType::ADC, AddressingMode::Immediate),
         (0x79, InstructionType::ADC, AddressingMode::AbsoluteY),
-        (0x89, InstructionType::STA, AddressingMode::Immediate),
+        (0x89, InstructionType::NOP, AddressingMode::Immediate),
         (0x99, InstructionType::STA, AddressingMode::AbsoluteY),
         (0xA9, InstructionType::LDA, AddressingMode::Immediate),
         (0xB9, InstructionType::LDA, AddressingMode::AbsoluteY),
@@ -164,7 +164,7 @@ static DECODE_TABLE: Lazy<HashMap<u8, OpCode>> = Lazy::new(|| {
         (0x7E, InstructionType::ROR, AddressingMode::AbsoluteX),
         (0x8E, InstructionType::STX, AddressingMode::Absolute),
         (0xAE, InstructionType::LDX, AddressingMode::Absolute),
-        (0xBE, InstructionType::LDX, AddressingMode::AbsoluteX),
+        (0xBE, InstructionType::LDX, AddressingMode::AbsoluteY),
         (0xCE, InstructionType::DEC, AddressingMode::Absolute),
         (0xDE, InstructionType::DEC, AddressingMode::AbsoluteX),
         (0xEE, InstructionType::INC, AddressingMode::Absolute),
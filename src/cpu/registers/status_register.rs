@@ -1,8 +1,6 @@
 pub struct StatusRegister {
     negative: bool,
     overflow: bool,
-    reserved: bool,
-    break_mode: bool,
     decimal_mode: bool,
     irq_disable: bool,
     zero: bool,
@@ -14,8 +12,6 @@ impl StatusRegister {
         Self {
             negative: false,
             overflow: false,
-            reserved: true,
-            break_mode: false,
             decimal_mode: false,
             irq_disable: false,
             zero: false,
@@ -26,14 +22,36 @@ impl StatusRegister {
     pub fn reset(&mut self) {
         self.negative = false;
         self.overflow = false;
-        self.reserved = true;
-        self.break_mode = false;
         self.decimal_mode = false;
         self.irq_disable = false;
         self.zero = false;
         self.carry = false;
     }
 
+    /// Packs the flags as pushed by PHP/BRK (`break_flag` set) or by a
+    /// hardware interrupt (`break_flag` clear).
+    pub fn to_byte(&self, break_flag: bool) -> u8 {
+        (self.negative as u8) << 7
+            | (self.overflow as u8) << 6
+            | 1 << 5
+            | (break_flag as u8) << 4
+            | (self.decimal_mode as u8) << 3
+            | (self.irq_disable as u8) << 2
+            | (self.zero as u8) << 1
+            | self.carry as u8
+    }
+
+    /// Restores the flags as pulled by PLP/RTI. Bits 4 and 5 do not exist in
+    /// the register and are ignored.
+    pub fn set_byte(&mut self, value: u8) {
+        self.negative = value & 0x80 != 0;
+        self.overflow = value & 0x40 != 0;
+        self.decimal_mode = value & 0x08 != 0;
+        self.irq_disable = value & 0x04 != 0;
+        self.zero = value & 0x02 != 0;
+        self.carry = value & 0x01 != 0;
+    }
+
     pub fn negative(&self) -> bool {
         self.negative
     }
@@ -50,22 +68,6 @@ impl StatusRegister {
         self.overflow = value;
     }
 
-    pub fn reserved(&self) -> bool {
-        self.reserved
-    }
-
-    pub fn break_mode(&self) -> bool {
-        self.break_mode
-    }
-
-    pub fn set_break_mode(&mut self, value: bool) {
-        self.break_mode = value;
-    }
-
-    pub fn decimal_mode(&self) -> bool {
-        self.decimal_mode
-    }
-
     pub fn set_decimal_mode(&mut self, value: bool) {
         self.decimal_mode = value;
     }
@@ -94,5 +96,3 @@ impl StatusRegister {
         self.carry = value;
     }
 }
-
-pub struct Registers {}
@@ -1,21 +1,32 @@
 use crate::bus::{BusAddr, ByteReadable, ByteWritable};
 
-pub struct DMA;
+pub const OAM_DMA_TRANSFER_BYTES: u16 = 256;
+
+/// The OAM DMA unit behind $4014. A write latches the source page; the bus
+/// then performs the transfer while the CPU is halted.
+pub struct DMA {
+    pending_page: Option<u8>,
+}
 
 impl DMA {
     pub fn new() -> Self {
-        Self
+        Self { pending_page: None }
+    }
+
+    pub fn take_pending_page(&mut self) -> Option<u8> {
+        self.pending_page.take()
     }
 }
 
 impl ByteReadable for DMA {
-    fn read_byte(&self, _addr: BusAddr) -> u8 {
-        todo!()
+    fn read_byte(&mut self, _addr: BusAddr) -> u8 {
+        // $4014 is write-only.
+        0
     }
 }
 
 impl ByteWritable for DMA {
-    fn write_byte(&mut self, _addr: BusAddr, _value: u8) {
-        todo!()
+    fn write_byte(&mut self, _addr: BusAddr, value: u8) {
+        self.pending_page = Some(value);
     }
 }
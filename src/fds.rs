@@ -0,0 +1,300 @@
+mod adapter;
+mod audio;
+
+pub use adapter::FDSAdapter;
+
+use crate::patch::Patch;
+
+const FWNES_HEADER_START: [u8; 4] = [0x46, 0x44, 0x53, 0x1A]; // "FDS" followed by MS-DOS EOF
+const FWNES_HEADER_BYTES: usize = 16;
+/// Bytes of block data on one side of a .fds image, without gaps or CRCs.
+pub const SIDE_BYTES: usize = 65500;
+pub const BIOS_BYTES: usize = 0x2000;
+
+const BLOCK_DISK_INFO: u8 = 1;
+const BLOCK_FILE_AMOUNT: u8 = 2;
+const BLOCK_FILE_HEADER: u8 = 3;
+const BLOCK_FILE_DATA: u8 = 4;
+/// Offset of the file size within a file header block.
+const FILE_HEADER_SIZE_OFFSET: usize = 13;
+
+/// Zero bytes before the first block (28300 bits) and after each block
+/// (976 bits), as the drive sees them.
+const LEADING_GAP_BYTES: usize = 28300 / 8;
+const BLOCK_GAP_BYTES: usize = 976 / 8;
+const BLOCK_START_MARK: u8 = 0x80;
+const CRC_BYTES: usize = 2;
+
+/// A Famicom Disk System image in the .fds layout, with or without the
+/// 16-byte fwNES header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FDS {
+    /// Block data of each side, `SIDE_BYTES` long.
+    pub sides: Vec<Vec<u8>>,
+    header: bool,
+}
+
+impl FDS {
+    pub fn is_fds(data: &[u8]) -> bool {
+        data.starts_with(&FWNES_HEADER_START)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<FDS, String> {
+        let header = FDS::is_fds(data);
+        let data = if header {
+            data.get(FWNES_HEADER_BYTES..)
+                .ok_or("FDS header must be 16 bytes long")?
+        } else {
+            data
+        };
+        if data.is_empty() || data.len() % SIDE_BYTES != 0 {
+            return Err(format!(
+                "FDS image must be a multiple of {} bytes long, but was {} bytes long",
+                SIDE_BYTES,
+                data.len()
+            ));
+        }
+
+        let sides: Vec<Vec<u8>> = data.chunks(SIDE_BYTES).map(<[u8]>::to_vec).collect();
+        for (index, side) in sides.iter().enumerate() {
+            if side[0] != BLOCK_DISK_INFO {
+                return Err(format!(
+                    "FDS side {} does not start with a disk info block",
+                    index
+                ));
+            }
+        }
+        Ok(FDS { sides, header })
+    }
+
+    /// The image as it would be saved, keeping the header if it had one.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        if self.header {
+            data.extend_from_slice(&FWNES_HEADER_START);
+            data.push(self.sides.len() as u8);
+            data.resize(FWNES_HEADER_BYTES, 0);
+        }
+        for side in self.sides.iter() {
+            data.extend_from_slice(side);
+        }
+        data
+    }
+
+    /// Path of the diff recording what the game wrote to the disk at
+    /// `image_path`.
+    pub fn save_path(image_path: &str) -> String {
+        format!("{}.ips", image_path)
+    }
+
+    /// Applies the diff saved next to `image_path`, if there is one.
+    pub fn load_save(&mut self, image_path: &str) -> Result<bool, String> {
+        let save_path = FDS::save_path(image_path);
+        if !std::path::Path::new(&save_path).exists() {
+            return Ok(false);
+        }
+        let patch = Patch::load(&save_path)?;
+        let data = patch
+            .apply(&self.to_bytes())
+            .map_err(|message| format!("{}: {}", save_path, message))?;
+        *self = FDS::parse(&data).map_err(|message| format!("{}: {}", save_path, message))?;
+        Ok(true)
+    }
+
+    /// Saves the difference between `original` and this image next to
+    /// `image_path` as an IPS patch, leaving the image itself untouched.
+    pub fn save(&self, original: &FDS, image_path: &str) -> Result<(), String> {
+        Patch::ips_diff(&original.to_bytes(), &self.to_bytes()).save(&FDS::save_path(image_path))
+    }
+}
+
+/// Lays out the blocks of a side the way the drive reads them: each block
+/// is preceded by a gap and a start mark and followed by its CRC. The free
+/// space of the side is kept as gap at the end so that games can append
+/// files.
+fn side_to_track(side: &[u8]) -> Vec<u8> {
+    let mut track = vec![0; LEADING_GAP_BYTES];
+    let mut offset = 0;
+    let mut file_size = 0;
+    while let Some(length) = block_length(side, offset, file_size) {
+        let block = &side[offset..offset + length];
+        if block[0] == BLOCK_FILE_HEADER {
+            file_size = u16::from_le_bytes([
+                block[FILE_HEADER_SIZE_OFFSET],
+                block[FILE_HEADER_SIZE_OFFSET + 1],
+            ]) as usize;
+        }
+        track.push(BLOCK_START_MARK);
+        track.extend_from_slice(block);
+        track.extend_from_slice(&block_crc(block).to_le_bytes());
+        track.resize(track.len() + BLOCK_GAP_BYTES, 0);
+        offset += length;
+    }
+    track.resize(track.len() + SIDE_BYTES - offset, 0);
+    track
+}
+
+/// Strips the gaps, start marks and CRCs `side_to_track` added, including
+/// those of blocks the game wrote since. Fails if the game wrote more
+/// blocks than a side of a .fds image can hold.
+fn track_to_side(track: &[u8]) -> Result<Vec<u8>, String> {
+    let mut side = Vec::with_capacity(SIDE_BYTES);
+    let mut position = 0;
+    let mut file_size = 0;
+    loop {
+        while track.get(position) == Some(&0) {
+            position += 1;
+        }
+        if track.get(position) != Some(&BLOCK_START_MARK) {
+            break;
+        }
+        position += 1;
+        let length = match block_length(track, position, file_size) {
+            Some(length) => length,
+            None => break,
+        };
+        let block = &track[position..position + length];
+        if block[0] == BLOCK_FILE_HEADER {
+            file_size = u16::from_le_bytes([
+                block[FILE_HEADER_SIZE_OFFSET],
+                block[FILE_HEADER_SIZE_OFFSET + 1],
+            ]) as usize;
+        }
+        side.extend_from_slice(block);
+        position += length + CRC_BYTES;
+    }
+    if side.len() > SIDE_BYTES {
+        return Err(format!(
+            "Disk side holds {} bytes of blocks, more than the {} bytes a .fds side can store",
+            side.len(),
+            SIDE_BYTES
+        ));
+    }
+    side.resize(SIDE_BYTES, 0);
+    Ok(side)
+}
+
+/// Length of the block starting at `offset`, or `None` if there is no
+/// valid complete block there. `file_size` comes from the preceding file
+/// header.
+fn block_length(data: &[u8], offset: usize, file_size: usize) -> Option<usize> {
+    let length = match *data.get(offset)? {
+        BLOCK_DISK_INFO => 56,
+        BLOCK_FILE_AMOUNT => 2,
+        BLOCK_FILE_HEADER => 16,
+        BLOCK_FILE_DATA => 1 + file_size,
+        _ => return None,
+    };
+    data.get(offset..offset + length).map(|_| length)
+}
+
+/// The CRC the drive appends to a block, computed over the start mark, the
+/// block and two zero bytes.
+fn block_crc(block: &[u8]) -> u16 {
+    let mut crc = 0;
+    crc = update_crc(crc, BLOCK_START_MARK);
+    for byte in block.iter().chain(&[0, 0]) {
+        crc = update_crc(crc, *byte);
+    }
+    crc
+}
+
+/// CRC-16/KERMIT, fed least significant bit first like the drive does.
+fn update_crc(mut crc: u16, value: u8) -> u16 {
+    for bit in 0..8 {
+        let carry = crc & 0x0001 != 0;
+        crc >>= 1;
+        if carry {
+            crc ^= 0x8408;
+        }
+        if value & (1 << bit) != 0 {
+            crc ^= 0x8000;
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A side with a disk info block and one four-byte file.
+    fn side() -> Vec<u8> {
+        let mut side = vec![BLOCK_DISK_INFO];
+        side.extend_from_slice(b"*NINTENDO-HVC*");
+        side.resize(56, 0);
+        side.extend_from_slice(&[BLOCK_FILE_AMOUNT, 1]);
+        let mut file_header = [0; 16];
+        file_header[0] = BLOCK_FILE_HEADER;
+        file_header[3..11].copy_from_slice(b"FILENAME");
+        file_header[FILE_HEADER_SIZE_OFFSET] = 4;
+        side.extend_from_slice(&file_header);
+        side.extend_from_slice(&[BLOCK_FILE_DATA, 0xDE, 0xAD, 0xBE, 0xEF]);
+        side.resize(SIDE_BYTES, 0);
+        side
+    }
+
+    #[test]
+    fn side_round_trips_through_track() {
+        let side = side();
+        let track = side_to_track(&side);
+        assert_eq!(track[LEADING_GAP_BYTES], BLOCK_START_MARK);
+        assert_eq!(track[LEADING_GAP_BYTES + 1], BLOCK_DISK_INFO);
+        assert_eq!(track_to_side(&track).unwrap(), side);
+    }
+
+    #[test]
+    fn track_keeps_blocks_written_to_free_space() {
+        let mut track = side_to_track(&side());
+        let end = track.iter().rposition(|byte| *byte != 0).unwrap() + 1;
+        let block = [BLOCK_FILE_AMOUNT, 2];
+        track[end + BLOCK_GAP_BYTES] = BLOCK_START_MARK;
+        track[end + BLOCK_GAP_BYTES + 1..end + BLOCK_GAP_BYTES + 3].copy_from_slice(&block);
+
+        let side = track_to_side(&track).unwrap();
+        assert_eq!(side[56 + 2 + 16 + 5..56 + 2 + 16 + 7], block);
+    }
+
+    #[test]
+    fn track_to_side_rejects_overfull_track() {
+        let mut track = Vec::new();
+        for _ in 0..SIDE_BYTES / 2 + 1 {
+            track.extend_from_slice(&[BLOCK_START_MARK, BLOCK_FILE_AMOUNT, 1, 0, 0]);
+        }
+        let error = track_to_side(&track).unwrap_err();
+        assert!(error.contains("more than"), "{}", error);
+    }
+
+    #[test]
+    fn block_crc_matches_crc16_kermit() {
+        // CRC-16/KERMIT check value, with the start mark fed as data.
+        let crc = b"123456789"
+            .iter()
+            .chain(&[0, 0])
+            .fold(0, |crc, byte| update_crc(crc, *byte));
+        assert_eq!(crc, 0x2189);
+        // CRC-16/KERMIT of $80 $02 $01.
+        assert_eq!(block_crc(&[BLOCK_FILE_AMOUNT, 1]), 0x2ED5);
+    }
+
+    #[test]
+    fn saves_and_loads_disk_writes() {
+        let original = FDS::parse(&side()).unwrap();
+        let mut written = original.clone();
+        written.sides[0][56 + 1] = 2;
+        written.sides[0][SIDE_BYTES - 1] = 0xFF;
+
+        let image_path = std::env::temp_dir()
+            .join(format!("nes-rs-fds-test-{}.fds", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut loaded = original.clone();
+        assert!(!loaded.load_save(&image_path).unwrap());
+
+        written.save(&original, &image_path).unwrap();
+        let result = loaded.load_save(&image_path);
+        std::fs::remove_file(FDS::save_path(&image_path)).unwrap();
+        assert!(result.unwrap());
+        assert_eq!(loaded, written);
+    }
+}
@@ -0,0 +1,436 @@
+use super::{audio::FDSAudio, side_to_track, track_to_side, update_crc, BIOS_BYTES, FDS};
+use crate::{bus::BusAddr, mapper::Mapper, ppu::Mirroring};
+
+const PRG_RAM_START_ADDR: BusAddr = 0x6000;
+const PRG_RAM_SIZE: usize = 0x8000; // 32KB
+const CHR_RAM_SIZE: usize = 0x2000; // 8KB
+const BIOS_START_ADDR: BusAddr = 0xE000;
+const AUDIO_START_ADDR: BusAddr = 0x4040;
+const AUDIO_END_ADDR: BusAddr = 0x409F;
+
+/// CPU cycles from the motor starting until the head reaches the first
+/// byte of the track.
+const SPIN_UP_CYCLES: usize = 50000;
+/// CPU cycles per byte at the drive's 96.4 kbit/s.
+const BYTE_CYCLES: usize = 150;
+/// How long the drive reports no disk when switching sides, so that the
+/// BIOS notices the change. About half a second.
+const SIDE_SWITCH_CYCLES: usize = 900_000;
+
+/// The RAM adapter plugged into the cartridge slot: 32KB of PRG-RAM at
+/// $6000-$DFFF, the 8KB BIOS at $E000-$FFFF, 8KB of CHR-RAM, a timer IRQ,
+/// the disk drive interface and the wavetable sound channel.
+pub struct FDSAdapter {
+    bios: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr_ram: Vec<u8>,
+    /// Each side as the drive sees it, with gaps and CRCs.
+    tracks: Vec<Vec<u8>>,
+    side: Option<usize>,
+    pending_side: Option<(usize, usize)>,
+    audio: FDSAudio,
+
+    timer_reload: u16,
+    timer_counter: u16,
+    timer_repeat: bool,
+    timer_enabled: bool,
+    timer_irq: bool,
+    disk_registers_enabled: bool,
+    sound_registers_enabled: bool,
+    external_output: u8,
+
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    mirroring: Mirroring,
+    crc_control: bool,
+    previous_crc_control: bool,
+    transfer_enabled: bool,
+    disk_irq_enabled: bool,
+    disk_irq: bool,
+    write_data: u8,
+    read_data: u8,
+    transfer_complete: bool,
+    gap_ended: bool,
+    crc: u16,
+
+    head_position: usize,
+    end_of_head: bool,
+    scanning: bool,
+    delay: usize,
+    modified: bool,
+}
+
+impl FDSAdapter {
+    /// An adapter with `bios` mapped at $E000 and side 0 of `fds` in the
+    /// drive.
+    pub fn new(bios: &[u8], fds: &FDS) -> Result<FDSAdapter, String> {
+        if bios.len() != BIOS_BYTES {
+            return Err(format!(
+                "FDS BIOS must be {} bytes long, but was {} bytes long",
+                BIOS_BYTES,
+                bios.len()
+            ));
+        }
+        Ok(FDSAdapter {
+            bios: bios.to_vec(),
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr_ram: vec![0; CHR_RAM_SIZE],
+            tracks: fds.sides.iter().map(|side| side_to_track(side)).collect(),
+            side: Some(0),
+            pending_side: None,
+            audio: FDSAudio::new(),
+            timer_reload: 0,
+            timer_counter: 0,
+            timer_repeat: false,
+            timer_enabled: false,
+            timer_irq: false,
+            disk_registers_enabled: false,
+            sound_registers_enabled: false,
+            external_output: 0,
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            mirroring: Mirroring::Horizontal,
+            crc_control: false,
+            previous_crc_control: false,
+            transfer_enabled: false,
+            disk_irq_enabled: false,
+            disk_irq: false,
+            write_data: 0,
+            read_data: 0,
+            transfer_complete: false,
+            gap_ended: false,
+            crc: 0,
+            head_position: 0,
+            end_of_head: true,
+            scanning: false,
+            delay: 0,
+            modified: false,
+        })
+    }
+
+    pub fn side_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// The side in the drive, or `None` while the drive is empty.
+    pub fn side(&self) -> Option<usize> {
+        self.side
+    }
+
+    pub fn eject(&mut self) {
+        self.side = None;
+        self.pending_side = None;
+    }
+
+    /// Puts `side` in the drive. Use `switch_side` to flip or change disks
+    /// while a side is inserted.
+    pub fn insert(&mut self, side: usize) -> Result<(), String> {
+        if side >= self.tracks.len() {
+            return Err(format!(
+                "Side {} is out of range 0-{}",
+                side,
+                self.tracks.len() - 1
+            ));
+        }
+        self.side = Some(side);
+        self.pending_side = None;
+        self.end_of_head = true;
+        Ok(())
+    }
+
+    /// Ejects the current side and inserts `side` once the BIOS has had
+    /// time to see the drive empty.
+    pub fn switch_side(&mut self, side: usize) -> Result<(), String> {
+        self.insert(side)?;
+        self.side = None;
+        self.pending_side = Some((side, SIDE_SWITCH_CYCLES));
+        Ok(())
+    }
+
+    /// Whether the game has written to any side.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// The disk with everything the game has written so far.
+    pub fn disk(&self, original: &FDS) -> Result<FDS, String> {
+        let sides = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| {
+                track_to_side(track).map_err(|message| format!("Side {}: {}", index, message))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(FDS {
+            sides,
+            ..original.clone()
+        })
+    }
+
+    fn clock_timer(&mut self) {
+        if !self.timer_enabled {
+            return;
+        }
+        if self.timer_counter == 0 {
+            self.timer_irq = true;
+            self.timer_counter = self.timer_reload;
+            self.timer_enabled = self.timer_repeat;
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    fn clock_side_switch(&mut self) {
+        if let Some((side, cycles)) = self.pending_side {
+            if cycles == 0 {
+                self.side = Some(side);
+                self.pending_side = None;
+            } else {
+                self.pending_side = Some((side, cycles - 1));
+            }
+        }
+    }
+
+    /// Moves the head one byte every `BYTE_CYCLES` while the motor runs,
+    /// transferring the byte under it.
+    fn clock_drive(&mut self) {
+        let side = match self.side {
+            Some(side) if self.motor_on => side,
+            _ => {
+                self.end_of_head = true;
+                self.scanning = false;
+                return;
+            }
+        };
+        if self.reset_transfer && !self.scanning {
+            return;
+        }
+        if self.end_of_head {
+            self.delay = SPIN_UP_CYCLES;
+            self.end_of_head = false;
+            self.head_position = 0;
+            self.gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        if self.read_mode {
+            self.read_disk(side);
+        } else {
+            self.write_disk(side);
+        }
+        self.previous_crc_control = self.crc_control;
+
+        self.head_position += 1;
+        if self.head_position >= self.tracks[side].len() {
+            self.motor_on = false;
+            self.end_of_head = true;
+        } else {
+            self.delay = BYTE_CYCLES;
+        }
+    }
+
+    fn read_disk(&mut self, side: usize) {
+        let value = self.tracks[side][self.head_position];
+        if !self.previous_crc_control {
+            self.crc = update_crc(self.crc, value);
+        }
+        let mut raise_irq = self.disk_irq_enabled;
+        if !self.transfer_enabled {
+            self.gap_ended = false;
+            self.crc = 0;
+        } else if value != 0 && !self.gap_ended {
+            // The start mark ends the gap without an IRQ.
+            self.gap_ended = true;
+            raise_irq = false;
+        }
+        if self.gap_ended {
+            self.transfer_complete = true;
+            self.read_data = value;
+            self.disk_irq |= raise_irq;
+        }
+    }
+
+    fn write_disk(&mut self, side: usize) {
+        let mut value = 0;
+        if !self.crc_control {
+            self.transfer_complete = true;
+            value = self.write_data;
+            self.disk_irq |= self.disk_irq_enabled;
+        }
+        if !self.transfer_enabled {
+            value = 0;
+        }
+        if !self.crc_control {
+            self.crc = update_crc(self.crc, value);
+        } else {
+            if !self.previous_crc_control {
+                self.crc = update_crc(self.crc, 0);
+                self.crc = update_crc(self.crc, 0);
+            }
+            value = self.crc as u8;
+            self.crc >>= 8;
+        }
+        self.gap_ended = false;
+
+        let byte = &mut self.tracks[side][self.head_position];
+        if *byte != value {
+            *byte = value;
+            self.modified = true;
+        }
+    }
+
+    fn read_register(&mut self, addr: BusAddr) -> u8 {
+        if !self.disk_registers_enabled {
+            return 0;
+        }
+        match addr {
+            0x4030 => {
+                let mut status = 0;
+                if self.timer_irq {
+                    status |= 0x01;
+                }
+                if self.transfer_complete {
+                    status |= 0x02;
+                }
+                if self.end_of_head {
+                    status |= 0x40;
+                }
+                self.transfer_complete = false;
+                self.timer_irq = false;
+                self.disk_irq = false;
+                status
+            }
+            0x4031 => {
+                self.transfer_complete = false;
+                self.disk_irq = false;
+                self.read_data
+            }
+            0x4032 => {
+                let mut status = 0;
+                if self.side.is_none() {
+                    // No disk, hence not ready and not writable either.
+                    status |= 0x07;
+                } else if !self.scanning {
+                    status |= 0x02;
+                }
+                status
+            }
+            // Bit 7 reports a good battery.
+            0x4033 => 0x80 | (self.external_output & 0x7F),
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, addr: BusAddr, value: u8) {
+        if !self.disk_registers_enabled && (0x4024..=0x4026).contains(&addr) {
+            return;
+        }
+        match addr {
+            0x4020 => self.timer_reload = (self.timer_reload & 0xFF00) | value as u16,
+            0x4021 => self.timer_reload = (self.timer_reload & 0x00FF) | (value as u16) << 8,
+            0x4022 => {
+                self.timer_repeat = value & 0x01 != 0;
+                self.timer_enabled = value & 0x02 != 0 && self.disk_registers_enabled;
+                if self.timer_enabled {
+                    self.timer_counter = self.timer_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4023 => {
+                self.disk_registers_enabled = value & 0x01 != 0;
+                self.sound_registers_enabled = value & 0x02 != 0;
+                if !self.disk_registers_enabled {
+                    self.timer_enabled = false;
+                    self.timer_irq = false;
+                    self.disk_irq = false;
+                }
+            }
+            0x4024 => {
+                self.write_data = value;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            }
+            0x4025 => {
+                self.motor_on = value & 0x01 != 0;
+                self.reset_transfer = value & 0x02 != 0;
+                self.read_mode = value & 0x04 != 0;
+                self.mirroring = if value & 0x08 != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+                self.crc_control = value & 0x10 != 0;
+                self.transfer_enabled = value & 0x40 != 0;
+                self.disk_irq_enabled = value & 0x80 != 0;
+                self.disk_irq = false;
+            }
+            0x4026 => self.external_output = value,
+            _ => { /* Unused */ }
+        }
+    }
+}
+
+impl Mapper for FDSAdapter {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= BIOS_START_ADDR {
+            self.bios[(addr - BIOS_START_ADDR) as usize]
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.prg_ram[(addr - PRG_RAM_START_ADDR) as usize]
+        } else if (AUDIO_START_ADDR..=AUDIO_END_ADDR).contains(&addr) {
+            self.audio.read_register(addr)
+        } else {
+            self.read_register(addr)
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= BIOS_START_ADDR {
+            // ROM
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.prg_ram[(addr - PRG_RAM_START_ADDR) as usize] = value;
+        } else if (AUDIO_START_ADDR..=AUDIO_END_ADDR).contains(&addr) {
+            if self.sound_registers_enabled {
+                self.audio.write_register(addr, value);
+            }
+        } else {
+            self.write_register(addr, value);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.chr_ram[addr as usize % CHR_RAM_SIZE]
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.chr_ram[addr as usize % CHR_RAM_SIZE] = value;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    /// Timer or disk transfer.
+    fn irq(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    fn tick(&mut self) {
+        self.clock_timer();
+        self.audio.clock();
+        self.clock_side_switch();
+        self.clock_drive();
+    }
+
+    fn audio_level(&self) -> f32 {
+        self.audio.level()
+    }
+}
@@ -0,0 +1,310 @@
+use crate::bus::BusAddr;
+
+const WAVE_TABLE_SIZE: usize = 64;
+const MOD_TABLE_SIZE: usize = 64;
+/// Master volume multipliers for 2/2, 2/3, 2/4 and 2/5 volume, in 1/36ths.
+const MASTER_VOLUMES: [u32; 4] = [36, 24, 17, 14];
+const MAX_GAIN: u8 = 32;
+const MAX_OUTPUT: f32 = 63.0;
+/// At full volume the channel is about 2.4 times as loud as a 2A03 pulse
+/// channel at full volume.
+const FULL_VOLUME_LEVEL: f32 = 0.36;
+const DEFAULT_ENVELOPE_SPEED: u8 = 0xE8;
+/// Mod table entries: how far each step moves the mod counter. `None`
+/// resets the counter to zero.
+const MOD_STEPS: [Option<i8>; 8] = [
+    Some(0),
+    Some(1),
+    Some(2),
+    Some(4),
+    None,
+    Some(-4),
+    Some(-2),
+    Some(-1),
+];
+
+/// The volume or mod envelope, with the pitch register it shares an
+/// address block with.
+struct Envelope {
+    speed: u8,
+    gain: u8,
+    disabled: bool,
+    increase: bool,
+    frequency: u16,
+    timer: u32,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Self {
+            speed: 0,
+            gain: 0,
+            disabled: true,
+            increase: false,
+            frequency: 0,
+            timer: 0,
+        }
+    }
+
+    fn write_register(&mut self, register: BusAddr, value: u8, master_speed: u8) {
+        match register {
+            0 => {
+                self.speed = value & 0x3F;
+                self.increase = value & 0x40 != 0;
+                self.disabled = value & 0x80 != 0;
+                self.reset_timer(master_speed);
+                if self.disabled {
+                    self.gain = self.speed;
+                }
+            }
+            2 => self.frequency = (self.frequency & 0x0F00) | value as u16,
+            3 => self.frequency = (self.frequency & 0x00FF) | ((value & 0x0F) as u16) << 8,
+            _ => {}
+        }
+    }
+
+    fn reset_timer(&mut self, master_speed: u8) {
+        self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+    }
+
+    /// Returns whether the gain was stepped.
+    fn clock(&mut self, master_speed: u8) -> bool {
+        if self.disabled || master_speed == 0 {
+            return false;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return false;
+        }
+        self.reset_timer(master_speed);
+        if self.increase && self.gain < MAX_GAIN {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+        true
+    }
+}
+
+/// The frequency modulator, which bends the wave's pitch by a signed
+/// counter stepped through the mod table.
+struct Modulator {
+    envelope: Envelope,
+    counter: i8,
+    disabled: bool,
+    table: [u8; MOD_TABLE_SIZE],
+    table_position: usize,
+    accumulator: u16,
+    pitch_offset: i32,
+}
+
+impl Modulator {
+    fn new() -> Self {
+        Self {
+            envelope: Envelope::new(),
+            counter: 0,
+            disabled: true,
+            table: [0; MOD_TABLE_SIZE],
+            table_position: 0,
+            accumulator: 0,
+            pitch_offset: 0,
+        }
+    }
+
+    fn set_counter(&mut self, value: i32) {
+        // 7-bit signed.
+        self.counter = (((value & 0x7F) << 1) as i8) >> 1;
+    }
+
+    /// $4088 pushes an entry into the table twice, but only while the
+    /// modulator is halted.
+    fn write_table(&mut self, value: u8) {
+        if self.disabled {
+            self.table[self.table_position] = value & 0x07;
+            self.table[(self.table_position + 1) % MOD_TABLE_SIZE] = value & 0x07;
+            self.table_position = (self.table_position + 2) % MOD_TABLE_SIZE;
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.disabled && self.envelope.frequency > 0
+    }
+
+    /// Returns whether the counter moved.
+    fn clock(&mut self) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        let (accumulator, overflow) = self.accumulator.overflowing_add(self.envelope.frequency);
+        self.accumulator = accumulator;
+        if !overflow {
+            return false;
+        }
+        match MOD_STEPS[self.table[self.table_position] as usize] {
+            Some(step) => self.set_counter(self.counter as i32 + step as i32),
+            None => self.set_counter(0),
+        }
+        self.table_position = (self.table_position + 1) % MOD_TABLE_SIZE;
+        true
+    }
+
+    /// Recomputes the pitch offset for `pitch`, with the rounding quirks
+    /// described on the NESdev wiki.
+    fn update_pitch_offset(&mut self, pitch: u16) {
+        let mut offset = self.counter as i32 * self.envelope.gain as i32;
+        let remainder = offset & 0x0F;
+        offset >>= 4;
+        if remainder > 0 && offset & 0x80 == 0 {
+            offset += if self.counter < 0 { -1 } else { 2 };
+        }
+        if offset >= 192 {
+            offset -= 256;
+        } else if offset < -64 {
+            offset += 256;
+        }
+        offset *= pitch as i32;
+        let remainder = offset & 0x3F;
+        offset >>= 6;
+        if remainder >= 32 {
+            offset += 1;
+        }
+        self.pitch_offset = offset;
+    }
+
+    fn pitch_offset(&self) -> i32 {
+        if self.is_enabled() {
+            self.pitch_offset
+        } else {
+            0
+        }
+    }
+}
+
+/// The RAM adapter's wavetable channel: a 64-step, 6-bit waveform played
+/// at a modulated pitch through a volume envelope.
+pub struct FDSAudio {
+    wave_table: [u8; WAVE_TABLE_SIZE],
+    wave_write_enabled: bool,
+    volume: Envelope,
+    modulator: Modulator,
+    envelopes_halted: bool,
+    wave_halted: bool,
+    master_volume: usize,
+    master_speed: u8,
+    wave_accumulator: u16,
+    wave_position: usize,
+    output: u8,
+}
+
+impl FDSAudio {
+    pub fn new() -> Self {
+        Self {
+            wave_table: [0; WAVE_TABLE_SIZE],
+            wave_write_enabled: false,
+            volume: Envelope::new(),
+            modulator: Modulator::new(),
+            envelopes_halted: false,
+            wave_halted: true,
+            master_volume: 0,
+            master_speed: DEFAULT_ENVELOPE_SPEED,
+            wave_accumulator: 0,
+            wave_position: 0,
+            output: 0,
+        }
+    }
+
+    /// Advances the channel by a single CPU cycle.
+    pub fn clock(&mut self) {
+        let pitch = self.volume.frequency;
+        if !self.wave_halted && !self.envelopes_halted {
+            self.volume.clock(self.master_speed);
+            if self.modulator.envelope.clock(self.master_speed) {
+                self.modulator.update_pitch_offset(pitch);
+            }
+        }
+        if self.modulator.clock() {
+            self.modulator.update_pitch_offset(pitch);
+        }
+
+        if self.wave_halted {
+            self.wave_position = 0;
+        } else {
+            let step = pitch as i32 + self.modulator.pitch_offset();
+            if step > 0 && !self.wave_write_enabled {
+                let (accumulator, overflow) = self.wave_accumulator.overflowing_add(step as u16);
+                self.wave_accumulator = accumulator;
+                if overflow {
+                    self.wave_position = (self.wave_position + 1) % WAVE_TABLE_SIZE;
+                }
+            }
+        }
+        self.update_output();
+    }
+
+    /// Output scaled relative to the 2A03 channels.
+    pub fn level(&self) -> f32 {
+        self.output as f32 / MAX_OUTPUT * FULL_VOLUME_LEVEL
+    }
+
+    pub fn read_register(&self, addr: BusAddr) -> u8 {
+        match addr {
+            0x4040..=0x407F => self.wave_table[(addr - 0x4040) as usize],
+            0x4090 => self.volume.gain,
+            0x4092 => self.modulator.envelope.gain,
+            _ => 0,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: BusAddr, value: u8) {
+        match addr {
+            // The wave table is only writable while the wave is held.
+            0x4040..=0x407F if self.wave_write_enabled => {
+                self.wave_table[(addr - 0x4040) as usize] = value & 0x3F;
+            }
+            0x4080 | 0x4082 => self
+                .volume
+                .write_register(addr - 0x4080, value, self.master_speed),
+            0x4083 => {
+                self.envelopes_halted = value & 0x40 != 0;
+                self.wave_halted = value & 0x80 != 0;
+                if self.envelopes_halted {
+                    self.volume.reset_timer(self.master_speed);
+                    self.modulator.envelope.reset_timer(self.master_speed);
+                }
+                self.volume
+                    .write_register(addr - 0x4080, value, self.master_speed);
+            }
+            0x4084 | 0x4086 => {
+                self.modulator
+                    .envelope
+                    .write_register(addr - 0x4084, value, self.master_speed)
+            }
+            0x4085 => self.modulator.set_counter(value as i32),
+            0x4087 => {
+                self.modulator.disabled = value & 0x80 != 0;
+                if self.modulator.disabled {
+                    self.modulator.accumulator = 0;
+                }
+                self.modulator
+                    .envelope
+                    .write_register(addr - 0x4084, value, self.master_speed);
+            }
+            0x4088 => self.modulator.write_table(value),
+            0x4089 => {
+                self.master_volume = (value & 0x03) as usize;
+                self.wave_write_enabled = value & 0x80 != 0;
+            }
+            0x408A => self.master_speed = value,
+            _ => { /* Unused */ }
+        }
+    }
+
+    fn update_output(&mut self) {
+        // The wave is held while the CPU can write to it.
+        if self.wave_write_enabled {
+            return;
+        }
+        let level = self.volume.gain.min(MAX_GAIN) as u32 * MASTER_VOLUMES[self.master_volume];
+        self.output = (self.wave_table[self.wave_position] as u32 * level / 1152) as u8;
+    }
+}
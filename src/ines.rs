@@ -1,67 +1,144 @@
 mod character_rom;
+mod database;
+mod error;
+mod header;
 mod program_rom;
+mod unif;
 
-use character_rom::CharacterROM;
+pub use character_rom::CharacterROM;
+pub use database::{GameDatabase, GameEntry, HeaderCorrection};
+pub use error::RomError;
+use header::HEADER_BYTES;
+pub use header::{
+    ConsoleType, ExpansionDevice, Header, HeaderFormat, Timing, VsHardwareType, VsPPUType, VsSystem,
+};
 pub use program_rom::ProgramROM;
+pub use unif::UNIF;
+use unif::UNIF_MAGIC;
 
+const TRAINER_BYTES: usize = 512;
+
+#[allow(non_camel_case_types, non_snake_case)]
 pub struct iNES {
+    pub header: Header,
+    /// 512 bytes the loader copies to $7000-$71FF before the game starts.
+    pub trainer: Option<Vec<u8>>,
     pub programROM: ProgramROM,
     pub characterROM: CharacterROM,
 }
 
-const HEADER_BYTES: usize = 16;
-const PROGRAM_ROM_UNIT: usize = 0x4000; // 16KB
-const CHARACTER_ROM_UNIT: usize = 0x2000; // 8KB
-const INES_HEADER_START: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A]; // "NES" followed by MS-DOS EOF
-
 impl iNES {
-    pub fn parse(data: &[u8]) -> Result<iNES, String> {
-        if data.len() < 16 {
-            return Err(format!(
-                "iNES header must be 16 bytes long, but was {} bytes long",
-                data.len()
-            ));
-        }
-        if data[0] != INES_HEADER_START[0]
-            || data[1] != INES_HEADER_START[1]
-            || data[2] != INES_HEADER_START[2]
-            || data[3] != INES_HEADER_START[3]
-        {
-            return Err("iNES header must start with 'NES'".to_string());
-        }
+    pub fn load(path: &str) -> Result<iNES, RomError> {
+        let data = std::fs::read(path).map_err(|e| RomError::Io {
+            message: e.to_string(),
+        })?;
+        iNES::parse(&data)
+    }
+
+    /// Like `load`, but corrects the header from `database` first.
+    pub fn load_with_database(
+        path: &str,
+        database: &GameDatabase,
+    ) -> Result<(iNES, Vec<HeaderCorrection>), RomError> {
+        let data = std::fs::read(path).map_err(|e| RomError::Io {
+            message: e.to_string(),
+        })?;
+        iNES::parse_with_database(&data, database)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<iNES, RomError> {
+        iNES::parse_with_database(data, &GameDatabase::new()).map(|(ines, _)| ines)
+    }
 
-        eprintln!("ProgramROM Size in 16 KB units: {}", data[4]);
-        eprintln!("CharacterROM Size in 8 KB units: {}", data[5]);
+    /// Parses the iNES or UNIF image, then looks its PRG-ROM and CHR-ROM up
+    /// in `database` and overwrites the header fields the entry knows about.
+    /// Returns the fields that were changed.
+    pub fn parse_with_database(
+        data: &[u8],
+        database: &GameDatabase,
+    ) -> Result<(iNES, Vec<HeaderCorrection>), RomError> {
+        let (mut header, trainer, program_rom, characte_rom) = if data.starts_with(UNIF_MAGIC) {
+            let unif = UNIF::parse(data)?;
+            (unif.header, None, unif.program_rom, unif.character_rom)
+        } else {
+            let header = Header::parse(data)?;
+            let trainer = extract_trainer(data, &header)?;
 
-        let program_rom = extract_program_rom(data);
-        let characte_rom = extract_character_rom(data);
+            let program_rom = extract_program_rom(data, &header)?;
+            let characte_rom = extract_character_rom(data, &header)?;
+            (header, trainer, program_rom, characte_rom)
+        };
 
-        Ok(iNES {
-            programROM: program_rom,
-            characterROM: characte_rom,
-        })
+        let mut corrections = Vec::new();
+        if !database.is_empty() {
+            let mut rom_data = program_rom.data.clone();
+            rom_data.extend_from_slice(characte_rom.as_bytes());
+            if let Some(entry) = database.lookup(&rom_data) {
+                corrections = header.apply_database_entry(entry);
+            }
+        }
+
+        Ok((
+            iNES {
+                header,
+                trainer,
+                programROM: program_rom,
+                characterROM: characte_rom,
+            },
+            corrections,
+        ))
+    }
+}
+
+fn extract_trainer(data: &[u8], header: &Header) -> Result<Option<Vec<u8>>, RomError> {
+    if !header.trainer {
+        return Ok(None);
     }
+    let trainer =
+        data.get(HEADER_BYTES..HEADER_BYTES + TRAINER_BYTES)
+            .ok_or(RomError::TrainerMissing {
+                offset: HEADER_BYTES,
+                available: data.len() - HEADER_BYTES,
+            })?;
+
+    Ok(Some(trainer.to_vec()))
 }
 
-fn extract_program_rom(data: &[u8]) -> ProgramROM {
-    let program_rom_start = HEADER_BYTES;
-    let program_rom_end = program_rom_start + extract_program_rom_size(data);
+fn extract_program_rom(data: &[u8], header: &Header) -> Result<ProgramROM, RomError> {
+    if header.prg_rom_size == 0 {
+        return Err(RomError::MissingPRG);
+    }
+    let program_rom_start = program_rom_start(header);
+    let program_rom_end = program_rom_start.saturating_add(header.prg_rom_size);
+    let program_rom =
+        data.get(program_rom_start..program_rom_end)
+            .ok_or(RomError::TruncatedPRG {
+                offset: program_rom_start,
+                expected: header.prg_rom_size,
+                available: data.len() - program_rom_start,
+            })?;
 
-    ProgramROM::new(&data[program_rom_start..program_rom_end])
+    Ok(ProgramROM::new(program_rom))
 }
 
-fn extract_character_rom(data: &[u8]) -> CharacterROM {
-    let character_rom_size_in_unit = data[5];
-    let character_rom_size = character_rom_size_in_unit as usize * CHARACTER_ROM_UNIT;
-    let program_rom_size = extract_program_rom_size(data);
-    let character_rom_start = HEADER_BYTES + program_rom_size;
-    let character_rom_end = character_rom_start + character_rom_size;
+fn extract_character_rom(data: &[u8], header: &Header) -> Result<CharacterROM, RomError> {
+    let character_rom_start = program_rom_start(header) + header.prg_rom_size;
+    let character_rom_end = character_rom_start.saturating_add(header.chr_rom_size);
+    let character_rom =
+        data.get(character_rom_start..character_rom_end)
+            .ok_or(RomError::TruncatedCHR {
+                offset: character_rom_start,
+                expected: header.chr_rom_size,
+                available: data.len() - character_rom_start,
+            })?;
 
-    CharacterROM::new(&data[character_rom_start..character_rom_end])
+    Ok(CharacterROM::new(character_rom))
 }
 
-fn extract_program_rom_size(data: &[u8]) -> usize {
-    let program_rom_size_in_unit = data[4];
-    
-    program_rom_size_in_unit as usize * PROGRAM_ROM_UNIT
+fn program_rom_start(header: &Header) -> usize {
+    if header.trainer {
+        HEADER_BYTES + TRAINER_BYTES
+    } else {
+        HEADER_BYTES
+    }
 }
@@ -21,6 +21,10 @@ impl CharacterROM {
         Sprite::new(sprite_bytes)
     }
 
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
     pub fn number_of_sprites(&self) -> usize {
         self.data.len() / SINGLE_SPRITE_BYTES
     }
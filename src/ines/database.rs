@@ -0,0 +1,466 @@
+use std::{collections::HashMap, fmt};
+
+use super::{Header, Timing};
+use crate::{hash, ppu::Mirroring};
+
+/// Entries shipped with the emulator, in the TSV layout read by
+/// `GameDatabase::parse_tsv`.
+const BUILT_IN_DATABASE: &str = include_str!("database.tsv");
+
+const TSV_COLUMNS: [&str; 8] = [
+    "crc32",
+    "sha1",
+    "mapper",
+    "submapper",
+    "mirroring",
+    "battery",
+    "region",
+    "name",
+];
+
+/// What the database knows about one dump, identified by the CRC-32 and/or
+/// SHA-1 of its PRG-ROM followed by its CHR-ROM. Fields the source did not
+/// specify are `None` and leave the header untouched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameEntry {
+    pub name: String,
+    pub crc32: Option<u32>,
+    pub sha1: Option<[u8; 20]>,
+    pub mapper: Option<u16>,
+    pub submapper: Option<u8>,
+    pub mirroring: Option<Mirroring>,
+    pub battery: Option<bool>,
+    pub timing: Option<Timing>,
+}
+
+/// A header field that was changed to match the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderCorrection {
+    Mapper { from: u16, to: u16 },
+    Submapper { from: u8, to: u8 },
+    Mirroring { from: Mirroring, to: Mirroring },
+    Battery { from: bool, to: bool },
+    Timing { from: Timing, to: Timing },
+}
+
+impl fmt::Display for HeaderCorrection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderCorrection::Mapper { from, to } => write!(f, "mapper {} -> {}", from, to),
+            HeaderCorrection::Submapper { from, to } => {
+                write!(f, "submapper {} -> {}", from, to)
+            }
+            HeaderCorrection::Mirroring { from, to } => {
+                write!(f, "mirroring {:?} -> {:?}", from, to)
+            }
+            HeaderCorrection::Battery { from, to } => write!(f, "battery {} -> {}", from, to),
+            HeaderCorrection::Timing { from, to } => write!(f, "region {:?} -> {:?}", from, to),
+        }
+    }
+}
+
+/// Known-good header values for ROM dumps, looked up by hash.
+#[derive(Debug, Clone, Default)]
+pub struct GameDatabase {
+    entries: Vec<GameEntry>,
+    by_crc32: HashMap<u32, usize>,
+    by_sha1: HashMap<[u8; 20], usize>,
+}
+
+impl GameDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn built_in() -> Self {
+        Self::parse_tsv(BUILT_IN_DATABASE).expect("built-in database must be valid")
+    }
+
+    /// Reads a database file: a nes20db or NesCartDB XML export, or a TSV
+    /// file with the columns of `TSV_COLUMNS`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        if text.trim_start().starts_with('<') {
+            Self::parse_xml(&text)
+        } else {
+            Self::parse_tsv(&text)
+        }
+        .map_err(|message| format!("{}: {}", path, message))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds an entry. Entries added later win over earlier ones with the
+    /// same hash.
+    pub fn insert(&mut self, entry: GameEntry) {
+        let index = self.entries.len();
+        if let Some(crc32) = entry.crc32 {
+            self.by_crc32.insert(crc32, index);
+        }
+        if let Some(sha1) = entry.sha1 {
+            self.by_sha1.insert(sha1, index);
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn extend(&mut self, other: GameDatabase) {
+        for entry in other.entries {
+            self.insert(entry);
+        }
+    }
+
+    /// Finds the entry for a dump whose PRG-ROM followed by CHR-ROM is
+    /// `data`. SHA-1 matches take precedence over CRC-32 matches.
+    pub fn lookup(&self, data: &[u8]) -> Option<&GameEntry> {
+        let index = self
+            .by_sha1
+            .get(&hash::sha1(data))
+            .or_else(|| self.by_crc32.get(&hash::crc32(data)))?;
+        self.entries.get(*index)
+    }
+
+    /// One entry per line, tab separated, in the order of `TSV_COLUMNS`.
+    /// Empty fields are unknown. Blank lines, lines starting with `#` and a
+    /// header line naming the columns are skipped.
+    pub fn parse_tsv(text: &str) -> Result<Self, String> {
+        let mut database = GameDatabase::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') || line.starts_with(TSV_COLUMNS[0]) {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let entry = parse_tsv_entry(&fields)
+                .map_err(|message| format!("Line {}: {}", line_number + 1, message))?;
+            if entry.crc32.is_none() && entry.sha1.is_none() {
+                return Err(format!("Line {}: entry has no hash", line_number + 1));
+            }
+            database.insert(entry);
+        }
+        Ok(database)
+    }
+
+    /// Reads either a nes20db (`<game>` with `<rom>`, `<pcb>` and
+    /// `<console>`) or a NesCartDB (`<game>` with one `<cartridge>` and
+    /// `<board>` per dump) export.
+    pub fn parse_xml(text: &str) -> Result<Self, String> {
+        let mut database = GameDatabase::new();
+        let mut entry: Option<GameEntry> = None;
+        let mut comment = String::new();
+        let mut game_name = String::new();
+
+        for tag in XmlTags::new(text) {
+            let tag = tag?;
+            match (tag.name, tag.closing) {
+                ("!--", _) => comment = tag.body.trim().to_string(),
+                ("game", false) => {
+                    // nes20db names games in a comment inside the element,
+                    // so the name is only known once the entry ends.
+                    game_name = tag.attribute("name").unwrap_or_default().to_string();
+                    entry = Some(GameEntry::default());
+                }
+                ("game", true) => {
+                    if game_name.is_empty() {
+                        game_name = comment.clone();
+                    }
+                    database.insert_xml_entry(entry.take(), &game_name);
+                }
+                // nes20db
+                ("rom", false) => {
+                    if let Some(entry) = entry.as_mut() {
+                        read_hashes(entry, &tag, "crc32")?;
+                    }
+                }
+                ("pcb", false) => {
+                    if let Some(entry) = entry.as_mut() {
+                        entry.mapper = tag.attribute("mapper").map(parse_number).transpose()?;
+                        entry.submapper =
+                            tag.attribute("submapper").map(parse_number).transpose()?;
+                        entry.mirroring = tag
+                            .attribute("mirroring")
+                            .map(parse_mirroring)
+                            .transpose()?
+                            .flatten();
+                        entry.battery = tag.attribute("battery").map(parse_flag).transpose()?;
+                    }
+                }
+                ("console", false) => {
+                    if let Some(entry) = entry.as_mut() {
+                        entry.timing = tag.attribute("region").map(parse_timing).transpose()?;
+                    }
+                }
+                // NesCartDB
+                ("cartridge", false) => {
+                    let mut cartridge = GameEntry::default();
+                    read_hashes(&mut cartridge, &tag, "crc")?;
+                    cartridge.timing = tag.attribute("system").and_then(system_timing);
+                    entry = Some(cartridge);
+                }
+                ("cartridge", true) => {
+                    if let Some(mut cartridge) = entry.take() {
+                        // Only battery-backed RAM is listed explicitly.
+                        cartridge.battery.get_or_insert(false);
+                        database.insert_xml_entry(Some(cartridge), &game_name);
+                    }
+                }
+                ("board", false) => {
+                    if let Some(entry) = entry.as_mut() {
+                        entry.mapper = tag.attribute("mapper").map(parse_number).transpose()?;
+                    }
+                }
+                ("pad", false) => {
+                    if let Some(entry) = entry.as_mut() {
+                        if tag.attribute("v") == Some("1") {
+                            entry.mirroring = Some(Mirroring::Vertical);
+                        } else if tag.attribute("h") == Some("1") {
+                            entry.mirroring = Some(Mirroring::Horizontal);
+                        }
+                    }
+                }
+                ("wram", false) | ("vram", false) => {
+                    if let Some(entry) = entry.as_mut() {
+                        if tag.attribute("battery") == Some("1") {
+                            entry.battery = Some(true);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(database)
+    }
+
+    fn insert_xml_entry(&mut self, entry: Option<GameEntry>, name: &str) {
+        if let Some(mut entry) = entry {
+            if entry.crc32.is_some() || entry.sha1.is_some() {
+                entry.name = name.to_string();
+                self.insert(entry);
+            }
+        }
+    }
+}
+
+impl Header {
+    /// Overwrites the fields `entry` knows about and returns what changed.
+    pub fn apply_database_entry(&mut self, entry: &GameEntry) -> Vec<HeaderCorrection> {
+        let mut corrections = Vec::new();
+        if let Some(mapper) = entry.mapper.filter(|m| *m != self.mapper) {
+            corrections.push(HeaderCorrection::Mapper {
+                from: self.mapper,
+                to: mapper,
+            });
+            self.mapper = mapper;
+        }
+        if let Some(submapper) = entry.submapper.filter(|s| *s != self.submapper) {
+            corrections.push(HeaderCorrection::Submapper {
+                from: self.submapper,
+                to: submapper,
+            });
+            self.submapper = submapper;
+        }
+        if let Some(mirroring) = entry.mirroring.filter(|m| *m != self.mirroring) {
+            corrections.push(HeaderCorrection::Mirroring {
+                from: self.mirroring,
+                to: mirroring,
+            });
+            self.mirroring = mirroring;
+        }
+        if let Some(battery) = entry.battery.filter(|b| *b != self.battery) {
+            corrections.push(HeaderCorrection::Battery {
+                from: self.battery,
+                to: battery,
+            });
+            self.battery = battery;
+            // Keep the RAM sizes consistent with the battery flag.
+            if battery {
+                self.prg_nvram_size = self.prg_nvram_size.max(self.prg_ram_size);
+                self.prg_ram_size = 0;
+            } else {
+                self.prg_ram_size = self.prg_ram_size.max(self.prg_nvram_size);
+                self.prg_nvram_size = 0;
+            }
+        }
+        if let Some(timing) = entry.timing.filter(|t| *t != self.timing) {
+            corrections.push(HeaderCorrection::Timing {
+                from: self.timing,
+                to: timing,
+            });
+            self.timing = timing;
+        }
+        corrections
+    }
+}
+
+fn parse_tsv_entry(fields: &[&str]) -> Result<GameEntry, String> {
+    let field = |index: usize| fields.get(index).copied().filter(|f| !f.is_empty());
+    Ok(GameEntry {
+        crc32: field(0).map(parse_crc32).transpose()?,
+        sha1: field(1).map(parse_sha1).transpose()?,
+        mapper: field(2).map(parse_number).transpose()?,
+        submapper: field(3).map(parse_number).transpose()?,
+        mirroring: field(4).map(parse_mirroring).transpose()?.flatten(),
+        battery: field(5).map(parse_flag).transpose()?,
+        timing: field(6).map(parse_timing).transpose()?,
+        name: field(7).unwrap_or_default().to_string(),
+    })
+}
+
+fn read_hashes(entry: &mut GameEntry, tag: &XmlTag, crc_attribute: &str) -> Result<(), String> {
+    entry.crc32 = tag.attribute(crc_attribute).map(parse_crc32).transpose()?;
+    entry.sha1 = tag.attribute("sha1").map(parse_sha1).transpose()?;
+    Ok(())
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number: {}", value))
+}
+
+fn parse_flag(value: &str) -> Result<bool, String> {
+    parse_number::<u8>(value).map(|flag| flag != 0)
+}
+
+fn parse_crc32(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value, 16).map_err(|_| format!("Invalid CRC-32: {}", value))
+}
+
+fn parse_sha1(value: &str) -> Result<[u8; 20], String> {
+    let invalid = || format!("Invalid SHA-1: {}", value);
+    if value.len() != 40 || !value.is_ascii() {
+        return Err(invalid());
+    }
+    let mut sha1 = [0; 20];
+    for (index, byte) in sha1.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(sha1)
+}
+
+/// nes20db uses H, V and 4 (four-screen), plus 1 and M for boards where the
+/// mapper controls mirroring. Those leave the header alone.
+fn parse_mirroring(value: &str) -> Result<Option<Mirroring>, String> {
+    match value {
+        "H" | "h" => Ok(Some(Mirroring::Horizontal)),
+        "V" | "v" => Ok(Some(Mirroring::Vertical)),
+        "4" => Ok(Some(Mirroring::FourScreen)),
+        "1" | "M" | "m" => Ok(None),
+        _ => Err(format!("Invalid mirroring: {}", value)),
+    }
+}
+
+/// The NES 2.0 timing values: 0 NTSC, 1 PAL, 2 multi-region, 3 Dendy.
+fn parse_timing(value: &str) -> Result<Timing, String> {
+    match value {
+        "0" => Ok(Timing::NTSC),
+        "1" => Ok(Timing::PAL),
+        "2" => Ok(Timing::MultiRegion),
+        "3" => Ok(Timing::Dendy),
+        _ => Err(format!("Invalid region: {}", value)),
+    }
+}
+
+fn system_timing(system: &str) -> Option<Timing> {
+    match system {
+        "NES-NTSC" | "Famicom" => Some(Timing::NTSC),
+        "NES-PAL" | "NES-PAL-A" | "NES-PAL-B" => Some(Timing::PAL),
+        "Dendy" => Some(Timing::Dendy),
+        _ => None,
+    }
+}
+
+/// A start, end or empty-element tag, or a comment (named `!--`).
+struct XmlTag<'a> {
+    name: &'a str,
+    closing: bool,
+    body: &'a str,
+}
+
+impl<'a> XmlTag<'a> {
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        let mut rest = self.body;
+        while let Some(equals) = rest.find('=') {
+            let key = rest[..equals].trim();
+            let value = rest[equals + 1..].trim_start();
+            let quote = value.chars().next()?;
+            let value = &value[1..];
+            let end = value.find(quote)?;
+            if key == name {
+                return Some(&value[..end]);
+            }
+            rest = &value[end + 1..];
+        }
+        None
+    }
+}
+
+/// Just enough of an XML tokenizer for database exports: yields tags and
+/// skips text, declarations and entities.
+struct XmlTags<'a> {
+    rest: &'a str,
+}
+
+impl<'a> XmlTags<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { rest: text }
+    }
+}
+
+impl<'a> Iterator for XmlTags<'a> {
+    type Item = Result<XmlTag<'a>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.rest.find('<')?;
+        let tag = &self.rest[start + 1..];
+
+        if let Some(comment) = tag.strip_prefix("!--") {
+            let end = match comment.find("-->") {
+                Some(end) => end,
+                None => return Some(Err("Unterminated XML comment".to_string())),
+            };
+            self.rest = &comment[end + 3..];
+            return Some(Ok(XmlTag {
+                name: "!--",
+                closing: false,
+                body: &comment[..end],
+            }));
+        }
+
+        let end = match tag.find('>') {
+            Some(end) => end,
+            None => return Some(Err("Unterminated XML tag".to_string())),
+        };
+        self.rest = &tag[end + 1..];
+        let tag = &tag[..end];
+
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+        Some(Ok(XmlTag {
+            name: &tag[..name_end],
+            closing,
+            body: &tag[name_end..],
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_database_parses() {
+        let database = GameDatabase::built_in();
+        assert!(!database.is_empty());
+        assert!(database.entries.iter().all(|entry| !entry.name.is_empty()));
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+
+/// Why a ROM image could not be loaded. Offsets are byte positions in the
+/// file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    Io {
+        message: String,
+    },
+    TruncatedHeader {
+        length: usize,
+    },
+    BadMagic {
+        found: Vec<u8>,
+    },
+    TrainerMissing {
+        offset: usize,
+        available: usize,
+    },
+    MissingPRG,
+    TruncatedPRG {
+        offset: usize,
+        expected: usize,
+        available: usize,
+    },
+    TruncatedCHR {
+        offset: usize,
+        expected: usize,
+        available: usize,
+    },
+    UnsupportedMapper {
+        mapper: u16,
+        submapper: u8,
+    },
+    BadNES2Field {
+        offset: usize,
+        value: u8,
+        reason: &'static str,
+    },
+    TruncatedChunk {
+        id: String,
+        offset: usize,
+        expected: usize,
+        available: usize,
+    },
+    MissingBoard,
+    UnsupportedBoard {
+        board: String,
+    },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Io { message } => write!(f, "{}", message),
+            RomError::TruncatedHeader { length } => write!(
+                f,
+                "iNES header must be 16 bytes long, but the file is {} bytes long",
+                length
+            ),
+            RomError::BadMagic { found } => write!(
+                f,
+                "iNES header must start with 'NES' followed by $1A at offset 0, but found {:02X?}",
+                found
+            ),
+            RomError::TrainerMissing { offset, available } => write!(
+                f,
+                "Header announces a 512-byte trainer at offset 0x{:X}, but only {} bytes remain",
+                offset, available
+            ),
+            RomError::MissingPRG => write!(f, "Header announces no PRG-ROM"),
+            RomError::TruncatedPRG {
+                offset,
+                expected,
+                available,
+            } => write!(
+                f,
+                "PRG-ROM of {} bytes expected at offset 0x{:X}, but only {} bytes remain",
+                expected, offset, available
+            ),
+            RomError::TruncatedCHR {
+                offset,
+                expected,
+                available,
+            } => write!(
+                f,
+                "CHR-ROM of {} bytes expected at offset 0x{:X}, but only {} bytes remain",
+                expected, offset, available
+            ),
+            RomError::UnsupportedMapper { mapper, submapper } => write!(
+                f,
+                "Mapper {} (submapper {}) is not supported",
+                mapper, submapper
+            ),
+            RomError::BadNES2Field {
+                offset,
+                value,
+                reason,
+            } => write!(
+                f,
+                "Invalid NES 2.0 header byte 0x{:02X} at offset {}: {}",
+                value, offset, reason
+            ),
+            RomError::TruncatedChunk {
+                id,
+                offset,
+                expected,
+                available,
+            } => write!(
+                f,
+                "UNIF chunk {:?} of {} bytes expected at offset 0x{:X}, but only {} bytes remain",
+                id, expected, offset, available
+            ),
+            RomError::MissingBoard => write!(f, "UNIF image has no MAPR chunk naming its board"),
+            RomError::UnsupportedBoard { board } => {
+                write!(f, "UNIF board {} is not supported", board)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
@@ -0,0 +1,446 @@
+use super::RomError;
+use crate::ppu::Mirroring;
+
+pub const HEADER_BYTES: usize = 16;
+const PROGRAM_ROM_UNIT: usize = 0x4000; // 16KB
+const CHARACTER_ROM_UNIT: usize = 0x2000; // 8KB
+const INES_PROGRAM_RAM_UNIT: usize = 0x2000; // 8KB
+const INES_HEADER_START: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A]; // "NES" followed by MS-DOS EOF
+
+const FLAG6_VERTICAL_MIRRORING: u8 = 0x01;
+const FLAG6_BATTERY: u8 = 0x02;
+const FLAG6_TRAINER: u8 = 0x04;
+const FLAG6_FOUR_SCREEN: u8 = 0x08;
+const FLAG7_NES2_ID_MASK: u8 = 0x0C;
+const FLAG7_NES2_ID: u8 = 0x08;
+const FLAG9_INES_PAL: u8 = 0x01;
+const ROM_SIZE_EXPONENT_NOTATION: u16 = 0x0F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    /// Old iNES dumps with garbage (e.g. "DiskDude!") in bytes 7-15. Only
+    /// bytes 4-6 are trusted.
+    ArchaicINES,
+    INES,
+    NES2,
+    /// Built from the chunks of a UNIF image rather than read from a header.
+    UNIF,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    NES,
+    VsSystem,
+    Playchoice10,
+    /// NES 2.0 extended console type from byte 13.
+    Extended(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    NTSC,
+    PAL,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsPPUType {
+    RP2C03B,
+    RP2C03G,
+    RP2C04_0001,
+    RP2C04_0002,
+    RP2C04_0003,
+    RP2C04_0004,
+    RC2C03B,
+    RC2C03C,
+    RC2C05_01,
+    RC2C05_02,
+    RC2C05_03,
+    RC2C05_04,
+    RC2C05_05,
+}
+
+impl VsPPUType {
+    fn from_nibble(value: u8) -> Option<Self> {
+        Some(match value {
+            0x0 => VsPPUType::RP2C03B,
+            0x1 => VsPPUType::RP2C03G,
+            0x2 => VsPPUType::RP2C04_0001,
+            0x3 => VsPPUType::RP2C04_0002,
+            0x4 => VsPPUType::RP2C04_0003,
+            0x5 => VsPPUType::RP2C04_0004,
+            0x6 => VsPPUType::RC2C03B,
+            0x7 => VsPPUType::RC2C03C,
+            0x8 => VsPPUType::RC2C05_01,
+            0x9 => VsPPUType::RC2C05_02,
+            0xA => VsPPUType::RC2C05_03,
+            0xB => VsPPUType::RC2C05_04,
+            0xC => VsPPUType::RC2C05_05,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsHardwareType {
+    UniSystem,
+    UniSystemRBIBaseball,
+    UniSystemTKOBoxing,
+    UniSystemSuperXevious,
+    UniSystemIceClimberJapan,
+    DualSystem,
+    DualSystemRaidOnBungelingBay,
+}
+
+impl VsHardwareType {
+    fn from_nibble(value: u8) -> Option<Self> {
+        Some(match value {
+            0x0 => VsHardwareType::UniSystem,
+            0x1 => VsHardwareType::UniSystemRBIBaseball,
+            0x2 => VsHardwareType::UniSystemTKOBoxing,
+            0x3 => VsHardwareType::UniSystemSuperXevious,
+            0x4 => VsHardwareType::UniSystemIceClimberJapan,
+            0x5 => VsHardwareType::DualSystem,
+            0x6 => VsHardwareType::DualSystemRaidOnBungelingBay,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VsSystem {
+    pub ppu: VsPPUType,
+    pub hardware: VsHardwareType,
+}
+
+/// NES 2.0 default expansion device (byte 15). Devices without a dedicated
+/// variant are kept as their raw number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpansionDevice {
+    Unspecified,
+    StandardControllers,
+    FourScore,
+    FamicomFourPlayersAdapter,
+    VsSystem,
+    Zapper,
+    TwoZappers,
+    PowerPadSideA,
+    PowerPadSideB,
+    ArkanoidVausNES,
+    ArkanoidVausFamicom,
+    FamilyBASICKeyboard,
+    Other(u8),
+}
+
+impl ExpansionDevice {
+    fn from_byte(value: u8) -> Self {
+        match value {
+            0x00 => ExpansionDevice::Unspecified,
+            0x01 => ExpansionDevice::StandardControllers,
+            0x02 => ExpansionDevice::FourScore,
+            0x03 => ExpansionDevice::FamicomFourPlayersAdapter,
+            0x04 => ExpansionDevice::VsSystem,
+            0x08 => ExpansionDevice::Zapper,
+            0x09 => ExpansionDevice::TwoZappers,
+            0x0B => ExpansionDevice::PowerPadSideA,
+            0x0C => ExpansionDevice::PowerPadSideB,
+            0x0F => ExpansionDevice::ArkanoidVausNES,
+            0x10 => ExpansionDevice::ArkanoidVausFamicom,
+            0x23 => ExpansionDevice::FamilyBASICKeyboard,
+            _ => ExpansionDevice::Other(value),
+        }
+    }
+}
+
+/// The 16-byte iNES / NES 2.0 header, or its equivalent for a UNIF image.
+/// All sizes are in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub format: HeaderFormat,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub trainer: bool,
+    pub console_type: ConsoleType,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub timing: Timing,
+    /// Only present for Vs. System NES 2.0 headers.
+    pub vs_system: Option<VsSystem>,
+    pub misc_roms: u8,
+    pub expansion_device: ExpansionDevice,
+}
+
+impl Header {
+    pub fn parse(data: &[u8]) -> Result<Header, RomError> {
+        if data.len() < HEADER_BYTES {
+            return Err(RomError::TruncatedHeader { length: data.len() });
+        }
+        if data[0..4] != INES_HEADER_START {
+            return Err(RomError::BadMagic {
+                found: data[0..4].to_vec(),
+            });
+        }
+
+        let format = if data[7] & FLAG7_NES2_ID_MASK == FLAG7_NES2_ID {
+            HeaderFormat::NES2
+        } else if data[7] & FLAG7_NES2_ID_MASK == 0 && data[12..16].iter().all(|b| *b == 0) {
+            HeaderFormat::INES
+        } else {
+            HeaderFormat::ArchaicINES
+        };
+
+        let mirroring = if data[6] & FLAG6_FOUR_SCREEN != 0 {
+            Mirroring::FourScreen
+        } else if data[6] & FLAG6_VERTICAL_MIRRORING != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        let mut header = Header {
+            format,
+            prg_rom_size: data[4] as usize * PROGRAM_ROM_UNIT,
+            chr_rom_size: data[5] as usize * CHARACTER_ROM_UNIT,
+            mapper: (data[6] >> 4) as u16,
+            submapper: 0,
+            mirroring,
+            battery: data[6] & FLAG6_BATTERY != 0,
+            trainer: data[6] & FLAG6_TRAINER != 0,
+            console_type: ConsoleType::NES,
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            timing: Timing::NTSC,
+            vs_system: None,
+            misc_roms: 0,
+            expansion_device: ExpansionDevice::Unspecified,
+        };
+
+        match format {
+            HeaderFormat::ArchaicINES => header.parse_ines_ram_sizes(1),
+            HeaderFormat::INES => {
+                header.mapper |= (data[7] & 0xF0) as u16;
+                header.console_type = match data[7] & 0x03 {
+                    0x01 => ConsoleType::VsSystem,
+                    0x02 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::NES,
+                };
+                // Byte 8 is rarely set; zero still means 8KB for compatibility.
+                header.parse_ines_ram_sizes(data[8].max(1));
+                if data[9] & FLAG9_INES_PAL != 0 {
+                    header.timing = Timing::PAL;
+                }
+            }
+            HeaderFormat::NES2 => header.parse_nes2(data)?,
+            HeaderFormat::UNIF => unreachable!("UNIF images have no iNES header"),
+        }
+
+        Ok(header)
+    }
+
+    pub fn is_nes2(&self) -> bool {
+        self.format == HeaderFormat::NES2
+    }
+
+    /// iNES 1.0 can only say whether PRG-RAM is battery-backed, and has no
+    /// CHR-RAM size, so assume 8KB of CHR-RAM when there is no CHR-ROM.
+    fn parse_ines_ram_sizes(&mut self, prg_ram_units: u8) {
+        let prg_ram_size = prg_ram_units as usize * INES_PROGRAM_RAM_UNIT;
+        if self.battery {
+            self.prg_nvram_size = prg_ram_size;
+        } else {
+            self.prg_ram_size = prg_ram_size;
+        }
+        if self.chr_rom_size == 0 {
+            self.chr_ram_size = CHARACTER_ROM_UNIT;
+        }
+    }
+
+    fn parse_nes2(&mut self, data: &[u8]) -> Result<(), RomError> {
+        self.mapper |= (data[7] & 0xF0) as u16 | ((data[8] & 0x0F) as u16) << 8;
+        self.submapper = data[8] >> 4;
+
+        self.prg_rom_size = nes2_rom_size(data[4], data[9] & 0x0F, PROGRAM_ROM_UNIT).ok_or(
+            RomError::BadNES2Field {
+                offset: 4,
+                value: data[4],
+                reason: "PRG-ROM size exponent is too large",
+            },
+        )?;
+        self.chr_rom_size = nes2_rom_size(data[5], data[9] >> 4, CHARACTER_ROM_UNIT).ok_or(
+            RomError::BadNES2Field {
+                offset: 5,
+                value: data[5],
+                reason: "CHR-ROM size exponent is too large",
+            },
+        )?;
+
+        self.prg_ram_size = nes2_ram_size(data[10] & 0x0F);
+        self.prg_nvram_size = nes2_ram_size(data[10] >> 4);
+        self.chr_ram_size = nes2_ram_size(data[11] & 0x0F);
+        self.chr_nvram_size = nes2_ram_size(data[11] >> 4);
+
+        self.timing = match data[12] & 0x03 {
+            0 => Timing::NTSC,
+            1 => Timing::PAL,
+            2 => Timing::MultiRegion,
+            _ => Timing::Dendy,
+        };
+
+        self.console_type = match data[7] & 0x03 {
+            0x00 => ConsoleType::NES,
+            0x01 => ConsoleType::VsSystem,
+            0x02 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(data[13] & 0x0F),
+        };
+        if self.console_type == ConsoleType::VsSystem {
+            let bad_field = RomError::BadNES2Field {
+                offset: 13,
+                value: data[13],
+                reason: "unknown Vs. System PPU or hardware type",
+            };
+            self.vs_system = Some(VsSystem {
+                ppu: VsPPUType::from_nibble(data[13] & 0x0F).ok_or(bad_field.clone())?,
+                hardware: VsHardwareType::from_nibble(data[13] >> 4).ok_or(bad_field)?,
+            });
+        }
+
+        self.misc_roms = data[14] & 0x03;
+        self.expansion_device = ExpansionDevice::from_byte(data[15] & 0x3F);
+
+        Ok(())
+    }
+}
+
+/// NES 2.0 ROM sizes are either a 12-bit count of `unit`s, or, when the
+/// upper nibble is $F, 2^E * (MM * 2 + 1) bytes encoded as EEEEEEMM.
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> Option<usize> {
+    let msb = msb as u16;
+    if msb == ROM_SIZE_EXPONENT_NOTATION {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        1usize.checked_shl(exponent)?.checked_mul(multiplier)
+    } else {
+        Some(((msb << 8) | lsb as u16) as usize * unit)
+    }
+}
+
+/// NES 2.0 RAM sizes are shift counts: 0 means none, otherwise 64 << n.
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(bytes_4_to_15: [u8; 12]) -> [u8; HEADER_BYTES] {
+        let mut data = [0; HEADER_BYTES];
+        data[0..4].copy_from_slice(&INES_HEADER_START);
+        data[4..].copy_from_slice(&bytes_4_to_15);
+        data
+    }
+
+    #[test]
+    fn parses_ines_header() {
+        // 128KB PRG, no CHR, mapper 2, vertical, battery, PAL.
+        let data = header([8, 0, 0x23, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.format, HeaderFormat::INES);
+        assert_eq!(header.prg_rom_size, 0x20000);
+        assert_eq!(header.chr_rom_size, 0);
+        assert_eq!(header.mapper, 2);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert!(header.battery);
+        assert_eq!(header.prg_nvram_size, 0x2000);
+        assert_eq!(header.prg_ram_size, 0);
+        assert_eq!(header.chr_ram_size, 0x2000);
+        assert_eq!(header.timing, Timing::PAL);
+    }
+
+    #[test]
+    fn ignores_garbage_in_archaic_ines_header() {
+        let mut data = header([2, 1, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data[7..16].copy_from_slice(b"DiskDude!");
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.format, HeaderFormat::ArchaicINES);
+        assert_eq!(header.mapper, 1);
+        assert_eq!(header.timing, Timing::NTSC);
+    }
+
+    #[test]
+    fn parses_nes2_header() {
+        // Mapper 0x105 submapper 3, PRG 0x102 units, 8KB PRG-NVRAM, 32KB
+        // CHR-RAM, Dendy timing, Zapper.
+        let data = header([
+            0x02, 0x04, 0x52, 0x08, 0x31, 0x01, 0x70, 0x09, 0x03, 0x00, 0x00, 0x08,
+        ]);
+        let header = Header::parse(&data).unwrap();
+        assert!(header.is_nes2());
+        assert_eq!(header.mapper, 0x105);
+        assert_eq!(header.submapper, 3);
+        assert_eq!(header.prg_rom_size, 0x102 * PROGRAM_ROM_UNIT);
+        assert_eq!(header.chr_rom_size, 4 * CHARACTER_ROM_UNIT);
+        assert_eq!(header.prg_ram_size, 0);
+        assert_eq!(header.prg_nvram_size, 0x2000);
+        assert_eq!(header.chr_ram_size, 0x8000);
+        assert_eq!(header.timing, Timing::Dendy);
+        assert_eq!(header.expansion_device, ExpansionDevice::Zapper);
+    }
+
+    #[test]
+    fn parses_nes2_vs_system_header() {
+        let data = header([2, 2, 0, 0x09, 0, 0, 0, 0, 0, 0x51, 0, 0]);
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.console_type, ConsoleType::VsSystem);
+        assert_eq!(
+            header.vs_system,
+            Some(VsSystem {
+                ppu: VsPPUType::RP2C03G,
+                hardware: VsHardwareType::DualSystem,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_nes2_exponent_multiplier_sizes() {
+        // PRG: 2^10 * (1 * 2 + 1) = 3KB. CHR: 2^13 * 1 = 8KB.
+        let data = header([0x29, 0x34, 0, 0x08, 0, 0xFF, 0, 0, 0, 0, 0, 0]);
+        let header = Header::parse(&data).unwrap();
+        assert_eq!(header.prg_rom_size, 3 * 1024);
+        assert_eq!(header.chr_rom_size, 8 * 1024);
+    }
+
+    #[test]
+    fn rejects_overflowing_exponent() {
+        let data = header([0xFF, 0, 0, 0x08, 0, 0x0F, 0, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            Header::parse(&data),
+            Err(RomError::BadNES2Field { offset: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_short_header() {
+        assert!(matches!(
+            Header::parse(&[0; HEADER_BYTES]),
+            Err(RomError::BadMagic { .. })
+        ));
+        assert!(matches!(
+            Header::parse(&INES_HEADER_START),
+            Err(RomError::TruncatedHeader { length: 4 })
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::bus::{BusAddr, ByteReadable};
+use crate::bus::BusAddr;
 
 pub struct ProgramROM {
     pub data: Vec<u8>,
@@ -10,10 +10,8 @@ impl ProgramROM {
             data: data.to_vec(),
         }
     }
-}
 
-impl ByteReadable for ProgramROM {
-    fn read_byte(&self, addr: BusAddr) -> u8 {
+    pub fn read_byte(&self, addr: BusAddr) -> u8 {
         self.data[addr as usize]
     }
 }
@@ -0,0 +1,353 @@
+use super::{
+    CharacterROM, ConsoleType, ExpansionDevice, Header, HeaderFormat, ProgramROM, RomError, Timing,
+};
+use crate::ppu::Mirroring;
+
+pub const UNIF_MAGIC: &[u8] = b"UNIF";
+const UNIF_HEADER_BYTES: usize = 32;
+const CHUNK_HEADER_BYTES: usize = 8;
+const PROGRAM_RAM_SIZE: usize = 0x2000; // 8KB
+const CHARACTER_RAM_SIZE: usize = 0x2000; // 8KB
+
+/// Prefixes UNIF puts in front of board names to say who made the board.
+const BOARD_PREFIXES: &[&str] = &["NES-", "HVC-", "UNL-", "BMC-", "BTL-", "IREM-", "KONAMI-"];
+
+/// UNIF board names, without their prefix, and the iNES mapper and
+/// submapper emulating them. Only licensed boards are listed: the UNL- and
+/// BMC- boards are pirate and multicart hardware that none of the mappers
+/// emulate, so they are reported as unsupported.
+const BOARDS: &[(&str, u16, u8)] = &[
+    ("NROM", 0, 0),
+    ("NROM-128", 0, 0),
+    ("NROM-256", 0, 0),
+    ("RROM", 0, 0),
+    ("RROM-128", 0, 0),
+    ("SAROM", 1, 0),
+    ("SBROM", 1, 0),
+    ("SCROM", 1, 0),
+    ("SEROM", 1, 5),
+    ("SFROM", 1, 0),
+    ("SGROM", 1, 0),
+    ("SHROM", 1, 5),
+    ("SH1ROM", 1, 5),
+    ("SIROM", 1, 0),
+    ("SJROM", 1, 0),
+    ("SKROM", 1, 0),
+    ("SLROM", 1, 0),
+    ("SL1ROM", 1, 0),
+    ("SL2ROM", 1, 0),
+    ("SL3ROM", 1, 0),
+    ("SLRROM", 1, 0),
+    ("SMROM", 1, 0),
+    ("SNROM", 1, 0),
+    ("SOROM", 1, 0),
+    ("SUROM", 1, 0),
+    ("SXROM", 1, 0),
+    ("UNROM", 2, 0),
+    ("UOROM", 2, 0),
+    ("CNROM", 3, 0),
+    ("TBROM", 4, 0),
+    ("TEROM", 4, 0),
+    ("HKROM", 4, 1),
+    ("TFROM", 4, 0),
+    ("TGROM", 4, 0),
+    ("TKROM", 4, 0),
+    ("TLROM", 4, 0),
+    ("TL1ROM", 4, 0),
+    ("TNROM", 4, 0),
+    ("TR1ROM", 4, 0),
+    ("TSROM", 4, 0),
+    ("TVROM", 4, 0),
+    ("EKROM", 5, 0),
+    ("ELROM", 5, 0),
+    ("ETROM", 5, 0),
+    ("EWROM", 5, 0),
+    ("AMROM", 7, 0),
+    ("ANROM", 7, 0),
+    ("AN1ROM", 7, 0),
+    ("AOROM", 7, 0),
+    ("PNROM", 9, 0),
+    ("FJROM", 10, 0),
+    ("FKROM", 10, 0),
+    ("GNROM", 66, 0),
+    ("MHROM", 66, 0),
+    ("JLROM", 69, 0),
+    ("JSROM", 69, 0),
+];
+
+/// A UNIF image converted to the header and ROMs an iNES file would have.
+pub struct UNIF {
+    pub board: String,
+    pub header: Header,
+    pub program_rom: ProgramROM,
+    pub character_rom: CharacterROM,
+}
+
+impl UNIF {
+    /// Reads the MAPR, PRG0-PRGF, CHR0-CHRF, MIRR, BATR and TVCI chunks.
+    /// Other chunks only describe the dump and are skipped.
+    pub fn parse(data: &[u8]) -> Result<UNIF, RomError> {
+        if data.len() < UNIF_HEADER_BYTES {
+            return Err(RomError::TruncatedChunk {
+                id: String::from_utf8_lossy(UNIF_MAGIC).into_owned(),
+                offset: 0,
+                expected: UNIF_HEADER_BYTES,
+                available: data.len(),
+            });
+        }
+        if !data.starts_with(UNIF_MAGIC) {
+            return Err(RomError::BadMagic {
+                found: data[0..4].to_vec(),
+            });
+        }
+
+        let mut board = None;
+        let mut program_chunks: [Option<&[u8]>; 16] = [None; 16];
+        let mut character_chunks: [Option<&[u8]>; 16] = [None; 16];
+        let mut mirroring = Mirroring::Horizontal;
+        let mut battery = false;
+        let mut timing = Timing::NTSC;
+
+        let mut offset = UNIF_HEADER_BYTES;
+        while offset < data.len() {
+            let (id, chunk) = read_chunk(data, offset)?;
+            offset += CHUNK_HEADER_BYTES + chunk.len();
+            match &id {
+                b"MAPR" => {
+                    let name = chunk.split(|b| *b == 0).next().unwrap_or_default();
+                    board = Some(String::from_utf8_lossy(name).trim().to_string());
+                }
+                [b'P', b'R', b'G', bank] => {
+                    if let Some(bank) = hex_digit(*bank) {
+                        program_chunks[bank] = Some(chunk);
+                    }
+                }
+                [b'C', b'H', b'R', bank] => {
+                    if let Some(bank) = hex_digit(*bank) {
+                        character_chunks[bank] = Some(chunk);
+                    }
+                }
+                b"MIRR" => {
+                    mirroring = match chunk.first() {
+                        Some(1) => Mirroring::Vertical,
+                        Some(2) => Mirroring::SingleScreenLower,
+                        Some(3) => Mirroring::SingleScreenUpper,
+                        Some(4) => Mirroring::FourScreen,
+                        // 0 is hard-wired horizontal, 5 is up to the mapper.
+                        _ => Mirroring::Horizontal,
+                    }
+                }
+                b"BATR" => battery = true,
+                b"TVCI" => {
+                    timing = match chunk.first() {
+                        Some(1) => Timing::PAL,
+                        Some(2) => Timing::MultiRegion,
+                        _ => Timing::NTSC,
+                    }
+                }
+                _ => { /* Not needed for emulation */ }
+            }
+        }
+
+        let board = board.ok_or(RomError::MissingBoard)?;
+        let (mapper, submapper) =
+            board_mapper(&board).ok_or_else(|| RomError::UnsupportedBoard {
+                board: board.clone(),
+            })?;
+        let program_rom = program_chunks
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>()
+            .concat();
+        if program_rom.is_empty() {
+            return Err(RomError::MissingPRG);
+        }
+        let character_rom = character_chunks
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>()
+            .concat();
+
+        let header = Header {
+            format: HeaderFormat::UNIF,
+            prg_rom_size: program_rom.len(),
+            chr_rom_size: character_rom.len(),
+            mapper,
+            submapper,
+            mirroring,
+            battery,
+            trainer: false,
+            console_type: ConsoleType::NES,
+            prg_ram_size: if battery { 0 } else { PROGRAM_RAM_SIZE },
+            prg_nvram_size: if battery { PROGRAM_RAM_SIZE } else { 0 },
+            chr_ram_size: if character_rom.is_empty() {
+                CHARACTER_RAM_SIZE
+            } else {
+                0
+            },
+            chr_nvram_size: 0,
+            timing,
+            vs_system: None,
+            misc_roms: 0,
+            expansion_device: ExpansionDevice::Unspecified,
+        };
+
+        Ok(UNIF {
+            board,
+            header,
+            program_rom: ProgramROM::new(&program_rom),
+            character_rom: CharacterROM::new(&character_rom),
+        })
+    }
+}
+
+/// Returns the 4-byte ID and the data of the chunk at `offset`.
+fn read_chunk(data: &[u8], offset: usize) -> Result<([u8; 4], &[u8]), RomError> {
+    let chunk_header = data
+        .get(offset..offset + CHUNK_HEADER_BYTES)
+        .ok_or_else(|| RomError::TruncatedChunk {
+            id: String::from_utf8_lossy(&data[offset..]).into_owned(),
+            offset,
+            expected: CHUNK_HEADER_BYTES,
+            available: data.len() - offset,
+        })?;
+    let id: [u8; 4] = chunk_header[0..4].try_into().unwrap();
+    let length = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as usize;
+    let start = offset + CHUNK_HEADER_BYTES;
+    let chunk = data
+        .get(start..start.saturating_add(length))
+        .ok_or_else(|| RomError::TruncatedChunk {
+            id: String::from_utf8_lossy(&id).into_owned(),
+            offset: start,
+            expected: length,
+            available: data.len() - start,
+        })?;
+    Ok((id, chunk))
+}
+
+fn hex_digit(digit: u8) -> Option<usize> {
+    (digit as char).to_digit(16).map(|digit| digit as usize)
+}
+
+fn board_mapper(board: &str) -> Option<(u16, u8)> {
+    let name = BOARD_PREFIXES
+        .iter()
+        .find_map(|prefix| board.strip_prefix(prefix))
+        .unwrap_or(board);
+    BOARDS
+        .iter()
+        .find(|(known, _, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, mapper, submapper)| (*mapper, *submapper))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut data = UNIF_MAGIC.to_vec();
+        data.resize(UNIF_HEADER_BYTES, 0);
+        data[4] = 7;
+        for (id, chunk) in chunks {
+            data.extend_from_slice(*id);
+            data.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            data.extend_from_slice(chunk);
+        }
+        data
+    }
+
+    #[test]
+    fn concatenates_rom_chunks_in_bank_order() {
+        let data = image(&[
+            (b"MAPR", b"NES-SLROM\0"),
+            (b"READ", b"Dumped by nobody\0"),
+            (b"PRG1", &[0x11; 4]),
+            (b"CHR1", &[0x21; 2]),
+            (b"PRG0", &[0x10; 4]),
+            (b"CHR0", &[0x20; 2]),
+        ]);
+        let unif = UNIF::parse(&data).unwrap();
+        assert_eq!(unif.board, "NES-SLROM");
+        assert_eq!(unif.header.format, HeaderFormat::UNIF);
+        assert_eq!(unif.header.mapper, 1);
+        assert_eq!(
+            unif.program_rom.data,
+            [0x10, 0x10, 0x10, 0x10, 0x11, 0x11, 0x11, 0x11]
+        );
+        assert_eq!(unif.character_rom.as_bytes(), [0x20, 0x20, 0x21, 0x21]);
+        assert_eq!(unif.header.prg_rom_size, 8);
+        assert_eq!(unif.header.chr_rom_size, 4);
+        assert_eq!(unif.header.chr_ram_size, 0);
+        assert_eq!(unif.header.mirroring, Mirroring::Horizontal);
+        assert!(!unif.header.battery);
+        assert_eq!(unif.header.prg_ram_size, PROGRAM_RAM_SIZE);
+    }
+
+    #[test]
+    fn reads_mirroring_battery_and_timing() {
+        let data = image(&[
+            (b"MAPR", b"HVC-UNROM\0"),
+            (b"PRG0", &[0; 4]),
+            (b"MIRR", &[1]),
+            (b"BATR", &[0]),
+            (b"TVCI", &[1]),
+        ]);
+        let unif = UNIF::parse(&data).unwrap();
+        assert_eq!(unif.header.mapper, 2);
+        assert_eq!(unif.header.mirroring, Mirroring::Vertical);
+        assert!(unif.header.battery);
+        assert_eq!(unif.header.prg_ram_size, 0);
+        assert_eq!(unif.header.prg_nvram_size, PROGRAM_RAM_SIZE);
+        assert_eq!(unif.header.timing, Timing::PAL);
+        assert_eq!(unif.header.chr_ram_size, CHARACTER_RAM_SIZE);
+    }
+
+    #[test]
+    fn maps_single_screen_and_four_screen_mirroring() {
+        for (value, mirroring) in [
+            (0, Mirroring::Horizontal),
+            (2, Mirroring::SingleScreenLower),
+            (3, Mirroring::SingleScreenUpper),
+            (4, Mirroring::FourScreen),
+            (5, Mirroring::Horizontal),
+        ] {
+            let data = image(&[
+                (b"MAPR", b"NES-AOROM\0"),
+                (b"PRG0", &[0; 4]),
+                (b"MIRR", &[value]),
+            ]);
+            assert_eq!(UNIF::parse(&data).unwrap().header.mirroring, mirroring);
+        }
+    }
+
+    #[test]
+    fn rejects_missing_and_unsupported_boards() {
+        let data = image(&[(b"PRG0", &[0; 4])]);
+        assert!(matches!(UNIF::parse(&data), Err(RomError::MissingBoard)));
+
+        let data = image(&[(b"MAPR", b"BMC-GK-192\0"), (b"PRG0", &[0; 4])]);
+        assert!(matches!(
+            UNIF::parse(&data),
+            Err(RomError::UnsupportedBoard { board }) if board == "BMC-GK-192"
+        ));
+
+        let data = image(&[(b"MAPR", b"NES-NROM-256\0")]);
+        assert!(matches!(UNIF::parse(&data), Err(RomError::MissingPRG)));
+    }
+
+    #[test]
+    fn rejects_truncated_chunk() {
+        let mut data = image(&[(b"MAPR", b"NES-NROM\0"), (b"PRG0", &[0; 4])]);
+        data.truncate(data.len() - 1);
+        assert!(matches!(
+            UNIF::parse(&data),
+            Err(RomError::TruncatedChunk {
+                expected: 4,
+                available: 3,
+                ..
+            })
+        ));
+    }
+}
@@ -1,17 +1,42 @@
+#![allow(
+    clippy::upper_case_acronyms,
+    clippy::new_without_default,
+    clippy::needless_late_init
+)]
+
 mod apu;
 mod bus;
 mod cpu;
 mod dma;
+mod fds;
+mod hash;
 mod ines;
+pub mod mapper;
+mod movie;
+mod nsf;
 mod pad;
+mod patch;
 mod ppu;
 mod ram;
+mod region;
+pub mod wav;
 
-pub use apu::APU;
+pub use apu::{Channel, APU};
 pub use bus::Bus;
 pub use cpu::CPU;
 pub use dma::DMA;
-pub use ines::iNES;
-pub use pad::Pad;
-pub use ppu::PPU;
+pub use fds::{FDSAdapter, FDS};
+pub use ines::{
+    iNES, ConsoleType, ExpansionDevice, GameDatabase, GameEntry, Header, HeaderCorrection,
+    HeaderFormat, RomError, Timing, VsHardwareType, VsPPUType, VsSystem, UNIF,
+};
+pub use mapper::{BatterySave, Mapper};
+pub use movie::{Movie, MovieFrame};
+pub use nsf::{NSFMemory, NSFPlayer, NSF};
+pub use pad::{
+    Button, FamilyBasicKeyboard, Multitap, Pad, Peripheral, Player, Port, PowerPad, Vaus, Zapper,
+};
+pub use patch::{Patch, PatchFormat};
+pub use ppu::{rgb, Mirroring, PPU, SCREEN_HEIGHT, SCREEN_WIDTH};
 pub use ram::RAM;
+pub use region::Region;
@@ -1,18 +1,133 @@
 use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use nes::{iNES, Bus, Pad, APU, CPU, DMA, PPU, RAM};
+use nes::{
+    iNES, mapper, wav, BatterySave, Bus, Channel, FDSAdapter, GameDatabase, Movie, Multitap,
+    NSFMemory, NSFPlayer, Pad, Patch, Region, Timing, APU, CPU, DMA, FDS, NSF, PPU, RAM,
+};
+
+/// How often battery-backed memory and disk writes are written out while a
+/// game runs, so that little is lost if the emulator does not exit cleanly.
+const SAVE_INTERVAL_FRAMES: u64 = 60;
+
+/// Set on SIGINT or SIGTERM, so that the emulation stops and the save,
+/// movie and audio are written out before exiting.
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::SeqCst)
+}
+
+struct Options {
+    ines_rom_path: String,
+    frames: Option<u64>,
+    /// Converted to frames once the ROM's region is known.
+    seconds: Option<f64>,
+    wav_path: Option<String>,
+    muted_channels: Vec<Channel>,
+    soloed_channels: Vec<Channel>,
+    stems_dir: Option<String>,
+    movie_path: Option<String>,
+    record_path: Option<String>,
+    database_path: Option<String>,
+    patch_paths: Vec<String>,
+    fds_bios_path: Option<String>,
+}
+
+impl Options {
+    /// Number of frames to run for, if limited by --frames or --seconds.
+    fn frames(&self, region: Region) -> Option<u64> {
+        self.frames.or_else(|| {
+            self.seconds
+                .map(|seconds| (seconds * region.frame_rate()).ceil() as u64)
+        })
+    }
+}
+
+struct NSFOptions {
+    nsf_path: String,
+    track: Option<u8>,
+    seconds: f64,
+    wav_path: String,
+    region: Option<Region>,
+}
 
 fn main() {
     let args = env::args().collect::<Vec<_>>();
-    if args.len() != 2 {
-        usage(&args[0]);
+    if args.get(1).map(String::as_str) == Some("nsf") {
+        match parse_nsf_options(&args[2..]) {
+            Ok(options) => play_nsf(options),
+            Err(message) => {
+                eprintln!("{}", message);
+                usage(&args[0]);
+            }
+        }
         return;
     }
 
-    let ines_rom_path = args[1].clone();
-    let ines_rom = read_rom_file(&ines_rom_path);
+    let options = match parse_options(&args[1..]) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            usage(&args[0]);
+            return;
+        }
+    };
+    if let Err(error) = ctrlc::set_handler(|| STOP_REQUESTED.store(true, Ordering::SeqCst)) {
+        eprintln!("Could not install the signal handler: {}", error);
+    }
 
-    let ines = iNES::parse(&ines_rom).unwrap();
+    let mut database = GameDatabase::built_in();
+    if let Some(database_path) = options.database_path.as_ref() {
+        match GameDatabase::load(database_path) {
+            Ok(loaded) => database.extend(loaded),
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut rom_data = match std::fs::read(&options.ines_rom_path) {
+        Ok(rom_data) => rom_data,
+        Err(error) => {
+            eprintln!("{}: {}", options.ines_rom_path, error);
+            std::process::exit(1);
+        }
+    };
+    for patch_path in options.patch_paths.iter() {
+        let patched = Patch::load(patch_path).and_then(|patch| {
+            patch
+                .apply(&rom_data)
+                .map_err(|message| format!("{}: {}", patch_path, message))
+        });
+        match patched {
+            Ok(patched) => rom_data = patched,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+        eprintln!("Applied patch {}", patch_path);
+    }
+
+    if FDS::is_fds(&rom_data) || options.ines_rom_path.to_ascii_lowercase().ends_with(".fds") {
+        run_fds(&options, &rom_data);
+        return;
+    }
+
+    let ines = match iNES::parse_with_database(&rom_data, &database) {
+        Ok((ines, corrections)) => {
+            for correction in corrections {
+                eprintln!("Corrected header from database: {}", correction);
+            }
+            ines
+        }
+        Err(error) => {
+            eprintln!("{}: {}", options.ines_rom_path, error);
+            std::process::exit(1);
+        }
+    };
     eprintln!("Successfully read ines header");
 
     println!(
@@ -20,36 +135,574 @@ fn main() {
         ines.characterROM.number_of_sprites()
     );
 
+    let movie = options.movie_path.as_ref().map(|path| {
+        let loaded = Movie::load(path).and_then(|movie| {
+            movie
+                .verify_rom(&ines)
+                .map_err(|message| format!("{}: {}", path, message))?;
+            Ok(movie)
+        });
+        match loaded {
+            Ok(movie) => movie,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    });
+    let mut region = match ines.header.timing {
+        Timing::PAL => Region::PAL,
+        _ => Region::NTSC,
+    };
+    // A movie plays back on the console it was recorded on.
+    if let Some(movie) = movie.as_ref() {
+        let movie_region = if movie.pal { Region::PAL } else { Region::NTSC };
+        if movie_region != region {
+            eprintln!(
+                "Movie is flagged {:?} but the ROM header says {:?}, following the movie",
+                movie_region, region
+            );
+            region = movie_region;
+        }
+    }
+    let mut recording = options.record_path.as_ref().map(|_| {
+        let rom_filename = std::path::Path::new(&options.ines_rom_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut recording = Movie::new(&rom_filename, &ines);
+        recording.pal = region == Region::PAL;
+        recording
+    });
+    let frames = options
+        .frames(region)
+        .or_else(|| movie.as_ref().map(|movie| movie.frames.len() as u64));
+
+    let mut mapper = match mapper::from_ines(&ines) {
+        Ok(mapper) => mapper,
+        Err(error) => {
+            eprintln!("{}: {}", options.ines_rom_path, error);
+            std::process::exit(1);
+        }
+    };
+    let mut battery_save = match BatterySave::load(&options.ines_rom_path, mapper.as_mut()) {
+        Ok(battery_save) => {
+            if let Some(battery_save) = battery_save.as_ref().filter(|save| save.loaded()) {
+                eprintln!("Loaded battery save from {}", battery_save.path());
+            }
+            battery_save
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    let mut ram = RAM::new();
+    let mut ppu = PPU::with_region(region);
+    let mut apu = APU::with_region(region);
+    let mut pad = Pad::new();
+    let mut dma = DMA::new();
+    let mut cpu_bus = Bus::new(
+        &mut ram,
+        mapper.as_mut(),
+        &mut ppu,
+        &mut apu,
+        &mut pad,
+        &mut dma,
+    );
+    let mut cpu = CPU::new(&mut cpu_bus);
+    cpu.set_trace(
+        options.wav_path.is_none()
+            && options.stems_dir.is_none()
+            && options.movie_path.is_none()
+            && options.record_path.is_none(),
+    );
+
+    configure_apu(cpu.bus_mut().apu_mut(), &options);
+
+    if movie.as_ref().is_some_and(|movie| movie.fourscore) {
+        cpu.bus_mut()
+            .pad_mut()
+            .set_multitap(Some(Multitap::FourScore));
+    }
+
+    cpu.boot();
+    match frames {
+        Some(frames) => {
+            for frame in 0..frames as usize {
+                if stop_requested() {
+                    break;
+                }
+                let mut commands = 0;
+                if let Some(input) = movie.as_ref().and_then(|movie| movie.frames.get(frame)) {
+                    if input.is_soft_reset() {
+                        cpu.reset();
+                    }
+                    commands = input.commands;
+                    input.apply(cpu.bus_mut().pad_mut());
+                }
+                if let Some(recording) = recording.as_mut() {
+                    recording.record_frame(cpu.bus_mut().pad_mut(), commands);
+                }
+                cpu.run_frame().unwrap();
+                if let Some(battery_save) = battery_save.as_mut() {
+                    if cpu
+                        .bus_mut()
+                        .frame_count()
+                        .is_multiple_of(SAVE_INTERVAL_FRAMES)
+                    {
+                        flush_battery_save(battery_save, cpu.bus_mut());
+                    }
+                }
+            }
+        }
+        None => {
+            while !stop_requested() {
+                cpu.run_frame().unwrap();
+                if let Some(battery_save) = battery_save.as_mut() {
+                    if cpu
+                        .bus_mut()
+                        .frame_count()
+                        .is_multiple_of(SAVE_INTERVAL_FRAMES)
+                    {
+                        flush_battery_save(battery_save, cpu.bus_mut());
+                    }
+                }
+            }
+        }
+    }
+    if let Some(battery_save) = battery_save.as_mut() {
+        flush_battery_save(battery_save, cpu.bus_mut());
+    }
+
+    if let (Some(recording), Some(record_path)) = (recording, options.record_path.as_ref()) {
+        if let Err(message) = recording.save(record_path) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        eprintln!(
+            "Recorded {} frames to {}",
+            recording.frames.len(),
+            record_path
+        );
+    }
+
+    save_audio(&mut apu, &options);
+}
+
+/// Writes the battery-backed memory out if it changed, reporting failures
+/// without stopping the emulation.
+fn flush_battery_save(battery_save: &mut BatterySave, bus: &Bus) {
+    if let Err(message) = battery_save.flush(bus.save_data()) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Keeps the `.ips` file next to an FDS image in step with what the game
+/// wrote to the disk.
+struct DiskSave {
+    original: FDS,
+    image_path: String,
+    /// The disk as last saved, to skip writes when nothing changed.
+    saved: Vec<u8>,
+}
+
+impl DiskSave {
+    fn new(original: FDS, fds_adapter: &FDSAdapter, image_path: &str) -> DiskSave {
+        // An unreadable disk is reported by the first flush.
+        let saved = fds_adapter
+            .disk(&original)
+            .map(|disk| disk.to_bytes())
+            .unwrap_or_default();
+        DiskSave {
+            original,
+            image_path: image_path.to_string(),
+            saved,
+        }
+    }
+
+    /// Writes the disk out if the game wrote to it since the last write.
+    fn flush(&mut self, fds_adapter: &FDSAdapter) {
+        if !fds_adapter.is_modified() {
+            return;
+        }
+        let disk = match fds_adapter.disk(&self.original) {
+            Ok(disk) => disk,
+            Err(message) => {
+                eprintln!("{}: {}", self.image_path, message);
+                return;
+            }
+        };
+        let data = disk.to_bytes();
+        if data == self.saved {
+            return;
+        }
+        match disk.save(&self.original, &self.image_path) {
+            Ok(()) => {
+                self.saved = data;
+                eprintln!("Saved disk writes to {}", FDS::save_path(&self.image_path));
+            }
+            Err(message) => eprintln!("{}", message),
+        }
+    }
+
+    fn flush_periodically(&mut self, bus: &mut Bus) {
+        if !bus.frame_count().is_multiple_of(SAVE_INTERVAL_FRAMES) {
+            return;
+        }
+        if let Some(fds_adapter) = bus.fds_adapter_mut() {
+            self.flush(fds_adapter);
+        }
+    }
+}
+
+/// Runs a Famicom Disk System image, saving what the game wrote to the
+/// disk next to the image as it runs and when it stops.
+fn run_fds(options: &Options, image_data: &[u8]) {
+    let (original, mut fds_adapter) = match load_fds(options, image_data) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    eprintln!("Loaded FDS image with {} sides", fds_adapter.side_count());
+    let mut disk_save = DiskSave::new(original, &fds_adapter, &options.ines_rom_path);
+
     let mut ram = RAM::new();
     let mut ppu = PPU::new();
     let mut apu = APU::new();
     let mut pad = Pad::new();
     let mut dma = DMA::new();
-    let mut cpu_bus = Bus::new(
+    let mut cpu_bus = Bus::new_fds(
         &mut ram,
-        &ines.programROM,
+        &mut fds_adapter,
         &mut ppu,
         &mut apu,
         &mut pad,
         &mut dma,
     );
     let mut cpu = CPU::new(&mut cpu_bus);
+    cpu.set_trace(options.wav_path.is_none() && options.stems_dir.is_none());
+    configure_apu(cpu.bus_mut().apu_mut(), options);
 
     cpu.boot();
-    loop {
-        cpu.run_single_cycle().unwrap();
+    let frames = options.frames(Region::NTSC).unwrap_or(u64::MAX);
+    for _ in 0..frames {
+        if stop_requested() {
+            break;
+        }
+        cpu.run_frame().unwrap();
+        disk_save.flush_periodically(cpu.bus_mut());
+    }
+
+    disk_save.flush(&fds_adapter);
+    save_audio(&mut apu, options);
+}
+
+/// Reads the BIOS and the image, with the disk writes of earlier runs
+/// applied. Also returns the image as it was before those writes.
+fn load_fds(options: &Options, image_data: &[u8]) -> Result<(FDS, FDSAdapter), String> {
+    if options.movie_path.is_some() || options.record_path.is_some() {
+        return Err("--movie and --record are not supported for FDS images".to_string());
+    }
+    let bios_path = options
+        .fds_bios_path
+        .as_ref()
+        .ok_or("FDS images require --fds-bios")?;
+    let bios = std::fs::read(bios_path).map_err(|e| format!("{}: {}", bios_path, e))?;
+    let original = FDS::parse(image_data)
+        .map_err(|message| format!("{}: {}", options.ines_rom_path, message))?;
+    let mut fds = original.clone();
+    if fds.load_save(&options.ines_rom_path)? {
+        eprintln!(
+            "Loaded disk writes from {}",
+            FDS::save_path(&options.ines_rom_path)
+        );
+    }
+    let fds_adapter = FDSAdapter::new(&bios, &fds)?;
+    Ok((original, fds_adapter))
+}
+
+fn configure_apu(apu: &mut APU, options: &Options) {
+    for channel in options.muted_channels.iter() {
+        apu.set_muted(*channel, true);
+    }
+    for channel in options.soloed_channels.iter() {
+        apu.set_soloed(*channel, true);
+    }
+    if options.stems_dir.is_some() {
+        apu.enable_stems();
+    }
+}
+
+fn save_audio(apu: &mut APU, options: &Options) {
+    if let Some(wav_path) = options.wav_path.as_ref() {
+        let samples = apu.take_samples();
+        if let Err(error) = wav::save_wav(wav_path, apu.sample_rate(), &samples) {
+            eprintln!("{}: {}", wav_path, error);
+            std::process::exit(1);
+        }
+        eprintln!("Wrote {} samples to {}", samples.len(), wav_path);
+    }
+
+    if let Some(stems_dir) = options.stems_dir.as_ref() {
+        if let Err(error) = std::fs::create_dir_all(stems_dir) {
+            eprintln!("{}: {}", stems_dir, error);
+            std::process::exit(1);
+        }
+        for channel in Channel::ALL {
+            let samples = apu.take_stem_samples(channel);
+            let stem_path = std::path::Path::new(stems_dir).join(format!("{}.wav", channel));
+            let stem_path = stem_path.to_string_lossy();
+            if let Err(error) = wav::save_wav(&stem_path, apu.sample_rate(), &samples) {
+                eprintln!("{}: {}", stem_path, error);
+                std::process::exit(1);
+            }
+            eprintln!("Wrote {} stem to {}", channel, stem_path);
+        }
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut ines_rom_path = None;
+    let mut frames = None;
+    let mut seconds = None;
+    let mut wav_path = None;
+    let mut muted_channels = Vec::new();
+    let mut soloed_channels = Vec::new();
+    let mut stems_dir = None;
+    let mut movie_path = None;
+    let mut record_path = None;
+    let mut database_path = None;
+    let mut patch_paths = Vec::new();
+    let mut fds_bios_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let value = args.next().ok_or("--frames requires a value")?;
+                let value = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid frame count: {}", value))?;
+                frames = Some(value);
+                seconds = None;
+            }
+            "--seconds" => {
+                let value = args.next().ok_or("--seconds requires a value")?;
+                seconds = Some(parse_seconds(value)?);
+                frames = None;
+            }
+            "--wav" => {
+                let value = args.next().ok_or("--wav requires a path")?;
+                wav_path = Some(value.clone());
+            }
+            "--mute" => {
+                let value = args.next().ok_or("--mute requires a channel list")?;
+                muted_channels.extend(parse_channels(value)?);
+            }
+            "--solo" => {
+                let value = args.next().ok_or("--solo requires a channel list")?;
+                soloed_channels.extend(parse_channels(value)?);
+            }
+            "--stems" => {
+                let value = args.next().ok_or("--stems requires a directory")?;
+                stems_dir = Some(value.clone());
+            }
+            "--movie" => {
+                let value = args.next().ok_or("--movie requires a path")?;
+                movie_path = Some(value.clone());
+            }
+            "--patch" => {
+                let value = args.next().ok_or("--patch requires a path")?;
+                patch_paths.push(value.clone());
+            }
+            "--fds-bios" => {
+                let value = args.next().ok_or("--fds-bios requires a path")?;
+                fds_bios_path = Some(value.clone());
+            }
+            "--db" => {
+                let value = args.next().ok_or("--db requires a path")?;
+                database_path = Some(value.clone());
+            }
+            "--record" => {
+                let value = args.next().ok_or("--record requires a path")?;
+                record_path = Some(value.clone());
+            }
+            _ if ines_rom_path.is_none() && !arg.starts_with("--") => {
+                ines_rom_path = Some(arg.clone());
+            }
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    let ines_rom_path = ines_rom_path.ok_or("Missing <ines> argument")?;
+    if (wav_path.is_some() || stems_dir.is_some() || record_path.is_some())
+        && frames.is_none()
+        && seconds.is_none()
+        && movie_path.is_none()
+    {
+        return Err(
+            "--wav, --stems and --record require --frames, --seconds or --movie".to_string(),
+        );
+    }
+
+    Ok(Options {
+        ines_rom_path,
+        frames,
+        seconds,
+        wav_path,
+        muted_channels,
+        soloed_channels,
+        stems_dir,
+        movie_path,
+        record_path,
+        database_path,
+        patch_paths,
+        fds_bios_path,
+    })
+}
+
+fn play_nsf(options: NSFOptions) {
+    let nsf_data = match read_rom_file(&options.nsf_path) {
+        Ok(nsf_data) => nsf_data,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+    let nsf = match NSF::parse(&nsf_data) {
+        Ok(nsf) => nsf,
+        Err(message) => {
+            eprintln!("{}: {}", options.nsf_path, message);
+            std::process::exit(1);
+        }
+    };
+    eprintln!(
+        "\"{}\" by {} ({} songs)",
+        nsf.song_name, nsf.artist, nsf.total_songs
+    );
+    if nsf.expansion_chips != 0 {
+        eprintln!(
+            "Expansion audio (chips 0x{:02X}) is not emulated and will be silent",
+            nsf.expansion_chips
+        );
     }
+
+    let region = options.region.unwrap_or_else(|| nsf.preferred_region());
+    let track = options.track.unwrap_or(nsf.starting_song);
+
+    let mut ram = RAM::new();
+    let mut nsf_memory = NSFMemory::new(&nsf);
+    let mut ppu = PPU::new();
+    let mut apu = APU::with_region(region);
+    let mut pad = Pad::new();
+    let mut dma = DMA::new();
+    let mut cpu_bus = Bus::new_nsf(
+        &mut ram,
+        &mut nsf_memory,
+        &mut ppu,
+        &mut apu,
+        &mut pad,
+        &mut dma,
+    );
+    let mut cpu = CPU::new(&mut cpu_bus);
+    cpu.set_trace(false);
+
+    let cpu_clock_rate = region.cpu_clock_rate() as u64;
+    let mut player = NSFPlayer::new(&nsf, region);
+    let cycles = (options.seconds * cpu_clock_rate as f64).ceil() as u64;
+    let played = player
+        .init(&mut cpu, track, cpu_clock_rate)
+        .and_then(|()| player.run(&mut cpu, cycles));
+    if let Err(message) = played {
+        eprintln!("{}: {}", options.nsf_path, message);
+        std::process::exit(1);
+    }
+
+    let samples = apu.take_samples();
+    if let Err(error) = wav::save_wav(&options.wav_path, apu.sample_rate(), &samples) {
+        eprintln!("{}: {}", options.wav_path, error);
+        std::process::exit(1);
+    }
+    eprintln!(
+        "Wrote track {} ({} samples) to {}",
+        track,
+        samples.len(),
+        options.wav_path
+    );
+}
+
+fn parse_nsf_options(args: &[String]) -> Result<NSFOptions, String> {
+    let mut nsf_path = None;
+    let mut track = None;
+    let mut seconds = None;
+    let mut wav_path = None;
+    let mut region = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--track" => {
+                let value = args.next().ok_or("--track requires a value")?;
+                let value = value
+                    .parse::<u8>()
+                    .map_err(|_| format!("Invalid track number: {}", value))?;
+                track = Some(value);
+            }
+            "--seconds" => {
+                let value = args.next().ok_or("--seconds requires a value")?;
+                seconds = Some(parse_seconds(value)?);
+            }
+            "--out" => {
+                let value = args.next().ok_or("--out requires a path")?;
+                wav_path = Some(value.clone());
+            }
+            "--region" => {
+                let value = args.next().ok_or("--region requires a value")?;
+                region = Some(value.parse()?);
+            }
+            _ if nsf_path.is_none() && !arg.starts_with("--") => {
+                nsf_path = Some(arg.clone());
+            }
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    Ok(NSFOptions {
+        nsf_path: nsf_path.ok_or("Missing <nsf> argument")?,
+        track,
+        seconds: seconds.ok_or("nsf requires --seconds")?,
+        wav_path: wav_path.ok_or("nsf requires --out")?,
+        region,
+    })
 }
 
-fn read_rom_file(path: &str) -> Vec<u8> {
-    use std::io::Read;
+/// A finite, non-negative number of seconds.
+fn parse_seconds(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && !seconds.is_sign_negative() => Ok(seconds),
+        _ => Err(format!("Invalid number of seconds: {}", value)),
+    }
+}
 
-    let mut rom_file = std::fs::File::open(path).unwrap();
-    let mut rom_data = Vec::new();
-    rom_file.read_to_end(&mut rom_data).unwrap();
-    rom_data
+fn parse_channels(list: &str) -> Result<Vec<Channel>, String> {
+    list.split(',').map(|name| name.trim().parse()).collect()
+}
+
+fn read_rom_file(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("{}: {}", path, e))
 }
 
 fn usage(prog_name: &str) {
-    eprintln!("Usage: {} <ines>", prog_name);
+    eprintln!(
+        "Usage: {0} <ines|unif|fds> [--frames N | --seconds S] [--wav out.wav] [--stems DIR]\n\
+         \x20      [--mute CH,...] [--solo CH,...] [--movie in.fm2] [--record out.fm2]\n\
+         \x20      [--db games.xml|games.tsv] [--patch file.ips|ups|bps ...]\n\
+         \x20      [--fds-bios disksys.rom]\n\
+         \x20      {0} nsf <nsf|nsfe> --seconds S --out song.wav [--track N] [--region ntsc|pal]\n\
+         Channels: pulse1, pulse2, triangle, noise, dmc, expansion",
+        prog_name
+    );
 }
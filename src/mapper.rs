@@ -0,0 +1,419 @@
+mod axrom;
+mod battery;
+mod cnrom;
+mod fme7;
+mod gxrom;
+mod mmc1;
+mod mmc2;
+mod mmc3;
+mod mmc5;
+mod n163;
+mod nrom;
+mod uxrom;
+mod vrc4;
+mod vrc6;
+mod vrc7;
+mod vrc_irq;
+
+pub use axrom::AxROM;
+pub use battery::BatterySave;
+pub use cnrom::CNROM;
+pub use fme7::FME7;
+pub use gxrom::GxROM;
+pub use mmc1::MMC1;
+pub use mmc2::MMC2;
+pub use mmc3::MMC3;
+pub use mmc5::MMC5;
+pub use n163::N163;
+pub use nrom::NROM;
+pub use uxrom::UxROM;
+pub use vrc4::VRC4;
+pub use vrc6::VRC6;
+pub use vrc7::VRC7;
+
+use crate::{
+    bus::BusAddr,
+    ines::{iNES, RomError},
+    ppu::Mirroring,
+};
+
+const PRG_RAM_START_ADDR: BusAddr = 0x6000;
+const TRAINER_START_ADDR: BusAddr = 0x7000;
+/// PRG-RAM and CHR-RAM given to boards whose header does not say.
+const DEFAULT_PRG_RAM_SIZE: usize = 0x2000; // 8KB
+const DEFAULT_CHR_RAM_SIZE: usize = 0x2000; // 8KB
+/// NES 2.0 submapper of the discrete boards that have bus conflicts.
+const SUBMAPPER_BUS_CONFLICTS: u8 = 2;
+
+/// The cartridge as the console sees it: what answers on the CPU bus from
+/// $4020 up and on the PPU bus below $2000, how the nametables are
+/// mirrored, and anything else the board wires to the console.
+pub trait Mapper {
+    /// Reads from $4020-$FFFF.
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8;
+
+    /// Writes to $4020-$FFFF, including the mapper's registers.
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8);
+
+    /// Reads from the pattern tables at $0000-$1FFF.
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8;
+
+    /// Writes to the pattern tables at $0000-$1FFF.
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8);
+
+    /// How the console's nametable RAM is mirrored, used by the default
+    /// `read_nametable` and `write_nametable`.
+    fn mirroring(&self) -> Mirroring;
+
+    /// Reads from the pattern tables for the PPU. `vram` is the console's
+    /// nametable RAM as in `read_nametable`, for boards that can map it
+    /// into the pattern tables.
+    fn read_pattern_table(&mut self, addr: BusAddr, _vram: &[u8]) -> u8 {
+        self.ppu_read_byte(addr)
+    }
+
+    /// Writes to the pattern tables for the PPU.
+    fn write_pattern_table(&mut self, addr: BusAddr, value: u8, _vram: &mut [u8]) {
+        self.ppu_write_byte(addr, value);
+    }
+
+    /// Reads from the nametables at $2000-$3EFF. `vram` is the console's
+    /// 2KB of nametable RAM followed by the 2KB four-screen boards add.
+    fn read_nametable(&mut self, addr: BusAddr, vram: &[u8]) -> u8 {
+        vram[self.mirroring().nametable_offset(addr)]
+    }
+
+    /// Writes to the nametables at $2000-$3EFF.
+    fn write_nametable(&mut self, addr: BusAddr, value: u8, vram: &mut [u8]) {
+        vram[self.mirroring().nametable_offset(addr)] = value;
+    }
+
+    /// State of the cartridge's IRQ line.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Advances the cartridge by a single CPU cycle.
+    fn tick(&mut self) {}
+
+    /// Called with every address the PPU puts on its bus below $3F00, in
+    /// order, so that the board can watch A12 or the fetch pattern.
+    fn ppu_address(&mut self, _addr: BusAddr) {}
+
+    /// Called with every CPU write to the PPU's registers, which boards
+    /// can watch on the data bus. `register` is in 0-7.
+    fn ppu_register_write(&mut self, _register: BusAddr, _value: u8) {}
+
+    /// Called at the first dot of every scanline. The number of the
+    /// pre-render scanline depends on the region, so it is flagged.
+    fn scanline(&mut self, _scanline: u16, _pre_render: bool, _rendering: bool) {}
+
+    /// Output of the cartridge's sound hardware, already scaled relative
+    /// to the 2A03.
+    fn audio_level(&self) -> f32 {
+        0.0
+    }
+
+    /// Memory the cartridge keeps while the console is off, if any.
+    fn save_data(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Restores memory returned by `save_data` in an earlier session.
+    fn load_save_data(&mut self, _data: &[u8]) {}
+}
+
+/// Builds the mapper for the board described by the cartridge's header.
+pub fn from_ines(ines: &iNES) -> Result<Box<dyn Mapper>, RomError> {
+    let memory = CartridgeMemory::new(ines);
+    let mirroring = ines.header.mirroring;
+    let bus_conflicts = match ines.header.submapper {
+        // Licensed UxROM and CNROM boards all have bus conflicts, so assume
+        // them when the header does not say. Most AxROM games were made for
+        // the conflict-free AMROM and ANROM boards.
+        0 => matches!(ines.header.mapper, 2 | 3),
+        submapper => submapper == SUBMAPPER_BUS_CONFLICTS,
+    };
+    match ines.header.mapper {
+        0 => Ok(Box::new(NROM::new(memory, mirroring))),
+        1 => Ok(Box::new(MMC1::new(memory, ines.header.submapper))),
+        2 => Ok(Box::new(UxROM::new(memory, mirroring, bus_conflicts))),
+        3 => Ok(Box::new(CNROM::new(memory, mirroring, bus_conflicts))),
+        4 => Ok(Box::new(MMC3::new(
+            memory,
+            mirroring,
+            ines.header.submapper,
+        ))),
+        5 => Ok(Box::new(MMC5::new(memory))),
+        7 => Ok(Box::new(AxROM::new(memory, bus_conflicts))),
+        9 => Ok(Box::new(MMC2::new(memory, false))),
+        10 => Ok(Box::new(MMC2::new(memory, true))),
+        19 => Ok(Box::new(N163::new(memory))),
+        21 | 22 | 23 | 25 => Ok(Box::new(VRC4::new(
+            memory,
+            ines.header.mapper,
+            ines.header.submapper,
+        ))),
+        24 => Ok(Box::new(VRC6::new(memory, false))),
+        26 => Ok(Box::new(VRC6::new(memory, true))),
+        66 => Ok(Box::new(GxROM::new(memory, mirroring))),
+        69 => Ok(Box::new(FME7::new(memory))),
+        85 => Ok(Box::new(VRC7::new(memory, ines.header.submapper))),
+        mapper => Err(RomError::UnsupportedMapper {
+            mapper,
+            submapper: ines.header.submapper,
+        }),
+    }
+}
+
+/// The memory chips on a cartridge, read through banks of whatever size
+/// the mapper switches. Bank numbers wrap around the chip, as the unused
+/// upper bank bits are not connected.
+struct CartridgeMemory {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_writable: bool,
+    prg_ram: Vec<u8>,
+    battery: bool,
+}
+
+impl CartridgeMemory {
+    /// Cartridges without CHR-ROM get CHR-RAM, and all of them get PRG-RAM
+    /// unless the NES 2.0 header says there is none. A trainer is copied
+    /// to $7000 of the PRG-RAM.
+    fn new(ines: &iNES) -> CartridgeMemory {
+        let header = &ines.header;
+        let character_rom = ines.characterROM.as_bytes();
+        let (chr, chr_writable) = if !character_rom.is_empty() {
+            (character_rom.to_vec(), false)
+        } else {
+            let chr_ram_size = match header.chr_ram_size + header.chr_nvram_size {
+                0 => DEFAULT_CHR_RAM_SIZE,
+                size => size,
+            };
+            (vec![0; chr_ram_size], true)
+        };
+
+        let prg_ram_size = match header.prg_ram_size + header.prg_nvram_size {
+            0 if !header.is_nes2() => DEFAULT_PRG_RAM_SIZE,
+            size => size,
+        };
+        let mut prg_ram = vec![0; prg_ram_size];
+        if let Some(trainer) = ines.trainer.as_ref() {
+            let start = (TRAINER_START_ADDR - PRG_RAM_START_ADDR) as usize;
+            if prg_ram.len() < start + trainer.len() {
+                prg_ram.resize(DEFAULT_PRG_RAM_SIZE, 0);
+            }
+            prg_ram[start..start + trainer.len()].copy_from_slice(trainer);
+        }
+
+        CartridgeMemory {
+            prg_rom: ines.programROM.data.clone(),
+            chr,
+            chr_writable,
+            prg_ram,
+            battery: header.battery,
+        }
+    }
+
+    fn prg_bank_count(&self, bank_size: usize) -> usize {
+        (self.prg_rom.len() / bank_size).max(1)
+    }
+
+    fn chr_bank_count(&self, bank_size: usize) -> usize {
+        (self.chr.len() / bank_size).max(1)
+    }
+
+    fn read_prg_rom(&self, bank: usize, bank_size: usize, offset: usize) -> u8 {
+        let bank = bank % self.prg_bank_count(bank_size);
+        self.prg_rom[(bank * bank_size + offset % bank_size) % self.prg_rom.len()]
+    }
+
+    fn read_chr(&self, bank: usize, bank_size: usize, offset: usize) -> u8 {
+        let bank = bank % self.chr_bank_count(bank_size);
+        self.chr[(bank * bank_size + offset % bank_size) % self.chr.len()]
+    }
+
+    fn write_chr(&mut self, bank: usize, bank_size: usize, offset: usize, value: u8) {
+        if self.chr_writable {
+            let bank = bank % self.chr_bank_count(bank_size);
+            let len = self.chr.len();
+            self.chr[(bank * bank_size + offset % bank_size) % len] = value;
+        }
+    }
+
+    /// Reads PRG-RAM, mirrored if the chip is smaller than the window.
+    /// Boards without PRG-RAM read back open bus.
+    fn read_prg_ram(&self, offset: usize) -> u8 {
+        if self.prg_ram.is_empty() {
+            return 0;
+        }
+        self.prg_ram[offset % self.prg_ram.len()]
+    }
+
+    fn write_prg_ram(&mut self, offset: usize, value: u8) {
+        if !self.prg_ram.is_empty() {
+            let len = self.prg_ram.len();
+            self.prg_ram[offset % len] = value;
+        }
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        if self.battery && !self.prg_ram.is_empty() {
+            Some(&self.prg_ram)
+        } else {
+            None
+        }
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.prg_ram.len());
+        self.prg_ram[..len].copy_from_slice(&data[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An NES 2.0 image with 8KB of PRG-RAM, and 8KB of CHR-RAM when
+    /// `chr_size` is zero. Every PRG-ROM byte holds the number of its 8KB
+    /// bank and every CHR-ROM byte the number of its 1KB bank.
+    pub(super) fn nes2(mapper: u16, submapper: u8, prg_size: usize, chr_size: usize) -> iNES {
+        let prg_units = prg_size / 0x4000;
+        let mut data = vec![
+            0x4E,
+            0x45,
+            0x53,
+            0x1A,
+            prg_units as u8,
+            (chr_size / 0x2000) as u8,
+            ((mapper & 0x0F) << 4) as u8,
+            (mapper & 0xF0) as u8 | 0x08,
+            submapper << 4 | (mapper >> 8) as u8,
+            (prg_units >> 8) as u8,
+            0x07,
+            if chr_size == 0 { 0x07 } else { 0x00 },
+            0,
+            0,
+            0,
+            0,
+        ];
+        data.extend((0..prg_size).map(|offset| (offset / 0x2000) as u8));
+        data.extend((0..chr_size).map(|offset| (offset / 0x0400) as u8));
+        iNES::parse(&data).unwrap()
+    }
+
+    pub(super) fn memory(prg_size: usize, chr_size: usize) -> CartridgeMemory {
+        CartridgeMemory::new(&nes2(0, 0, prg_size, chr_size))
+    }
+
+    /// An iNES 1.0 image with the given flags 6 and ROMs.
+    fn ines(flags6: u8, trainer: &[u8], prg_rom: &[u8], chr_rom: &[u8]) -> iNES {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A];
+        data.push((prg_rom.len() / 0x4000) as u8);
+        data.push((chr_rom.len() / 0x2000) as u8);
+        data.push(flags6);
+        data.resize(16, 0);
+        data.extend_from_slice(trainer);
+        data.extend_from_slice(prg_rom);
+        data.extend_from_slice(chr_rom);
+        iNES::parse(&data).unwrap()
+    }
+
+    #[test]
+    fn mirrors_16kb_nrom_into_both_halves() {
+        let prg_rom: Vec<u8> = (0..0x4000).map(|offset| (offset >> 8) as u8).collect();
+        let mut mapper = from_ines(&ines(0, &[], &prg_rom, &[0; 0x2000])).unwrap();
+        for addr in [0x8000, 0x8123, 0xBFFC, 0xBFFF] {
+            assert_eq!(
+                mapper.cpu_read_byte(addr),
+                mapper.cpu_read_byte(addr + 0x4000)
+            );
+        }
+        assert_eq!(mapper.cpu_read_byte(0xC123), 0x01);
+        assert_eq!(mapper.cpu_read_byte(0xFFFC), 0x3F);
+    }
+
+    #[test]
+    fn allocates_chr_ram_without_chr_rom() {
+        let mut mapper = from_ines(&ines(0, &[], &[0; 0x4000], &[])).unwrap();
+        mapper.ppu_write_byte(0x1FFF, 0x5A);
+        assert_eq!(mapper.ppu_read_byte(0x1FFF), 0x5A);
+
+        let memory = CartridgeMemory::new(&ines(0, &[], &[0; 0x4000], &[]));
+        assert_eq!(memory.chr.len(), DEFAULT_CHR_RAM_SIZE);
+        assert!(memory.chr_writable);
+
+        let mut ines = nes2(0, 0, 0x4000, 0);
+        ines.header.chr_ram_size = 0x8000;
+        assert_eq!(CartridgeMemory::new(&ines).chr.len(), 0x8000);
+    }
+
+    #[test]
+    fn chr_rom_is_read_only() {
+        let mut mapper = from_ines(&ines(0, &[], &[0; 0x4000], &[0x11; 0x2000])).unwrap();
+        mapper.ppu_write_byte(0x0000, 0x5A);
+        assert_eq!(mapper.ppu_read_byte(0x0000), 0x11);
+    }
+
+    #[test]
+    fn copies_trainer_to_7000() {
+        let trainer: Vec<u8> = (0..512).map(|offset| offset as u8 ^ 0xA5).collect();
+        let mut mapper = from_ines(&ines(0x04, &trainer, &[0; 0x4000], &[0; 0x2000])).unwrap();
+        assert_eq!(mapper.cpu_read_byte(0x6FFF), 0);
+        for (offset, byte) in trainer.iter().enumerate() {
+            assert_eq!(mapper.cpu_read_byte(0x7000 + offset as BusAddr), *byte);
+        }
+        assert_eq!(mapper.cpu_read_byte(0x7200), 0);
+    }
+
+    #[test]
+    fn trainer_adds_prg_ram_when_header_has_none() {
+        let mut ines = nes2(0, 0, 0x4000, 0x2000);
+        ines.header.prg_ram_size = 0;
+        ines.trainer = Some(vec![0xEA; 512]);
+        let memory = CartridgeMemory::new(&ines);
+        assert_eq!(memory.prg_ram.len(), DEFAULT_PRG_RAM_SIZE);
+        assert_eq!(memory.read_prg_ram(0x1000), 0xEA);
+    }
+
+    #[test]
+    fn nes2_header_without_prg_ram_reads_open_bus() {
+        let mut ines = nes2(0, 0, 0x4000, 0x2000);
+        ines.header.prg_ram_size = 0;
+        let mut mapper = from_ines(&ines).unwrap();
+        mapper.cpu_write_byte(0x6000, 0x5A);
+        assert_eq!(mapper.cpu_read_byte(0x6000), 0);
+    }
+
+    #[test]
+    fn bus_conflicts_and_written_value_with_rom() {
+        // The last bank of a 128KB UxROM reads $0E, so writing $07 there
+        // selects bank 6 when the ROM drives the bus too.
+        for (submapper, bank) in [(0, 6), (1, 7), (2, 6)] {
+            let mut mapper = from_ines(&nes2(2, submapper, 0x20000, 0)).unwrap();
+            assert_eq!(mapper.cpu_read_byte(0xC000), 0x0E);
+            mapper.cpu_write_byte(0xC000, 0x07);
+            assert_eq!(mapper.cpu_read_byte(0x8000), bank * 2);
+        }
+
+        // CNROM defaults to conflicts, AxROM only has them on submapper 2.
+        let mut mapper = from_ines(&nes2(3, 0, 0x8000, 0x8000)).unwrap();
+        mapper.cpu_write_byte(0x8000, 0x03);
+        assert_eq!(mapper.ppu_read_byte(0x0000), 0);
+        mapper.cpu_write_byte(0xA000, 0x03);
+        assert_eq!(mapper.ppu_read_byte(0x0000), 8);
+        let mut mapper = from_ines(&nes2(7, 0, 0x20000, 0)).unwrap();
+        mapper.cpu_write_byte(0x8000, 0x03);
+        assert_eq!(mapper.cpu_read_byte(0x8000), 12);
+    }
+
+    #[test]
+    fn bank_numbers_wrap_around_the_chip() {
+        let memory = memory(0x8000, 0x2000);
+        assert_eq!(memory.read_prg_rom(4, 0x2000, 0), 0);
+        assert_eq!(memory.read_prg_rom(7, 0x2000, 0), 3);
+        assert_eq!(memory.read_chr(9, 0x0400, 0), 1);
+    }
+}
@@ -0,0 +1,65 @@
+use super::{CartridgeMemory, Mapper};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x8000; // 32KB
+const CHR_BANK_SIZE: usize = 0x2000; // 8KB
+const BANK_SELECT_NAMETABLE: u8 = 0x10;
+
+/// Mapper 7, ANROM, AMROM and AOROM: a switchable 32KB PRG bank and a
+/// register bit that picks which nametable fills the screen.
+pub struct AxROM {
+    memory: CartridgeMemory,
+    bus_conflicts: bool,
+    bank_select: u8,
+}
+
+impl AxROM {
+    pub(super) fn new(memory: CartridgeMemory, bus_conflicts: bool) -> AxROM {
+        AxROM {
+            memory,
+            bus_conflicts,
+            bank_select: 0,
+        }
+    }
+}
+
+impl Mapper for AxROM {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory.read_prg_rom(
+                (self.bank_select & 0x07) as usize,
+                PRG_BANK_SIZE,
+                (addr - 0x8000) as usize,
+            )
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.bank_select = if self.bus_conflicts {
+                value & self.cpu_read_byte(addr)
+            } else {
+                value
+            };
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory.read_chr(0, CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(0, CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.bank_select & BANK_SELECT_NAMETABLE != 0 {
+            Mirroring::SingleScreenUpper
+        } else {
+            Mirroring::SingleScreenLower
+        }
+    }
+}
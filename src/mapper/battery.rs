@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use super::Mapper;
+
+/// Keeps the `.sav` file next to a ROM in step with the cartridge's
+/// battery-backed memory.
+pub struct BatterySave {
+    path: String,
+    /// What the file holds, to skip writes when nothing changed.
+    saved: Vec<u8>,
+    loaded: bool,
+}
+
+impl BatterySave {
+    /// Loads the `.sav` file next to `rom_path` into the mapper, if the
+    /// cartridge has a battery and the file exists. Returns `None` for
+    /// cartridges without one.
+    pub fn load(rom_path: &str, mapper: &mut dyn Mapper) -> Result<Option<BatterySave>, String> {
+        let Some(save_data) = mapper.save_data() else {
+            return Ok(None);
+        };
+        let path = Path::new(rom_path)
+            .with_extension("sav")
+            .to_string_lossy()
+            .into_owned();
+        let mut saved = save_data.to_vec();
+        let mut loaded = false;
+        match std::fs::read(&path) {
+            Ok(data) => {
+                mapper.load_save_data(&data);
+                saved = data;
+                loaded = true;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        }
+        Ok(Some(BatterySave {
+            path,
+            saved,
+            loaded,
+        }))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether `load` found a `.sav` file.
+    pub fn loaded(&self) -> bool {
+        self.loaded
+    }
+
+    /// Writes `save_data` out if it changed since the last write, and
+    /// returns whether it did.
+    pub fn flush(&mut self, save_data: Option<&[u8]>) -> Result<bool, String> {
+        let Some(save_data) = save_data else {
+            return Ok(false);
+        };
+        if save_data == self.saved.as_slice() {
+            return Ok(false);
+        }
+        std::fs::write(&self.path, save_data).map_err(|e| format!("{}: {}", self.path, e))?;
+        self.saved = save_data.to_vec();
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::{from_ines, tests::nes2};
+
+    /// An NROM board with battery-backed PRG-RAM.
+    fn battery_nrom() -> Box<dyn Mapper> {
+        let mut ines = nes2(0, 0, 0x4000, 0x2000);
+        ines.header.battery = true;
+        from_ines(&ines).unwrap()
+    }
+
+    fn rom_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("nes-rs-{}-{}.nes", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn cartridge_without_battery_has_no_save() {
+        let mut nrom = from_ines(&nes2(0, 0, 0x4000, 0x2000)).unwrap();
+        assert!(BatterySave::load(&rom_path("no-battery"), nrom.as_mut())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn loads_existing_save() {
+        let rom_path = rom_path("battery-load");
+        let mut data = vec![0; 0x2000];
+        data[0x0010] = 0x42;
+        std::fs::write(Path::new(&rom_path).with_extension("sav"), &data).unwrap();
+
+        let mut nrom = battery_nrom();
+        let result = BatterySave::load(&rom_path, nrom.as_mut());
+        std::fs::remove_file(Path::new(&rom_path).with_extension("sav")).unwrap();
+        let mut save = result.unwrap().unwrap();
+        assert!(save.loaded());
+        assert_eq!(nrom.cpu_read_byte(0x6010), 0x42);
+        assert!(!save.flush(nrom.save_data()).unwrap());
+    }
+
+    #[test]
+    fn flushes_only_changed_data() {
+        let rom_path = rom_path("battery-flush");
+        let mut nrom = battery_nrom();
+        let mut save = BatterySave::load(&rom_path, nrom.as_mut())
+            .unwrap()
+            .unwrap();
+        assert!(!save.loaded());
+        assert!(!save.flush(nrom.save_data()).unwrap());
+        assert!(!Path::new(save.path()).exists());
+
+        nrom.cpu_write_byte(0x6000, 0x99);
+        let flushed = save.flush(nrom.save_data());
+        let written = std::fs::read(save.path());
+        let flushed_again = save.flush(nrom.save_data());
+        std::fs::remove_file(save.path()).unwrap();
+        assert!(flushed.unwrap());
+        assert_eq!(written.unwrap()[0], 0x99);
+        assert!(!flushed_again.unwrap());
+    }
+}
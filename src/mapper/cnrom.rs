@@ -0,0 +1,73 @@
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x8000; // 32KB
+const CHR_BANK_SIZE: usize = 0x2000; // 8KB
+
+/// Mapper 3: NROM's fixed PRG-ROM with a switchable 8KB CHR-ROM bank.
+pub struct CNROM {
+    memory: CartridgeMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    chr_bank: u8,
+}
+
+impl CNROM {
+    pub(super) fn new(memory: CartridgeMemory, mirroring: Mirroring, bus_conflicts: bool) -> CNROM {
+        CNROM {
+            memory,
+            mirroring,
+            bus_conflicts,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for CNROM {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory
+                .read_prg_rom(0, PRG_BANK_SIZE, (addr - 0x8000) as usize)
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.memory
+                .read_prg_ram((addr - PRG_RAM_START_ADDR) as usize)
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.chr_bank = if self.bus_conflicts {
+                value & self.cpu_read_byte(addr)
+            } else {
+                value
+            };
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.memory
+                .write_prg_ram((addr - PRG_RAM_START_ADDR) as usize, value);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank as usize, CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank as usize, CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
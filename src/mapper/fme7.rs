@@ -0,0 +1,157 @@
+mod audio;
+
+use self::audio::Sunsoft5BAudio;
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x2000; // 8KB
+const CHR_BANK_SIZE: usize = 0x0400; // 1KB
+
+const PRG_RAM_SELECT: u8 = 0x40;
+const PRG_RAM_ENABLE: u8 = 0x80;
+const IRQ_ENABLE: u8 = 0x01;
+const IRQ_COUNTER_ENABLE: u8 = 0x80;
+
+/// Mapper 69: the Sunsoft FME-7 and the 5B, which adds sound. Registers
+/// are written through a command port at $8000 and a parameter port at
+/// $A000. The IRQ counter counts down every CPU cycle.
+pub struct FME7 {
+    memory: CartridgeMemory,
+    audio: Sunsoft5BAudio,
+    command: u8,
+    chr_banks: [u8; 8],
+    /// Command 8: the bank at $6000, ROM or RAM.
+    prg_ram_bank: u8,
+    prg_banks: [u8; 3],
+    mirroring: Mirroring,
+    irq_control: u8,
+    irq_counter: u16,
+    irq_pending: bool,
+}
+
+impl FME7 {
+    pub(super) fn new(memory: CartridgeMemory) -> FME7 {
+        FME7 {
+            memory,
+            audio: Sunsoft5BAudio::new(),
+            command: 0,
+            chr_banks: [0; 8],
+            prg_ram_bank: 0,
+            prg_banks: [0; 3],
+            mirroring: Mirroring::Vertical,
+            irq_control: 0,
+            irq_counter: 0,
+            irq_pending: false,
+        }
+    }
+
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        self.chr_banks[addr as usize / CHR_BANK_SIZE] as usize
+    }
+
+    fn write_parameter(&mut self, value: u8) {
+        match self.command {
+            command @ 0x0..=0x7 => self.chr_banks[command as usize] = value,
+            0x8 => self.prg_ram_bank = value,
+            command @ 0x9..=0xB => self.prg_banks[command as usize - 0x9] = value & 0x3F,
+            0xC => {
+                self.mirroring = match value & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::SingleScreenLower,
+                    _ => Mirroring::SingleScreenUpper,
+                };
+            }
+            0xD => {
+                self.irq_control = value;
+                self.irq_pending = false;
+            }
+            0xE => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | (value as u16) << 8,
+        }
+    }
+}
+
+impl Mapper for FME7 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            let bank = match (addr - 0x8000) as usize / PRG_BANK_SIZE {
+                3 => self.memory.prg_bank_count(PRG_BANK_SIZE) - 1,
+                slot => self.prg_banks[slot] as usize,
+            };
+            self.memory
+                .read_prg_rom(bank, PRG_BANK_SIZE, (addr & 0x1FFF) as usize)
+        } else if addr >= PRG_RAM_START_ADDR {
+            let bank = (self.prg_ram_bank & 0x3F) as usize;
+            let offset = (addr - PRG_RAM_START_ADDR) as usize;
+            if self.prg_ram_bank & PRG_RAM_SELECT == 0 {
+                self.memory.read_prg_rom(bank, PRG_BANK_SIZE, offset)
+            } else if self.prg_ram_bank & PRG_RAM_ENABLE != 0 {
+                self.memory.read_prg_ram(bank * PRG_BANK_SIZE + offset)
+            } else {
+                0
+            }
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.command = value & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(value),
+            0xC000..=0xDFFF => self.audio.select_register(value),
+            0xE000..=0xFFFF => self.audio.write_register(value),
+            PRG_RAM_START_ADDR..=0x7FFF => {
+                let ram = PRG_RAM_SELECT | PRG_RAM_ENABLE;
+                if self.prg_ram_bank & ram == ram {
+                    let bank = (self.prg_ram_bank & 0x3F) as usize;
+                    let offset = (addr - PRG_RAM_START_ADDR) as usize;
+                    self.memory
+                        .write_prg_ram(bank * PRG_BANK_SIZE + offset, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn tick(&mut self) {
+        if self.irq_control & IRQ_COUNTER_ENABLE != 0 {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF && self.irq_control & IRQ_ENABLE != 0 {
+                self.irq_pending = true;
+            }
+        }
+        self.audio.clock();
+    }
+
+    fn audio_level(&self) -> f32 {
+        self.audio.level()
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
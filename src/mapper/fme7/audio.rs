@@ -0,0 +1,244 @@
+/// The chip's own clock runs at the CPU's divided by 16.
+const PRESCALER_PERIOD: u8 = 16;
+const MIXER_TONE_DISABLE: u8 = 0x01;
+const MIXER_NOISE_DISABLE: u8 = 0x08;
+const VOLUME_ENVELOPE: u8 = 0x10;
+const ENVELOPE_CONTINUE: u8 = 0x08;
+const ENVELOPE_ATTACK: u8 = 0x04;
+const ENVELOPE_ALTERNATE: u8 = 0x02;
+const ENVELOPE_HOLD: u8 = 0x01;
+const ENVELOPE_MAX_STEP: u8 = 31;
+/// Each of the 32 output levels is 1.5dB apart.
+const DB_PER_STEP: f32 = 1.5;
+/// A channel at volume 12 is about as loud as a 2A03 pulse channel at full
+/// volume, which puts the loudest level here.
+const CHANNEL_FULL_LEVEL: f32 = 0.42;
+
+struct Tone {
+    period: u16,
+    counter: u16,
+    high: bool,
+}
+
+impl Tone {
+    fn new() -> Self {
+        Self {
+            period: 0,
+            counter: 0,
+            high: false,
+        }
+    }
+
+    fn clock(&mut self) {
+        self.counter += 1;
+        if self.counter >= self.period.max(1) {
+            self.counter = 0;
+            self.high = !self.high;
+        }
+    }
+}
+
+/// A 17-bit linear feedback shift register, advanced at half the rate of
+/// the tone counters.
+struct Noise {
+    period: u8,
+    counter: u8,
+    half: bool,
+    shift_register: u32,
+}
+
+impl Noise {
+    fn new() -> Self {
+        Self {
+            period: 0,
+            counter: 0,
+            half: false,
+            shift_register: 1,
+        }
+    }
+
+    fn clock(&mut self) {
+        self.half = !self.half;
+        if self.half {
+            return;
+        }
+        self.counter += 1;
+        if self.counter >= self.period.max(1) {
+            self.counter = 0;
+            let feedback = (self.shift_register ^ (self.shift_register >> 3)) & 0x01;
+            self.shift_register = (self.shift_register >> 1) | feedback << 16;
+        }
+    }
+
+    fn high(&self) -> bool {
+        self.shift_register & 0x01 != 0
+    }
+}
+
+/// Sweeps through the 32 levels, up or down, once or repeatedly, as
+/// picked by the shape register.
+struct Envelope {
+    period: u16,
+    counter: u16,
+    shape: u8,
+    step: u8,
+    attack: bool,
+    holding: bool,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Self {
+            period: 0,
+            counter: 0,
+            shape: 0,
+            step: 0,
+            attack: false,
+            holding: true,
+        }
+    }
+
+    /// Writing the shape restarts the envelope.
+    fn write_shape(&mut self, value: u8) {
+        self.shape = value & 0x0F;
+        self.counter = 0;
+        self.step = 0;
+        self.attack = value & ENVELOPE_ATTACK != 0;
+        self.holding = false;
+    }
+
+    fn clock(&mut self) {
+        self.counter += 1;
+        if self.counter < self.period.max(1) {
+            return;
+        }
+        self.counter = 0;
+        if self.holding {
+            return;
+        }
+        if self.step < ENVELOPE_MAX_STEP {
+            self.step += 1;
+            return;
+        }
+
+        if self.shape & ENVELOPE_CONTINUE == 0 {
+            self.holding = true;
+            self.attack = false;
+        } else if self.shape & ENVELOPE_HOLD != 0 {
+            self.holding = true;
+            if self.shape & ENVELOPE_ALTERNATE != 0 {
+                self.attack = !self.attack;
+            }
+        } else {
+            if self.shape & ENVELOPE_ALTERNATE != 0 {
+                self.attack = !self.attack;
+            }
+            self.step = 0;
+        }
+    }
+
+    fn level(&self) -> u8 {
+        if self.attack {
+            self.step
+        } else {
+            ENVELOPE_MAX_STEP - self.step
+        }
+    }
+}
+
+/// The Sunsoft 5B's sound hardware, a licensed copy of the AY-3-8910: three
+/// square wave channels that can each mix in a shared noise generator, and
+/// a shared volume envelope.
+pub struct Sunsoft5BAudio {
+    selected: u8,
+    prescaler: u8,
+    tones: [Tone; 3],
+    noise: Noise,
+    envelope: Envelope,
+    mixer: u8,
+    volumes: [u8; 3],
+    /// Amplitude of each of the 32 logarithmic levels.
+    levels: [f32; 32],
+}
+
+impl Sunsoft5BAudio {
+    pub fn new() -> Self {
+        let mut levels = [0.0; 32];
+        for (level, amplitude) in levels.iter_mut().enumerate().skip(1) {
+            let attenuation = (ENVELOPE_MAX_STEP as usize - level) as f32 * DB_PER_STEP;
+            *amplitude = 10f32.powf(-attenuation / 20.0) * CHANNEL_FULL_LEVEL;
+        }
+        Self {
+            selected: 0,
+            prescaler: 0,
+            tones: [Tone::new(), Tone::new(), Tone::new()],
+            noise: Noise::new(),
+            envelope: Envelope::new(),
+            mixer: 0,
+            volumes: [0; 3],
+            levels,
+        }
+    }
+
+    /// Advances the channels by a single CPU cycle.
+    pub fn clock(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < PRESCALER_PERIOD {
+            return;
+        }
+        self.prescaler = 0;
+        for tone in self.tones.iter_mut() {
+            tone.clock();
+        }
+        self.noise.clock();
+        self.envelope.clock();
+    }
+
+    /// Output scaled relative to the 2A03 channels.
+    pub fn level(&self) -> f32 {
+        let mut sum = 0.0;
+        for (channel, tone) in self.tones.iter().enumerate() {
+            let tone_on = tone.high || self.mixer & (MIXER_TONE_DISABLE << channel) != 0;
+            let noise_on = self.noise.high() || self.mixer & (MIXER_NOISE_DISABLE << channel) != 0;
+            if !(tone_on && noise_on) {
+                continue;
+            }
+            let volume = self.volumes[channel];
+            let level = if volume & VOLUME_ENVELOPE != 0 {
+                self.envelope.level()
+            } else if volume & 0x0F == 0 {
+                0
+            } else {
+                (volume & 0x0F) * 2 + 1
+            };
+            sum += self.levels[level as usize];
+        }
+        sum
+    }
+
+    /// $C000-$DFFF: selects the register the next data write goes to.
+    pub fn select_register(&mut self, value: u8) {
+        self.selected = value & 0x0F;
+    }
+
+    /// $E000-$FFFF: writes the selected register.
+    pub fn write_register(&mut self, value: u8) {
+        match self.selected {
+            register @ (0x00 | 0x02 | 0x04) => {
+                let tone = &mut self.tones[register as usize / 2];
+                tone.period = (tone.period & 0x0F00) | value as u16;
+            }
+            register @ (0x01 | 0x03 | 0x05) => {
+                let tone = &mut self.tones[register as usize / 2];
+                tone.period = (tone.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+            }
+            0x06 => self.noise.period = value & 0x1F,
+            0x07 => self.mixer = value,
+            register @ 0x08..=0x0A => self.volumes[register as usize - 0x08] = value & 0x1F,
+            0x0B => self.envelope.period = (self.envelope.period & 0xFF00) | value as u16,
+            0x0C => self.envelope.period = (self.envelope.period & 0x00FF) | (value as u16) << 8,
+            0x0D => self.envelope.write_shape(value),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use super::{CartridgeMemory, Mapper};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x8000; // 32KB
+const CHR_BANK_SIZE: usize = 0x2000; // 8KB
+
+/// Mapper 66, GNROM and MHROM: one register selecting a 32KB PRG bank
+/// (bits 4-5) and an 8KB CHR bank (bits 0-1). The board always has bus
+/// conflicts.
+pub struct GxROM {
+    memory: CartridgeMemory,
+    mirroring: Mirroring,
+    bank_select: u8,
+}
+
+impl GxROM {
+    pub(super) fn new(memory: CartridgeMemory, mirroring: Mirroring) -> GxROM {
+        GxROM {
+            memory,
+            mirroring,
+            bank_select: 0,
+        }
+    }
+}
+
+impl Mapper for GxROM {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory.read_prg_rom(
+                ((self.bank_select >> 4) & 0x03) as usize,
+                PRG_BANK_SIZE,
+                (addr - 0x8000) as usize,
+            )
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.bank_select = value & self.cpu_read_byte(addr);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory.read_chr(
+            (self.bank_select & 0x03) as usize,
+            CHR_BANK_SIZE,
+            addr as usize,
+        )
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory.write_chr(
+            (self.bank_select & 0x03) as usize,
+            CHR_BANK_SIZE,
+            addr as usize,
+            value,
+        );
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
@@ -0,0 +1,304 @@
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x4000; // 16KB
+const CHR_BANK_SIZE: usize = 0x1000; // 4KB
+const PRG_RAM_BANK_SIZE: usize = 0x2000; // 8KB
+/// SUROM and SXROM boards select which 256KB half of the PRG-ROM is used
+/// through bit 4 of the CHR registers.
+const PRG_OUTER_BANK_SIZE: usize = 0x40000; // 256KB
+const SUBMAPPER_FIXED_PRG: u8 = 5;
+
+const SHIFT_RESET: u8 = 0x80;
+const SHIFT_REGISTER_BITS: u8 = 5;
+const CONTROL_POWER_ON: u8 = 0x0C;
+const CONTROL_CHR_4KB: u8 = 0x10;
+const PRG_RAM_DISABLE: u8 = 0x10;
+
+/// Mapper 1, the MMC1 on the SxROM boards. Registers are loaded one bit at
+/// a time through a 5-bit shift register.
+pub struct MMC1 {
+    memory: CartridgeMemory,
+    submapper: u8,
+    shift_register: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+    cycle: u64,
+    last_write_cycle: Option<u64>,
+}
+
+impl MMC1 {
+    pub(super) fn new(memory: CartridgeMemory, submapper: u8) -> MMC1 {
+        MMC1 {
+            memory,
+            submapper,
+            shift_register: 0,
+            shift_count: 0,
+            control: CONTROL_POWER_ON,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cycle: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    fn write_serial(&mut self, addr: BusAddr, value: u8) {
+        // The MMC1 ignores a write on the cycle right after another one, so
+        // only the first write of a read-modify-write instruction counts.
+        let consecutive = self
+            .last_write_cycle
+            .is_some_and(|last| self.cycle - last <= 1);
+        self.last_write_cycle = Some(self.cycle);
+        if consecutive {
+            return;
+        }
+
+        if value & SHIFT_RESET != 0 {
+            self.shift_register = 0;
+            self.shift_count = 0;
+            self.control |= CONTROL_POWER_ON;
+            return;
+        }
+        self.shift_register |= (value & 0x01) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count < SHIFT_REGISTER_BITS {
+            return;
+        }
+
+        let register = self.shift_register;
+        match addr {
+            0x8000..=0x9FFF => self.control = register,
+            0xA000..=0xBFFF => self.chr_bank_0 = register,
+            0xC000..=0xDFFF => self.chr_bank_1 = register,
+            _ => self.prg_bank = register,
+        }
+        self.shift_register = 0;
+        self.shift_count = 0;
+    }
+
+    fn prg_rom_bank(&self, addr: BusAddr) -> usize {
+        let upper_half = addr >= 0xC000;
+        if self.submapper == SUBMAPPER_FIXED_PRG {
+            return upper_half as usize;
+        }
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let bank = match (self.control >> 2) & 0x03 {
+            0 | 1 => (bank & !0x01) | upper_half as usize,
+            2 if upper_half => bank,
+            2 => 0,
+            _ if upper_half => 0x0F,
+            _ => bank,
+        };
+        if self.memory.prg_rom.len() > PRG_OUTER_BANK_SIZE {
+            bank | (self.chr_bank_0 & 0x10) as usize
+        } else {
+            bank
+        }
+    }
+
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        let upper_half = addr >= 0x1000;
+        if self.control & CONTROL_CHR_4KB != 0 {
+            if upper_half {
+                self.chr_bank_1 as usize
+            } else {
+                self.chr_bank_0 as usize
+            }
+        } else {
+            (self.chr_bank_0 & !0x01) as usize | upper_half as usize
+        }
+    }
+
+    /// SOROM has 16KB of PRG-RAM banked by CHR bit 3, SXROM 32KB banked by
+    /// CHR bits 2-3.
+    fn prg_ram_bank(&self) -> usize {
+        match self.memory.prg_ram.len() / PRG_RAM_BANK_SIZE {
+            0 | 1 => 0,
+            2 => ((self.chr_bank_0 >> 3) & 0x01) as usize,
+            _ => ((self.chr_bank_0 >> 2) & 0x03) as usize,
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & PRG_RAM_DISABLE == 0
+    }
+}
+
+impl Mapper for MMC1 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory.read_prg_rom(
+                self.prg_rom_bank(addr),
+                PRG_BANK_SIZE,
+                (addr - 0x8000) as usize,
+            )
+        } else if addr >= PRG_RAM_START_ADDR && self.prg_ram_enabled() {
+            self.memory.read_prg_ram(
+                self.prg_ram_bank() * PRG_RAM_BANK_SIZE + (addr - PRG_RAM_START_ADDR) as usize,
+            )
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.write_serial(addr, value);
+        } else if addr >= PRG_RAM_START_ADDR && self.prg_ram_enabled() {
+            self.memory.write_prg_ram(
+                self.prg_ram_bank() * PRG_RAM_BANK_SIZE + (addr - PRG_RAM_START_ADDR) as usize,
+                value,
+            );
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::{memory, nes2};
+
+    /// Loads `value` into the register at `addr` one bit per write, with a
+    /// cycle between the writes like a run of STA instructions.
+    fn load(mmc1: &mut MMC1, addr: BusAddr, value: u8) {
+        for bit in 0..SHIFT_REGISTER_BITS {
+            mmc1.cpu_write_byte(addr, (value >> bit) & 0x01);
+            mmc1.tick();
+            mmc1.tick();
+        }
+    }
+
+    #[test]
+    fn loads_registers_through_shift_register() {
+        let mut mmc1 = MMC1::new(memory(0x40000, 0x20000), 0);
+        for bit in 0..4 {
+            mmc1.cpu_write_byte(0x8000, (0x0E >> bit) & 0x01);
+            mmc1.tick();
+            mmc1.tick();
+        }
+        assert_eq!(mmc1.mirroring(), Mirroring::SingleScreenLower);
+        mmc1.cpu_write_byte(0x8000, 0);
+        mmc1.tick();
+        mmc1.tick();
+        assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+
+        load(&mut mmc1, 0xE000, 0x05);
+        assert_eq!(mmc1.cpu_read_byte(0x8000), 10);
+        assert_eq!(mmc1.cpu_read_byte(0xC000), 30);
+        load(&mut mmc1, 0xA000, 0x03);
+        load(&mut mmc1, 0xC000, 0x07);
+        assert_eq!(mmc1.ppu_read_byte(0x0000), 4 * 2);
+        assert_eq!(mmc1.ppu_read_byte(0x1000), 4 * 3);
+    }
+
+    #[test]
+    fn ignores_write_on_the_following_cycle() {
+        let mut mmc1 = MMC1::new(memory(0x40000, 0x2000), 0);
+        // The second write of a read-modify-write instruction is dropped.
+        mmc1.cpu_write_byte(0x8000, 0);
+        mmc1.tick();
+        mmc1.cpu_write_byte(0x8000, 1);
+        mmc1.tick();
+        mmc1.tick();
+        for value in [1, 0, 0, 0] {
+            mmc1.cpu_write_byte(0x8000, value);
+            mmc1.tick();
+            mmc1.tick();
+        }
+        assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn bit_7_write_resets_shift_register_and_prg_mode() {
+        let mut mmc1 = MMC1::new(memory(0x40000, 0x2000), 0);
+        load(&mut mmc1, 0x8000, 0x03);
+        load(&mut mmc1, 0xE000, 0x02);
+        assert_eq!(mmc1.cpu_read_byte(0xC000), 6);
+
+        mmc1.cpu_write_byte(0x8000, 1);
+        mmc1.tick();
+        mmc1.tick();
+        mmc1.cpu_write_byte(0x9FFF, SHIFT_RESET);
+        mmc1.tick();
+        mmc1.tick();
+        assert_eq!(mmc1.control, 0x0F);
+        assert_eq!(mmc1.cpu_read_byte(0x8000), 4);
+        assert_eq!(mmc1.cpu_read_byte(0xC000), 30);
+
+        load(&mut mmc1, 0x8000, 0x02);
+        assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn surom_selects_256kb_half_through_chr_register() {
+        let mut mmc1 = MMC1::new(memory(0x80000, 0), 0);
+        load(&mut mmc1, 0xE000, 0x01);
+        assert_eq!(mmc1.cpu_read_byte(0x8000), 2);
+        assert_eq!(mmc1.cpu_read_byte(0xC000), 30);
+
+        load(&mut mmc1, 0xA000, 0x10);
+        assert_eq!(mmc1.cpu_read_byte(0x8000), 34);
+        assert_eq!(mmc1.cpu_read_byte(0xC000), 62);
+    }
+
+    #[test]
+    fn sxrom_banks_prg_ram_through_chr_register() {
+        let mut ines = nes2(1, 0, 0x80000, 0);
+        ines.header.prg_ram_size = 0x8000;
+        let mut mmc1 = MMC1::new(CartridgeMemory::new(&ines), 0);
+        for bank in 0..4 {
+            load(&mut mmc1, 0xA000, bank << 2);
+            mmc1.cpu_write_byte(0x6000, 0xA0 | bank);
+        }
+        for bank in 0..4 {
+            load(&mut mmc1, 0xA000, bank << 2);
+            assert_eq!(mmc1.cpu_read_byte(0x6000), 0xA0 | bank);
+        }
+
+        load(&mut mmc1, 0xE000, PRG_RAM_DISABLE);
+        assert_eq!(mmc1.cpu_read_byte(0x6000), 0);
+    }
+
+    #[test]
+    fn serom_fixes_prg_rom() {
+        let mut mmc1 = MMC1::new(memory(0x8000, 0x2000), SUBMAPPER_FIXED_PRG);
+        load(&mut mmc1, 0xE000, 0x01);
+        assert_eq!(mmc1.cpu_read_byte(0x8000), 0);
+        assert_eq!(mmc1.cpu_read_byte(0xC000), 2);
+    }
+}
@@ -0,0 +1,212 @@
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const MMC2_PRG_BANK_SIZE: usize = 0x2000; // 8KB
+const MMC4_PRG_BANK_SIZE: usize = 0x4000; // 16KB
+const CHR_BANK_SIZE: usize = 0x1000; // 4KB
+
+const LATCH_FD: u8 = 0xFD;
+const LATCH_FE: u8 = 0xFE;
+
+/// Mapper 9, the MMC2 on PxROM, and mapper 10, the MMC4 on FxROM. Each
+/// pattern table has two CHR banks, and a latch picks between them by
+/// watching for the PPU fetching tile $FD or $FE.
+pub struct MMC2 {
+    memory: CartridgeMemory,
+    mmc4: bool,
+    prg_bank: u8,
+    /// Banks for latch values $FD and $FE, for each pattern table.
+    chr_banks: [[u8; 2]; 2],
+    latches: [u8; 2],
+    mirroring: Mirroring,
+}
+
+impl MMC2 {
+    pub(super) fn new(memory: CartridgeMemory, mmc4: bool) -> MMC2 {
+        MMC2 {
+            memory,
+            mmc4,
+            prg_bank: 0,
+            chr_banks: [[0; 2]; 2],
+            latches: [LATCH_FE; 2],
+            mirroring: Mirroring::Vertical,
+        }
+    }
+
+    fn prg_bank_size(&self) -> usize {
+        if self.mmc4 {
+            MMC4_PRG_BANK_SIZE
+        } else {
+            MMC2_PRG_BANK_SIZE
+        }
+    }
+
+    /// The MMC2 switches the first 8KB and fixes the last three banks, the
+    /// MMC4 switches the first 16KB and fixes the last.
+    fn prg_bank(&self, addr: BusAddr) -> usize {
+        let size = self.prg_bank_size();
+        match (addr - 0x8000) as usize / size {
+            0 => self.prg_bank as usize,
+            slot => self
+                .memory
+                .prg_bank_count(size)
+                .saturating_sub(0x8000 / size - slot),
+        }
+    }
+
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        let table = addr as usize / CHR_BANK_SIZE;
+        let latch = (self.latches[table] - LATCH_FD) as usize;
+        self.chr_banks[table][latch] as usize
+    }
+
+    /// Sets the latch after the PPU has fetched the high plane of tile $FD
+    /// or $FE. The MMC2 only reacts to the first byte of that plane in the
+    /// first pattern table.
+    fn update_latch(&mut self, addr: BusAddr) {
+        let table = addr as usize / CHR_BANK_SIZE;
+        let tile = match addr & 0x0FF8 {
+            0x0FD8 => LATCH_FD,
+            0x0FE8 => LATCH_FE,
+            _ => return,
+        };
+        if table == 0 && !self.mmc4 && addr & 0x07 != 0 {
+            return;
+        }
+        self.latches[table] = tile;
+    }
+}
+
+impl Mapper for MMC2 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            let size = self.prg_bank_size();
+            self.memory
+                .read_prg_rom(self.prg_bank(addr), size, addr as usize % size)
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.memory
+                .read_prg_ram((addr - PRG_RAM_START_ADDR) as usize)
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        match addr {
+            0xA000..=0xAFFF => self.prg_bank = value & 0x0F,
+            0xB000..=0xEFFF => {
+                let register = ((addr - 0xB000) >> 12) as usize;
+                self.chr_banks[register / 2][register % 2] = value & 0x1F;
+            }
+            0xF000..=0xFFFF => {
+                self.mirroring = if value & 0x01 != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            PRG_RAM_START_ADDR..=0x7FFF => {
+                self.memory
+                    .write_prg_ram((addr - PRG_RAM_START_ADDR) as usize, value);
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        let value = self
+            .memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize);
+        self.update_latch(addr);
+        value
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::memory;
+
+    fn mapper(mmc4: bool) -> MMC2 {
+        let mut mmc2 = MMC2::new(memory(0x20000, 0x20000), mmc4);
+        for (register, bank) in (0xB000..=0xE000).step_by(0x1000).zip([1, 2, 3, 4]) {
+            mmc2.cpu_write_byte(register, bank);
+        }
+        mmc2
+    }
+
+    /// The bank of each pattern table, in 4KB units.
+    fn banks(mmc2: &mut MMC2) -> [u8; 2] {
+        [
+            mmc2.ppu_read_byte(0x0000) / 4,
+            mmc2.ppu_read_byte(0x1000) / 4,
+        ]
+    }
+
+    #[test]
+    fn latch_switches_after_the_fetch() {
+        let mut mmc2 = mapper(false);
+        assert_eq!(banks(&mut mmc2), [2, 4]);
+        // The fetch that sets the latch still comes from the old bank.
+        assert_eq!(mmc2.ppu_read_byte(0x0FD8) / 4, 2);
+        assert_eq!(banks(&mut mmc2), [1, 4]);
+        mmc2.ppu_read_byte(0x1FD8);
+        assert_eq!(banks(&mut mmc2), [1, 3]);
+        mmc2.ppu_read_byte(0x0FE8);
+        mmc2.ppu_read_byte(0x1FE8);
+        assert_eq!(banks(&mut mmc2), [2, 4]);
+    }
+
+    #[test]
+    fn mmc2_matches_exact_address_in_first_table() {
+        let mut mmc2 = mapper(false);
+        for addr in [0x0FD9, 0x0FDF, 0x0FD0, 0x0FE0] {
+            mmc2.ppu_read_byte(addr);
+        }
+        assert_eq!(banks(&mut mmc2), [2, 4]);
+        // The second table reacts to the whole plane.
+        mmc2.ppu_read_byte(0x1FDF);
+        assert_eq!(banks(&mut mmc2), [2, 3]);
+    }
+
+    #[test]
+    fn mmc4_matches_range_in_both_tables() {
+        let mut mmc4 = mapper(true);
+        mmc4.ppu_read_byte(0x0FDF);
+        mmc4.ppu_read_byte(0x1FD9);
+        assert_eq!(banks(&mut mmc4), [1, 3]);
+        mmc4.ppu_read_byte(0x0FEF);
+        mmc4.ppu_read_byte(0x1FEA);
+        assert_eq!(banks(&mut mmc4), [2, 4]);
+    }
+
+    #[test]
+    fn fixes_last_prg_banks() {
+        let mut mmc2 = mapper(false);
+        mmc2.cpu_write_byte(0xA000, 5);
+        let banks = [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| mmc2.cpu_read_byte(addr));
+        assert_eq!(banks, [5, 13, 14, 15]);
+
+        let mut mmc4 = mapper(true);
+        mmc4.cpu_write_byte(0xA000, 5);
+        let banks = [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| mmc4.cpu_read_byte(addr));
+        assert_eq!(banks, [10, 11, 14, 15]);
+    }
+}
@@ -0,0 +1,357 @@
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x2000; // 8KB
+const CHR_BANK_SIZE: usize = 0x0400; // 1KB
+
+const SUBMAPPER_MMC6: u8 = 1;
+const SUBMAPPER_MMC3A: u8 = 4;
+
+const BANK_SELECT_REGISTER: u8 = 0x07;
+const BANK_SELECT_MMC6_PRG_RAM_ENABLE: u8 = 0x20;
+const BANK_SELECT_PRG_MODE: u8 = 0x40;
+const BANK_SELECT_CHR_INVERSION: u8 = 0x80;
+const PRG_RAM_PROTECT_WRITE_DENY: u8 = 0x40;
+const PRG_RAM_PROTECT_ENABLE: u8 = 0x80;
+
+/// The MMC6's 1KB of PRG-RAM at $7000-$7FFF, protected in two 512-byte
+/// halves.
+const MMC6_PRG_RAM_START_ADDR: BusAddr = 0x7000;
+const MMC6_PRG_RAM_SIZE: usize = 0x0400;
+const MMC6_PRG_RAM_HALF_SIZE: usize = 0x0200;
+
+/// CPU cycles A12 has to stay low before a rise clocks the IRQ counter.
+/// This filters out the short drops between sprite pattern fetches.
+const A12_FILTER_CYCLES: u64 = 3;
+
+/// Mapper 4, the MMC3 on the TxROM boards and the MMC6 on HKROM: 8KB PRG
+/// banks, 1KB and 2KB CHR banks, and an IRQ counter clocked by the PPU's
+/// A12 rising once per scanline.
+pub struct MMC3 {
+    memory: CartridgeMemory,
+    mmc6: bool,
+    /// The MMC3A and older chips only raise the IRQ when the counter is
+    /// decremented or reloaded to zero, not on every clock at zero.
+    old_irq: bool,
+    four_screen: bool,
+    bank_select: u8,
+    bank_registers: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12_high: bool,
+    a12_low_since: u64,
+    cycle: u64,
+}
+
+impl MMC3 {
+    pub(super) fn new(memory: CartridgeMemory, mirroring: Mirroring, submapper: u8) -> MMC3 {
+        MMC3 {
+            memory,
+            mmc6: submapper == SUBMAPPER_MMC6,
+            old_irq: submapper == SUBMAPPER_MMC3A,
+            four_screen: mirroring == Mirroring::FourScreen,
+            bank_select: 0,
+            bank_registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring,
+            prg_ram_protect: PRG_RAM_PROTECT_ENABLE,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12_high: false,
+            a12_low_since: 0,
+            cycle: 0,
+        }
+    }
+
+    fn prg_bank(&self, addr: BusAddr) -> usize {
+        let second_last = self.memory.prg_bank_count(PRG_BANK_SIZE).saturating_sub(2);
+        let swapped = self.bank_select & BANK_SELECT_PRG_MODE != 0;
+        match (addr - 0x8000) / PRG_BANK_SIZE as BusAddr {
+            0 if swapped => second_last,
+            0 => self.bank_registers[6] as usize,
+            1 => self.bank_registers[7] as usize,
+            2 if swapped => self.bank_registers[6] as usize,
+            2 => second_last,
+            _ => second_last + 1,
+        }
+    }
+
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        let mut slot = (addr / CHR_BANK_SIZE as BusAddr) as usize;
+        if self.bank_select & BANK_SELECT_CHR_INVERSION != 0 {
+            slot ^= 0x04;
+        }
+        match slot {
+            // Two 2KB banks, selected in 1KB units with the low bit ignored.
+            0 | 1 => (self.bank_registers[0] & 0xFE) as usize | slot,
+            2 | 3 => (self.bank_registers[1] & 0xFE) as usize | (slot & 0x01),
+            _ => self.bank_registers[slot - 2] as usize,
+        }
+    }
+
+    fn write_register(&mut self, addr: BusAddr, value: u8) {
+        let odd = addr & 0x01 != 0;
+        match (addr & 0xE000, odd) {
+            (0x8000, false) => self.bank_select = value,
+            (0x8000, true) => {
+                self.bank_registers[(self.bank_select & BANK_SELECT_REGISTER) as usize] = value
+            }
+            (0xA000, false) => {
+                if !self.four_screen {
+                    self.mirroring = if value & 0x01 != 0 {
+                        Mirroring::Horizontal
+                    } else {
+                        Mirroring::Vertical
+                    };
+                }
+            }
+            (0xA000, true) => self.prg_ram_protect = value,
+            (0xC000, false) => self.irq_latch = value,
+            (0xC000, true) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (0xE000, false) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            _ => self.irq_enabled = true,
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let previous = self.irq_counter;
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+        } else {
+            self.irq_counter -= 1;
+        }
+        let triggered = if self.old_irq {
+            self.irq_counter == 0 && (previous > 0 || self.irq_reload)
+        } else {
+            self.irq_counter == 0
+        };
+        self.irq_reload = false;
+        if triggered && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    /// Where a PRG-RAM access lands and whether reading or writing it is
+    /// allowed right now, or `None` when the chip does not answer at all.
+    fn prg_ram_access(&self, addr: BusAddr) -> Option<(usize, bool, bool)> {
+        if self.mmc6 {
+            if addr < MMC6_PRG_RAM_START_ADDR
+                || self.bank_select & BANK_SELECT_MMC6_PRG_RAM_ENABLE == 0
+            {
+                return None;
+            }
+            // Bits 7 and 6 enable reading and writing the upper half, bits
+            // 5 and 4 the lower half. With neither half readable the chip
+            // leaves the bus alone.
+            if self.prg_ram_protect & 0xA0 == 0 {
+                return None;
+            }
+            let offset = (addr - MMC6_PRG_RAM_START_ADDR) as usize % MMC6_PRG_RAM_SIZE;
+            let shift = if offset >= MMC6_PRG_RAM_HALF_SIZE {
+                6
+            } else {
+                4
+            };
+            let readable = self.prg_ram_protect & (0x02 << shift) != 0;
+            let writable = readable && self.prg_ram_protect & (0x01 << shift) != 0;
+            Some((offset, readable, writable))
+        } else {
+            if self.prg_ram_protect & PRG_RAM_PROTECT_ENABLE == 0 {
+                return None;
+            }
+            let writable = self.prg_ram_protect & PRG_RAM_PROTECT_WRITE_DENY == 0;
+            Some(((addr - PRG_RAM_START_ADDR) as usize, true, writable))
+        }
+    }
+}
+
+impl Mapper for MMC3 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory
+                .read_prg_rom(self.prg_bank(addr), PRG_BANK_SIZE, (addr & 0x1FFF) as usize)
+        } else if addr >= PRG_RAM_START_ADDR {
+            match self.prg_ram_access(addr) {
+                Some((offset, true, _)) => self.memory.read_prg_ram(offset),
+                _ => 0,
+            }
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.write_register(addr, value);
+        } else if addr >= PRG_RAM_START_ADDR {
+            if let Some((offset, _, true)) = self.prg_ram_access(addr) {
+                self.memory.write_prg_ram(offset, value);
+            }
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn tick(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn ppu_address(&mut self, addr: BusAddr) {
+        let a12_high = addr & 0x1000 != 0;
+        if a12_high && !self.a12_high && self.cycle - self.a12_low_since >= A12_FILTER_CYCLES {
+            self.clock_irq_counter();
+        } else if !a12_high && self.a12_high {
+            self.a12_low_since = self.cycle;
+        }
+        self.a12_high = a12_high;
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::memory;
+
+    /// Holds A12 low long enough to pass the filter, then raises it as the
+    /// PPU does once per scanline.
+    fn clock_scanline(mmc3: &mut MMC3) {
+        mmc3.ppu_address(0x0FF0);
+        for _ in 0..A12_FILTER_CYCLES {
+            mmc3.tick();
+        }
+        mmc3.ppu_address(0x1000);
+    }
+
+    fn mmc3(submapper: u8, latch: u8) -> MMC3 {
+        let mut mmc3 = MMC3::new(memory(0x20000, 0x20000), Mirroring::Vertical, submapper);
+        mmc3.cpu_write_byte(0xC000, latch);
+        mmc3.cpu_write_byte(0xC001, 0);
+        mmc3.cpu_write_byte(0xE001, 0);
+        mmc3
+    }
+
+    #[test]
+    fn irq_fires_when_counter_reaches_zero() {
+        let mut mmc3 = mmc3(0, 3);
+        for _ in 0..3 {
+            clock_scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+        clock_scanline(&mut mmc3);
+        assert!(mmc3.irq());
+
+        mmc3.cpu_write_byte(0xE000, 0);
+        assert!(!mmc3.irq());
+        mmc3.cpu_write_byte(0xE001, 0);
+        // Reloaded from the latch on the clock after reaching zero.
+        for _ in 0..3 {
+            clock_scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+        clock_scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+
+    #[test]
+    fn new_irq_fires_on_every_clock_with_zero_latch() {
+        let mut mmc3 = mmc3(0, 0);
+        for _ in 0..3 {
+            clock_scanline(&mut mmc3);
+            assert!(mmc3.irq());
+            mmc3.cpu_write_byte(0xE000, 0);
+            mmc3.cpu_write_byte(0xE001, 0);
+        }
+    }
+
+    #[test]
+    fn old_irq_fires_once_with_zero_latch() {
+        let mut mmc3 = mmc3(SUBMAPPER_MMC3A, 0);
+        clock_scanline(&mut mmc3);
+        assert!(mmc3.irq());
+        mmc3.cpu_write_byte(0xE000, 0);
+        mmc3.cpu_write_byte(0xE001, 0);
+        for _ in 0..3 {
+            clock_scanline(&mut mmc3);
+            assert!(!mmc3.irq());
+        }
+    }
+
+    #[test]
+    fn a12_filter_ignores_short_drops() {
+        let mut mmc3 = mmc3(0, 1);
+        clock_scanline(&mut mmc3);
+        assert_eq!(mmc3.irq_counter, 1);
+        // Sprite fetches drop A12 for less than the filter between rises.
+        for _ in 0..8 {
+            mmc3.ppu_address(0x0000);
+            mmc3.tick();
+            mmc3.ppu_address(0x1000);
+        }
+        assert_eq!(mmc3.irq_counter, 1);
+        assert!(!mmc3.irq());
+        clock_scanline(&mut mmc3);
+        assert!(mmc3.irq());
+    }
+
+    #[test]
+    fn prg_mode_swaps_fixed_bank() {
+        let mut mmc3 = mmc3(0, 0);
+        mmc3.cpu_write_byte(0x8000, 6);
+        mmc3.cpu_write_byte(0x8001, 3);
+        assert_eq!(mmc3.cpu_read_byte(0x8000), 3);
+        assert_eq!(mmc3.cpu_read_byte(0xC000), 14);
+        assert_eq!(mmc3.cpu_read_byte(0xE000), 15);
+        mmc3.cpu_write_byte(0x8000, BANK_SELECT_PRG_MODE | 6);
+        assert_eq!(mmc3.cpu_read_byte(0x8000), 14);
+        assert_eq!(mmc3.cpu_read_byte(0xC000), 3);
+    }
+
+    #[test]
+    fn reads_8kb_prg_rom() {
+        let memory = CartridgeMemory {
+            prg_rom: vec![0xEA; PRG_BANK_SIZE],
+            ..memory(0x4000, 0x2000)
+        };
+        let mut mmc3 = MMC3::new(memory, Mirroring::Vertical, 0);
+        for addr in [0x8000, 0xA000, 0xC000, 0xE000] {
+            assert_eq!(mmc3.cpu_read_byte(addr), 0xEA);
+        }
+    }
+}
@@ -0,0 +1,665 @@
+mod audio;
+
+use self::audio::MMC5Audio;
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x2000; // 8KB
+const EXRAM_SIZE: usize = 0x0400; // 1KB
+const EXRAM_START_ADDR: BusAddr = 0x5C00;
+const NAMETABLE_SIZE: usize = 0x0400;
+const ATTRIBUTE_TABLE_OFFSET: usize = 0x03C0;
+
+const PRG_BANK_ROM: u8 = 0x80;
+const PPU_CTRL_SPRITE_SIZE_8X16: u8 = 0x20;
+const SPLIT_ENABLE: u8 = 0x80;
+const SPLIT_RIGHT_SIDE: u8 = 0x40;
+const IRQ_ENABLE: u8 = 0x80;
+/// Scanlines the split region scrolls through before wrapping.
+const SPLIT_HEIGHT: u16 = 240;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ExRAMMode {
+    Nametable,
+    ExtendedAttributes,
+    RAM,
+    ReadOnlyRAM,
+}
+
+/// Where the background tile being fetched comes from. The MMC5 works this
+/// out from the nametable fetch and substitutes the attribute and pattern
+/// fetches that follow.
+#[derive(Debug, Clone, Copy)]
+enum BackgroundTile {
+    Nametable,
+    /// The ExRAM byte of the tile: a 4KB CHR bank and a palette.
+    ExtendedAttributes(u8),
+    /// A tile of the vertical split region, drawn from ExRAM.
+    Split {
+        row: u16,
+        column: u16,
+    },
+}
+
+/// Mapper 5, the MMC5 on the ExROM boards: PRG banks of up to four sizes
+/// with RAM mappable into the ROM area, separate CHR banks for sprites and
+/// background in 8x16 mode, 1KB of ExRAM usable as a nametable, extended
+/// attributes or a vertical split, a scanline IRQ, a multiplier, and extra
+/// sound channels.
+pub struct MMC5 {
+    memory: CartridgeMemory,
+    exram: [u8; EXRAM_SIZE],
+    audio: MMC5Audio,
+    prg_mode: u8,
+    chr_mode: u8,
+    prg_ram_protect: [u8; 2],
+    exram_mode: ExRAMMode,
+    nametable_mapping: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+    /// $5113-$5117: the PRG-RAM bank at $6000 and the four PRG banks.
+    prg_banks: [u8; 5],
+    /// $5120-$5127, used for sprites in 8x16 mode.
+    sprite_chr_banks: [u16; 8],
+    /// $5128-$512B, used for the background in 8x16 mode.
+    background_chr_banks: [u16; 4],
+    /// Whether $5128-$512B were written after $5120-$5127. The last set
+    /// written is used for everything outside 8x16 rendering.
+    background_chr_last: bool,
+    chr_upper_bits: u8,
+    split_control: u8,
+    split_scroll: u8,
+    split_chr_bank: u8,
+    irq_compare: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    multiplicand: u8,
+    multiplier: u8,
+
+    sprites_8x16: bool,
+    rendering: bool,
+    in_frame: bool,
+    scanline: u16,
+    pre_render: bool,
+    irq_scanline: u8,
+    nametable_fetches: u16,
+    pattern_fetches: u16,
+    background_tile: BackgroundTile,
+}
+
+impl MMC5 {
+    pub(super) fn new(memory: CartridgeMemory) -> MMC5 {
+        MMC5 {
+            memory,
+            exram: [0; EXRAM_SIZE],
+            audio: MMC5Audio::new(),
+            prg_mode: 3,
+            chr_mode: 0,
+            prg_ram_protect: [0; 2],
+            exram_mode: ExRAMMode::Nametable,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            prg_banks: [0, 0xFF, 0xFF, 0xFF, 0xFF],
+            sprite_chr_banks: [0; 8],
+            background_chr_banks: [0; 4],
+            background_chr_last: false,
+            chr_upper_bits: 0,
+            split_control: 0,
+            split_scroll: 0,
+            split_chr_bank: 0,
+            irq_compare: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            sprites_8x16: false,
+            rendering: false,
+            in_frame: false,
+            scanline: 0,
+            pre_render: false,
+            irq_scanline: 0,
+            nametable_fetches: 0,
+            pattern_fetches: 0,
+            background_tile: BackgroundTile::Nametable,
+        }
+    }
+
+    /// The register behind an 8KB slot of $8000-$FFFF and the 8KB bank it
+    /// selects there, counting from the register's bank in 16KB and 32KB
+    /// modes.
+    fn prg_bank(&self, addr: BusAddr) -> (u8, usize) {
+        let slot = ((addr - 0x8000) as usize / PRG_BANK_SIZE) as u8;
+        let (register, bank) = match (self.prg_mode, slot) {
+            (0, _) => (self.prg_banks[4], (self.prg_banks[4] & 0x7C) | slot),
+            (1 | 2, 0 | 1) => (self.prg_banks[2], (self.prg_banks[2] & 0x7E) | slot),
+            (1, _) => (
+                self.prg_banks[4],
+                (self.prg_banks[4] & 0x7E) | (slot & 0x01),
+            ),
+            _ => {
+                let register = self.prg_banks[1 + slot as usize];
+                (register, register)
+            }
+        };
+        // $E000-$FFFF is always ROM.
+        let register = if slot == 3 {
+            register | PRG_BANK_ROM
+        } else {
+            register
+        };
+        (register, (bank & 0x7F) as usize)
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_protect == [0x02, 0x01]
+    }
+
+    fn read_prg_ram(&self, bank: usize, addr: BusAddr) -> u8 {
+        self.memory
+            .read_prg_ram((bank & 0x07) * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1)))
+    }
+
+    fn write_prg_ram(&mut self, bank: usize, addr: BusAddr, value: u8) {
+        if self.prg_ram_writable() {
+            self.memory.write_prg_ram(
+                (bank & 0x07) * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1)),
+                value,
+            );
+        }
+    }
+
+    /// Whether the PPU is fetching sprite patterns right now. After the
+    /// two pattern fetches of each background tile, further pattern
+    /// fetches before the next nametable fetch belong to sprites.
+    fn is_sprite_fetch(&self) -> bool {
+        self.pattern_fetches > 2
+    }
+
+    /// Maps a pattern table address to its offset in CHR memory.
+    fn chr_offset(&self, addr: BusAddr) -> usize {
+        let addr = addr as usize;
+        if self.rendering && !self.is_sprite_fetch() {
+            match self.background_tile {
+                BackgroundTile::ExtendedAttributes(value) => {
+                    let bank = (value & 0x3F) as usize | (self.chr_upper_bits as usize) << 6;
+                    return bank * 0x1000 + (addr & 0x0FFF);
+                }
+                BackgroundTile::Split { row, .. } => {
+                    // The split has its own fine vertical scroll.
+                    let offset = (addr & 0x0FF8) | (row & 0x07) as usize;
+                    return self.split_chr_bank as usize * 0x1000 + offset;
+                }
+                BackgroundTile::Nametable => {}
+            }
+        }
+
+        let background = if self.rendering && self.sprites_8x16 {
+            !self.is_sprite_fetch()
+        } else {
+            self.background_chr_last
+        };
+        let banks = if background {
+            // The four background registers cover both pattern tables.
+            let banks = &self.background_chr_banks;
+            [
+                banks[0], banks[1], banks[2], banks[3], banks[0], banks[1], banks[2], banks[3],
+            ]
+        } else {
+            self.sprite_chr_banks
+        };
+        let (bank, bank_size) = match self.chr_mode {
+            0 => (banks[7], 0x2000),
+            1 => (banks[(addr / 0x1000) * 4 + 3], 0x1000),
+            2 => (banks[(addr / 0x0800) * 2 + 1], 0x0800),
+            _ => (banks[addr / 0x0400], 0x0400),
+        };
+        bank as usize * bank_size + (addr & (bank_size - 1))
+    }
+
+    fn read_chr(&self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(0, self.memory.chr.len(), self.chr_offset(addr))
+    }
+
+    /// Called for each nametable fetch while rendering, to find out where
+    /// the tile comes from.
+    fn fetch_background_tile(&mut self, addr: BusAddr) {
+        // The first 32 fetches of a scanline are its tiles 2-33, the next
+        // two are tiles 0-1 of the following scanline, and the last two
+        // are dummy fetches.
+        let fetch = self.nametable_fetches;
+        self.nametable_fetches += 1;
+        let (column, line) = match fetch {
+            0..=31 => (fetch + 2, self.scanline),
+            32 | 33 if self.pre_render => (fetch - 32, 0),
+            32 | 33 => (fetch - 32, self.scanline + 1),
+            _ => {
+                self.background_tile = BackgroundTile::Nametable;
+                return;
+            }
+        };
+
+        let threshold = (self.split_control & 0x1F) as u16;
+        let in_split = if self.split_control & SPLIT_RIGHT_SIDE != 0 {
+            column >= threshold
+        } else {
+            column < threshold
+        };
+        self.background_tile = if self.split_control & SPLIT_ENABLE != 0
+            && self.exram_mode <= ExRAMMode::ExtendedAttributes
+            && in_split
+        {
+            BackgroundTile::Split {
+                row: (line + self.split_scroll as u16) % SPLIT_HEIGHT,
+                column: column & 0x1F,
+            }
+        } else if self.exram_mode == ExRAMMode::ExtendedAttributes {
+            BackgroundTile::ExtendedAttributes(self.exram[addr as usize & (EXRAM_SIZE - 1)])
+        } else {
+            BackgroundTile::Nametable
+        };
+    }
+
+    fn read_split(&self, offset: usize, row: u16, column: u16) -> u8 {
+        let (row, column) = (row as usize / 8, column as usize);
+        if offset < ATTRIBUTE_TABLE_OFFSET {
+            return self.exram[row * 32 + column];
+        }
+        let attribute = self.exram[ATTRIBUTE_TABLE_OFFSET + (row / 4) * 8 + column / 4];
+        let shift = (row & 0x02) * 2 + (column & 0x02);
+        // Repeated in all four quadrants, as the PPU picks the quadrant
+        // from its own scroll position.
+        ((attribute >> shift) & 0x03) * 0x55
+    }
+
+    fn write_register(&mut self, addr: BusAddr, value: u8) {
+        match addr {
+            0x5000..=0x5015 => self.audio.write_register(addr, value),
+            0x5100 => self.prg_mode = value & 0x03,
+            0x5101 => self.chr_mode = value & 0x03,
+            0x5102 => self.prg_ram_protect[0] = value & 0x03,
+            0x5103 => self.prg_ram_protect[1] = value & 0x03,
+            0x5104 => {
+                self.exram_mode = match value & 0x03 {
+                    0 => ExRAMMode::Nametable,
+                    1 => ExRAMMode::ExtendedAttributes,
+                    2 => ExRAMMode::RAM,
+                    _ => ExRAMMode::ReadOnlyRAM,
+                }
+            }
+            0x5105 => self.nametable_mapping = value,
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_attribute = value & 0x03,
+            0x5113..=0x5117 => self.prg_banks[(addr - 0x5113) as usize] = value,
+            0x5120..=0x5127 => {
+                self.sprite_chr_banks[(addr - 0x5120) as usize] =
+                    value as u16 | (self.chr_upper_bits as u16) << 8;
+                self.background_chr_last = false;
+            }
+            0x5128..=0x512B => {
+                self.background_chr_banks[(addr - 0x5128) as usize] =
+                    value as u16 | (self.chr_upper_bits as u16) << 8;
+                self.background_chr_last = true;
+            }
+            0x5130 => self.chr_upper_bits = value & 0x03,
+            0x5200 => self.split_control = value,
+            0x5201 => self.split_scroll = value,
+            0x5202 => self.split_chr_bank = value,
+            0x5203 => self.irq_compare = value,
+            0x5204 => self.irq_enabled = value & IRQ_ENABLE != 0,
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            EXRAM_START_ADDR.. if self.exram_mode != ExRAMMode::ReadOnlyRAM => {
+                self.exram[(addr - EXRAM_START_ADDR) as usize] = value;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_register(&mut self, addr: BusAddr) -> u8 {
+        match addr {
+            0x5010 | 0x5015 => self.audio.read_register(addr),
+            0x5204 => {
+                let status = (self.irq_pending as u8) << 7 | (self.in_frame as u8) << 6;
+                self.irq_pending = false;
+                status
+            }
+            0x5205 => (self.multiplicand as u16 * self.multiplier as u16) as u8,
+            0x5206 => ((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8,
+            EXRAM_START_ADDR.. if self.exram_mode >= ExRAMMode::RAM => {
+                self.exram[(addr - EXRAM_START_ADDR) as usize]
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl Mapper for MMC5 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            let (register, bank) = self.prg_bank(addr);
+            let value = if register & PRG_BANK_ROM != 0 {
+                self.memory
+                    .read_prg_rom(bank, PRG_BANK_SIZE, (addr & 0x1FFF) as usize)
+            } else {
+                self.read_prg_ram(bank, addr)
+            };
+            self.audio.capture_read(addr, value);
+            value
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.read_prg_ram(self.prg_banks[0] as usize, addr)
+        } else {
+            self.read_register(addr)
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            let (register, bank) = self.prg_bank(addr);
+            if register & PRG_BANK_ROM == 0 {
+                self.write_prg_ram(bank, addr, value);
+            }
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.write_prg_ram(self.prg_banks[0] as usize, addr, value);
+        } else {
+            self.write_register(addr, value);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.read_chr(addr)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        let offset = self.chr_offset(addr);
+        let len = self.memory.chr.len();
+        self.memory.write_chr(0, len, offset, value);
+    }
+
+    /// The layout of $5105 when it matches one of the usual mirrorings.
+    /// Nametable accesses go through `read_nametable` either way.
+    fn mirroring(&self) -> Mirroring {
+        match self.nametable_mapping {
+            0x00 => Mirroring::SingleScreenLower,
+            0x55 => Mirroring::SingleScreenUpper,
+            0x50 => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn read_nametable(&mut self, addr: BusAddr, vram: &[u8]) -> u8 {
+        let offset = addr as usize & (NAMETABLE_SIZE - 1);
+        if self.rendering {
+            match self.background_tile {
+                BackgroundTile::Split { row, column } => {
+                    return self.read_split(offset, row, column);
+                }
+                BackgroundTile::ExtendedAttributes(value) if offset >= ATTRIBUTE_TABLE_OFFSET => {
+                    return (value >> 6) * 0x55;
+                }
+                _ => {}
+            }
+        }
+
+        let table = ((addr as usize - 0x2000) / NAMETABLE_SIZE) % 4;
+        match (self.nametable_mapping >> (table * 2)) & 0x03 {
+            0 => vram[offset],
+            1 => vram[NAMETABLE_SIZE + offset],
+            2 if self.exram_mode <= ExRAMMode::ExtendedAttributes => self.exram[offset],
+            2 => 0,
+            _ if offset < ATTRIBUTE_TABLE_OFFSET => self.fill_tile,
+            _ => self.fill_attribute * 0x55,
+        }
+    }
+
+    fn write_nametable(&mut self, addr: BusAddr, value: u8, vram: &mut [u8]) {
+        let offset = addr as usize & (NAMETABLE_SIZE - 1);
+        let table = ((addr as usize - 0x2000) / NAMETABLE_SIZE) % 4;
+        match (self.nametable_mapping >> (table * 2)) & 0x03 {
+            0 => vram[offset] = value,
+            1 => vram[NAMETABLE_SIZE + offset] = value,
+            2 if self.exram_mode <= ExRAMMode::ExtendedAttributes => self.exram[offset] = value,
+            _ => {}
+        }
+    }
+
+    fn irq(&self) -> bool {
+        (self.irq_pending && self.irq_enabled) || self.audio.irq()
+    }
+
+    fn tick(&mut self) {
+        self.audio.clock();
+    }
+
+    fn ppu_register_write(&mut self, register: BusAddr, value: u8) {
+        if register == 0 {
+            self.sprites_8x16 = value & PPU_CTRL_SPRITE_SIZE_8X16 != 0;
+        }
+    }
+
+    fn ppu_address(&mut self, addr: BusAddr) {
+        if !self.rendering {
+            return;
+        }
+        if addr < 0x2000 {
+            self.pattern_fetches += 1;
+        } else if addr as usize & (NAMETABLE_SIZE - 1) < ATTRIBUTE_TABLE_OFFSET {
+            self.pattern_fetches = 0;
+            self.fetch_background_tile(addr);
+        }
+    }
+
+    /// The MMC5 counts the scanlines it sees the PPU render, and raises
+    /// the IRQ on the one matching $5203.
+    fn scanline(&mut self, scanline: u16, pre_render: bool, rendering: bool) {
+        self.scanline = scanline;
+        self.pre_render = pre_render;
+        self.rendering = rendering && (scanline < 240 || pre_render);
+        self.nametable_fetches = 0;
+        self.pattern_fetches = 0;
+        if !rendering || scanline >= 240 {
+            self.in_frame = false;
+        } else if !self.in_frame {
+            self.in_frame = true;
+            self.irq_scanline = 0;
+            self.irq_pending = false;
+        } else {
+            self.irq_scanline = self.irq_scanline.wrapping_add(1);
+            if self.irq_scanline == self.irq_compare {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn audio_level(&self) -> f32 {
+        self.audio.level()
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::{memory, nes2};
+
+    fn mmc5() -> MMC5 {
+        let mut ines = nes2(5, 0, 0x20000, 0x8000);
+        ines.header.prg_ram_size = 0x10000;
+        MMC5::new(CartridgeMemory::new(&ines))
+    }
+
+    /// Starts rendering scanline `scanline` of a frame.
+    fn render_scanline(mmc5: &mut MMC5, scanline: u16) {
+        mmc5.scanline(scanline, false, true);
+    }
+
+    #[test]
+    fn decodes_prg_modes() {
+        let mut mmc5 = mmc5();
+        let banks =
+            |mmc5: &mut MMC5| [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| mmc5.cpu_read_byte(addr));
+
+        mmc5.cpu_write_byte(0x5100, 0);
+        mmc5.cpu_write_byte(0x5117, 0x87);
+        assert_eq!(banks(&mut mmc5), [4, 5, 6, 7]);
+
+        mmc5.cpu_write_byte(0x5100, 1);
+        mmc5.cpu_write_byte(0x5115, 0x85);
+        mmc5.cpu_write_byte(0x5117, 0x8B);
+        assert_eq!(banks(&mut mmc5), [4, 5, 10, 11]);
+
+        mmc5.cpu_write_byte(0x5100, 2);
+        mmc5.cpu_write_byte(0x5115, 0x83);
+        mmc5.cpu_write_byte(0x5116, 0x89);
+        mmc5.cpu_write_byte(0x5117, 0x8E);
+        assert_eq!(banks(&mut mmc5), [2, 3, 9, 14]);
+
+        mmc5.cpu_write_byte(0x5100, 3);
+        for (register, bank) in (0x5114..=0x5117).zip([0x81, 0x82, 0x83, 0x04]) {
+            mmc5.cpu_write_byte(register, bank);
+        }
+        // $E000-$FFFF is ROM even with bit 7 clear.
+        assert_eq!(banks(&mut mmc5), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn maps_prg_ram_into_rom_area() {
+        let mut mmc5 = mmc5();
+        mmc5.cpu_write_byte(0x5114, 0x01);
+        mmc5.cpu_write_byte(0x8000, 0x5A);
+        assert_eq!(mmc5.cpu_read_byte(0x8000), 0);
+
+        mmc5.cpu_write_byte(0x5102, 0x02);
+        mmc5.cpu_write_byte(0x5103, 0x01);
+        mmc5.cpu_write_byte(0x8000, 0x5A);
+        assert_eq!(mmc5.cpu_read_byte(0x8000), 0x5A);
+        mmc5.cpu_write_byte(0x5113, 0x01);
+        assert_eq!(mmc5.cpu_read_byte(0x6000), 0x5A);
+    }
+
+    #[test]
+    fn exram_modes() {
+        let mut mmc5 = mmc5();
+        let vram = [0x11; 0x1000];
+        mmc5.cpu_write_byte(0x5105, 0xAA);
+        mmc5.cpu_write_byte(0x5C10, 0x42);
+        // As a nametable, ExRAM is not readable by the CPU.
+        assert_eq!(mmc5.cpu_read_byte(0x5C10), 0);
+        assert_eq!(mmc5.read_nametable(0x2010, &vram), 0x42);
+
+        mmc5.cpu_write_byte(0x5104, 2);
+        assert_eq!(mmc5.cpu_read_byte(0x5C10), 0x42);
+        assert_eq!(mmc5.read_nametable(0x2010, &vram), 0);
+        mmc5.cpu_write_byte(0x5C10, 0x43);
+        assert_eq!(mmc5.cpu_read_byte(0x5C10), 0x43);
+
+        mmc5.cpu_write_byte(0x5104, 3);
+        mmc5.cpu_write_byte(0x5C10, 0x44);
+        assert_eq!(mmc5.cpu_read_byte(0x5C10), 0x43);
+    }
+
+    #[test]
+    fn fill_mode_nametable() {
+        let mut mmc5 = mmc5();
+        let vram = [0x11; 0x1000];
+        mmc5.cpu_write_byte(0x5105, 0xE4);
+        mmc5.cpu_write_byte(0x5106, 0x42);
+        mmc5.cpu_write_byte(0x5107, 0x02);
+        assert_eq!(mmc5.read_nametable(0x2000, &vram), 0x11);
+        assert_eq!(mmc5.read_nametable(0x2C00, &vram), 0x42);
+        assert_eq!(mmc5.read_nametable(0x2FBF, &vram), 0x42);
+        assert_eq!(mmc5.read_nametable(0x2FC0, &vram), 0xAA);
+    }
+
+    #[test]
+    fn multiplies() {
+        let mut mmc5 = mmc5();
+        assert_eq!(mmc5.cpu_read_byte(0x5205), 0x01);
+        assert_eq!(mmc5.cpu_read_byte(0x5206), 0xFE);
+        mmc5.cpu_write_byte(0x5205, 200);
+        mmc5.cpu_write_byte(0x5206, 100);
+        assert_eq!(mmc5.cpu_read_byte(0x5205), 0x20);
+        assert_eq!(mmc5.cpu_read_byte(0x5206), 0x4E);
+    }
+
+    #[test]
+    fn scanline_irq() {
+        let mut mmc5 = mmc5();
+        mmc5.cpu_write_byte(0x5203, 3);
+        mmc5.cpu_write_byte(0x5204, IRQ_ENABLE);
+        mmc5.scanline(261, true, true);
+        for scanline in 0..3 {
+            render_scanline(&mut mmc5, scanline);
+            assert!(!mmc5.irq());
+        }
+        render_scanline(&mut mmc5, 3);
+        assert!(mmc5.irq());
+        assert_eq!(mmc5.cpu_read_byte(0x5204), 0xC0);
+        assert!(!mmc5.irq());
+        assert_eq!(mmc5.cpu_read_byte(0x5204), 0x40);
+
+        mmc5.scanline(240, false, true);
+        assert_eq!(mmc5.cpu_read_byte(0x5204), 0x00);
+    }
+
+    #[test]
+    fn extended_attributes_select_chr_bank_and_palette() {
+        let mut mmc5 = mmc5();
+        let vram = [0x11; 0x1000];
+        mmc5.cpu_write_byte(0x5104, 1);
+        mmc5.cpu_write_byte(0x5C05, 0xC3);
+        render_scanline(&mut mmc5, 10);
+        mmc5.ppu_address(0x2005);
+        assert_eq!(mmc5.read_nametable(0x2005, &vram), 0x11);
+        assert_eq!(mmc5.read_nametable(0x23C1, &vram), 0xFF);
+        assert_eq!(mmc5.ppu_read_byte(0x0010), 12);
+    }
+
+    #[test]
+    fn vertical_split_draws_from_exram() {
+        let mut mmc5 = mmc5();
+        let vram = [0x11; 0x1000];
+        mmc5.cpu_write_byte(0x5C00 + 34, 0x77);
+        mmc5.cpu_write_byte(0x5FC0, 0x0C);
+        mmc5.cpu_write_byte(0x5200, SPLIT_ENABLE | SPLIT_RIGHT_SIDE | 2);
+        mmc5.cpu_write_byte(0x5201, 8);
+        mmc5.cpu_write_byte(0x5202, 1);
+
+        // The first fetch of scanline 5 is tile 2, on split row 13.
+        render_scanline(&mut mmc5, 5);
+        mmc5.ppu_address(0x2000);
+        assert_eq!(mmc5.read_nametable(0x2000, &vram), 0x77);
+        assert_eq!(mmc5.read_nametable(0x23C0, &vram), 0xFF);
+        assert_eq!(mmc5.ppu_read_byte(0x0770), ((0x1000 + 0x775) / 0x400) as u8);
+
+        // Tiles 0 and 1 of the next scanline are left of the split.
+        for _ in 0..32 {
+            mmc5.ppu_address(0x2000);
+        }
+        assert_eq!(mmc5.read_nametable(0x2000, &vram), 0x11);
+    }
+
+    #[test]
+    fn chr_banks_for_sprites_and_background_in_8x16_mode() {
+        let mut mmc5 = MMC5::new(memory(0x20000, 0x20000));
+        mmc5.cpu_write_byte(0x5101, 3);
+        mmc5.cpu_write_byte(0x5120, 0x10);
+        mmc5.cpu_write_byte(0x5128, 0x20);
+        mmc5.ppu_register_write(0, PPU_CTRL_SPRITE_SIZE_8X16);
+        render_scanline(&mut mmc5, 0);
+        mmc5.ppu_address(0x2000);
+        assert_eq!(mmc5.ppu_read_byte(0x0000), 0x20);
+        mmc5.ppu_address(0x0000);
+        mmc5.ppu_address(0x0008);
+        mmc5.ppu_address(0x0000);
+        assert_eq!(mmc5.ppu_read_byte(0x0000), 0x10);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{apu::Pulse, bus::BusAddr};
+
+/// The envelopes and length counters are clocked at a fixed 240Hz instead
+/// of by the APU's frame counter.
+const FRAME_PERIOD: u16 = 7457;
+const PCM_READ_MODE: u8 = 0x01;
+const PCM_IRQ_ENABLE: u8 = 0x80;
+/// The 8-bit PCM channel at full scale is about as loud as the DMC.
+const PCM_FULL_LEVEL: f32 = 0.57;
+
+/// The MMC5's sound hardware: two pulse channels like the 2A03's, minus
+/// the sweep units, and an 8-bit PCM channel written directly or fed by
+/// reads from $8000-$BFFF.
+pub struct MMC5Audio {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    frame_timer: u16,
+    cycle: u64,
+    pcm_control: u8,
+    pcm: u8,
+    pcm_irq: bool,
+}
+
+impl MMC5Audio {
+    pub fn new() -> Self {
+        Self {
+            pulse1: Pulse::without_sweep(),
+            pulse2: Pulse::without_sweep(),
+            frame_timer: 0,
+            cycle: 0,
+            pcm_control: 0,
+            pcm: 0,
+            pcm_irq: false,
+        }
+    }
+
+    /// Advances the channels by a single CPU cycle.
+    pub fn clock(&mut self) {
+        if self.cycle % 2 == 1 {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.frame_timer += 1;
+        if self.frame_timer == FRAME_PERIOD {
+            self.frame_timer = 0;
+            for pulse in [&mut self.pulse1, &mut self.pulse2] {
+                pulse.clock_quarter_frame();
+                pulse.clock_half_frame();
+            }
+        }
+        self.cycle += 1;
+    }
+
+    /// Output scaled relative to the 2A03 channels. The pulse channels go
+    /// through the same non-linear DAC curve as the 2A03's.
+    pub fn level(&self) -> f32 {
+        let pulse_sum = (self.pulse1.output() + self.pulse2.output()) as f32;
+        let pulse_out = if pulse_sum == 0.0 {
+            0.0
+        } else {
+            95.88 / (8128.0 / pulse_sum + 100.0)
+        };
+        pulse_out + self.pcm as f32 / 255.0 * PCM_FULL_LEVEL
+    }
+
+    pub fn irq(&self) -> bool {
+        self.pcm_irq && self.pcm_control & PCM_IRQ_ENABLE != 0
+    }
+
+    /// In read mode, the PCM channel takes every byte the CPU reads from
+    /// $8000-$BFFF. A zero byte raises the IRQ instead.
+    pub fn capture_read(&mut self, addr: BusAddr, value: u8) {
+        if self.pcm_control & PCM_READ_MODE != 0 && (0x8000..0xC000).contains(&addr) {
+            if value == 0 {
+                self.pcm_irq = true;
+            } else {
+                self.pcm = value;
+            }
+        }
+    }
+
+    pub fn read_register(&mut self, addr: BusAddr) -> u8 {
+        match addr {
+            0x5010 => {
+                // The flag reads back even with the IRQ disabled.
+                let status = (self.pcm_irq as u8) << 7;
+                self.pcm_irq = false;
+                status
+            }
+            0x5015 => (self.pulse2.is_active() as u8) << 1 | self.pulse1.is_active() as u8,
+            _ => 0,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: BusAddr, value: u8) {
+        match addr {
+            // There is no sweep unit behind $5001 and $5005.
+            0x5000 | 0x5002 | 0x5003 => self.pulse1.write_register(addr - 0x5000, value),
+            0x5004 | 0x5006 | 0x5007 => self.pulse2.write_register(addr - 0x5004, value),
+            0x5010 => self.pcm_control = value,
+            // Zero cannot be played, and writing it is ignored.
+            0x5011 if self.pcm_control & PCM_READ_MODE == 0 && value != 0 => self.pcm = value,
+            0x5015 => {
+                self.pulse1.set_enabled(value & 0x01 != 0);
+                self.pulse2.set_enabled(value & 0x02 != 0);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pcm_irq_flag_reads_back_while_disabled() {
+        let mut audio = MMC5Audio::new();
+        audio.write_register(0x5010, PCM_READ_MODE);
+        audio.capture_read(0x8000, 0x00);
+        assert!(!audio.irq());
+        assert_eq!(audio.read_register(0x5010), 0x80);
+        assert_eq!(audio.read_register(0x5010), 0x00);
+
+        audio.write_register(0x5010, PCM_READ_MODE | PCM_IRQ_ENABLE);
+        audio.capture_read(0x8000, 0x00);
+        assert!(audio.irq());
+        assert_eq!(audio.read_register(0x5010), 0x80);
+        assert!(!audio.irq());
+    }
+
+    #[test]
+    fn pcm_read_mode_captures_reads() {
+        let mut audio = MMC5Audio::new();
+        audio.capture_read(0x8000, 0x40);
+        assert_eq!(audio.pcm, 0);
+        audio.write_register(0x5010, PCM_READ_MODE);
+        audio.capture_read(0xC000, 0x40);
+        assert_eq!(audio.pcm, 0);
+        audio.capture_read(0xBFFF, 0x40);
+        assert_eq!(audio.pcm, 0x40);
+    }
+}
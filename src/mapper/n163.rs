@@ -0,0 +1,348 @@
+mod audio;
+
+use self::audio::N163Audio;
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x2000; // 8KB
+const CHR_BANK_SIZE: usize = 0x0400; // 1KB
+const NAMETABLE_SIZE: usize = 0x0400;
+
+const SOUND_RAM_SIZE: usize = 0x80;
+const SOUND_ADDRESS_INCREMENT: u8 = 0x80;
+const SOUND_DISABLE: u8 = 0x40;
+/// Bank numbers from $E0 up select the console's nametable RAM.
+const CIRAM_BANKS_START: u8 = 0xE0;
+/// Bits of $E800 that keep pattern table banks from $E0 up in CHR-ROM, for
+/// $0000-$0FFF and $1000-$1FFF.
+const CHR_LOW_CIRAM_DISABLE: u8 = 0x40;
+const CHR_HIGH_CIRAM_DISABLE: u8 = 0x80;
+/// PRG-RAM only takes writes while the upper nibble of $F800 is 0100, and
+/// then only to the 2KB quarters whose protect bit is clear.
+const PRG_RAM_WRITE_KEY: u8 = 0x40;
+const PRG_RAM_QUARTER_SIZE: usize = 0x0800;
+const IRQ_ENABLE: u8 = 0x80;
+const IRQ_COUNTER_MAX: u16 = 0x7FFF;
+
+/// Mapper 19, the Namco 163: 8KB PRG banks, 1KB CHR banks, nametables that
+/// can come from CHR-ROM, a 15-bit IRQ counter and wavetable sound. The
+/// chip's 128 bytes of sound RAM are battery-backed along with PRG-RAM.
+pub struct N163 {
+    /// The sound RAM is kept after the PRG-RAM so that both are saved.
+    memory: CartridgeMemory,
+    prg_ram_size: usize,
+    audio: N163Audio,
+    sound_address: u8,
+    chr_banks: [u8; 8],
+    nametable_banks: [u8; 4],
+    /// $E000, $E800 and $F000, with control bits above the bank number.
+    prg_banks: [u8; 3],
+    /// $F800: PRG-RAM write protection, written along with the sound
+    /// address.
+    prg_ram_protect: u8,
+    irq_counter: u16,
+    irq_pending: bool,
+}
+
+impl N163 {
+    pub(super) fn new(mut memory: CartridgeMemory) -> N163 {
+        let prg_ram_size = memory.prg_ram.len();
+        memory.prg_ram.resize(prg_ram_size + SOUND_RAM_SIZE, 0);
+        N163 {
+            memory,
+            prg_ram_size,
+            audio: N163Audio::new(),
+            sound_address: 0,
+            chr_banks: [0; 8],
+            nametable_banks: [CIRAM_BANKS_START; 4],
+            prg_banks: [0; 3],
+            prg_ram_protect: 0,
+            irq_counter: 0,
+            irq_pending: false,
+        }
+    }
+
+    fn sound_ram(&self) -> &[u8] {
+        &self.memory.prg_ram[self.prg_ram_size..]
+    }
+
+    fn sound_ram_mut(&mut self) -> &mut [u8] {
+        &mut self.memory.prg_ram[self.prg_ram_size..]
+    }
+
+    fn sound_enabled(&self) -> bool {
+        self.prg_banks[0] & SOUND_DISABLE == 0
+    }
+
+    /// Reads or writes through the data port move the address along when
+    /// auto-increment is on.
+    fn sound_ram_index(&mut self) -> usize {
+        let index = (self.sound_address & 0x7F) as usize;
+        if self.sound_address & SOUND_ADDRESS_INCREMENT != 0 {
+            self.sound_address =
+                SOUND_ADDRESS_INCREMENT | (self.sound_address.wrapping_add(1) & 0x7F);
+        }
+        index
+    }
+
+    fn prg_bank(&self, addr: BusAddr) -> usize {
+        match (addr - 0x8000) as usize / PRG_BANK_SIZE {
+            3 => self.memory.prg_bank_count(PRG_BANK_SIZE) - 1,
+            slot => (self.prg_banks[slot] & 0x3F) as usize,
+        }
+    }
+
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        self.chr_banks[addr as usize / CHR_BANK_SIZE] as usize
+    }
+
+    /// Pattern table banks from $E0 up show the console's nametable RAM,
+    /// unless $E800 keeps that half of the pattern tables in CHR-ROM.
+    fn chr_ciram_page(&self, addr: BusAddr) -> Option<usize> {
+        let disable = if addr < 0x1000 {
+            CHR_LOW_CIRAM_DISABLE
+        } else {
+            CHR_HIGH_CIRAM_DISABLE
+        };
+        if self.prg_banks[1] & disable != 0 {
+            return None;
+        }
+        Self::ciram_page(self.chr_banks[addr as usize / CHR_BANK_SIZE])
+    }
+
+    /// The console's nametable RAM page the bank register selects, if any.
+    fn ciram_page(bank: u8) -> Option<usize> {
+        (bank >= CIRAM_BANKS_START).then_some((bank & 0x01) as usize)
+    }
+
+    fn prg_ram_writable(&self, offset: usize) -> bool {
+        self.prg_ram_protect & 0xF0 == PRG_RAM_WRITE_KEY
+            && self.prg_ram_protect & (1 << (offset / PRG_RAM_QUARTER_SIZE)) == 0
+    }
+}
+
+impl Mapper for N163 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        match addr {
+            0x4800..=0x4FFF => {
+                let index = self.sound_ram_index();
+                self.sound_ram()[index]
+            }
+            0x5000..=0x57FF => self.irq_counter as u8,
+            0x5800..=0x5FFF => (self.irq_counter >> 8) as u8,
+            0x6000..=0x7FFF if self.prg_ram_size > 0 => {
+                self.memory.prg_ram[(addr - PRG_RAM_START_ADDR) as usize % self.prg_ram_size]
+            }
+            0x8000..=0xFFFF => self.memory.read_prg_rom(
+                self.prg_bank(addr),
+                PRG_BANK_SIZE,
+                (addr & 0x1FFF) as usize,
+            ),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        match addr {
+            0x4800..=0x4FFF => {
+                let index = self.sound_ram_index();
+                self.sound_ram_mut()[index] = value;
+            }
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0xFF00) | value as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | (value as u16) << 8;
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF if self.prg_ram_size > 0 => {
+                let offset = (addr - PRG_RAM_START_ADDR) as usize;
+                if self.prg_ram_writable(offset) {
+                    self.memory.prg_ram[offset % self.prg_ram_size] = value;
+                }
+            }
+            0x8000..=0xBFFF => self.chr_banks[(addr - 0x8000) as usize >> 11] = value,
+            0xC000..=0xDFFF => self.nametable_banks[(addr - 0xC000) as usize >> 11] = value,
+            0xE000..=0xF7FF => self.prg_banks[(addr - 0xE000) as usize >> 11] = value,
+            0xF800..=0xFFFF => {
+                self.sound_address = value;
+                self.prg_ram_protect = value;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn read_pattern_table(&mut self, addr: BusAddr, vram: &[u8]) -> u8 {
+        match self.chr_ciram_page(addr) {
+            Some(page) => vram[page * NAMETABLE_SIZE + addr as usize % CHR_BANK_SIZE],
+            None => self.ppu_read_byte(addr),
+        }
+    }
+
+    fn write_pattern_table(&mut self, addr: BusAddr, value: u8, vram: &mut [u8]) {
+        match self.chr_ciram_page(addr) {
+            Some(page) => vram[page * NAMETABLE_SIZE + addr as usize % CHR_BANK_SIZE] = value,
+            None => self.ppu_write_byte(addr, value),
+        }
+    }
+
+    /// The nearest match to the nametable banks, if they all point at the
+    /// console's nametable RAM.
+    fn mirroring(&self) -> Mirroring {
+        match self.nametable_banks.map(Self::ciram_page) {
+            [Some(0), Some(0), Some(0), Some(0)] => Mirroring::SingleScreenLower,
+            [Some(1), Some(1), Some(1), Some(1)] => Mirroring::SingleScreenUpper,
+            [Some(0), Some(0), Some(1), Some(1)] => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn read_nametable(&mut self, addr: BusAddr, vram: &[u8]) -> u8 {
+        let offset = addr as usize & (NAMETABLE_SIZE - 1);
+        let bank = self.nametable_banks[((addr as usize - 0x2000) / NAMETABLE_SIZE) % 4];
+        match Self::ciram_page(bank) {
+            Some(page) => vram[page * NAMETABLE_SIZE + offset],
+            None => self.memory.read_chr(bank as usize, CHR_BANK_SIZE, offset),
+        }
+    }
+
+    fn write_nametable(&mut self, addr: BusAddr, value: u8, vram: &mut [u8]) {
+        let offset = addr as usize & (NAMETABLE_SIZE - 1);
+        let bank = self.nametable_banks[((addr as usize - 0x2000) / NAMETABLE_SIZE) % 4];
+        match Self::ciram_page(bank) {
+            Some(page) => vram[page * NAMETABLE_SIZE + offset] = value,
+            None => self
+                .memory
+                .write_chr(bank as usize, CHR_BANK_SIZE, offset, value),
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn tick(&mut self) {
+        let counter = self.irq_counter & IRQ_COUNTER_MAX;
+        if self.irq_counter & (IRQ_ENABLE as u16) << 8 != 0 && counter != IRQ_COUNTER_MAX {
+            self.irq_counter += 1;
+            if counter + 1 == IRQ_COUNTER_MAX {
+                self.irq_pending = true;
+            }
+        }
+        if self.sound_enabled() {
+            let ram = &mut self.memory.prg_ram[self.prg_ram_size..];
+            self.audio.clock(ram);
+        }
+    }
+
+    fn audio_level(&self) -> f32 {
+        if self.sound_enabled() {
+            self.audio.level(self.sound_ram())
+        } else {
+            0.0
+        }
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::memory;
+
+    fn n163() -> N163 {
+        N163::new(memory(0x20000, 0x40000))
+    }
+
+    #[test]
+    fn sound_ram_address_auto_increments() {
+        let mut n163 = n163();
+        n163.cpu_write_byte(0xF800, SOUND_ADDRESS_INCREMENT | 0x7E);
+        for value in [0x11, 0x22, 0x33] {
+            n163.cpu_write_byte(0x4800, value);
+        }
+        assert_eq!(n163.sound_ram()[0x7E..], [0x11, 0x22]);
+        assert_eq!(n163.sound_ram()[0x00], 0x33);
+
+        n163.cpu_write_byte(0xF800, SOUND_ADDRESS_INCREMENT | 0x7F);
+        assert_eq!(n163.cpu_read_byte(0x4800), 0x22);
+        assert_eq!(n163.cpu_read_byte(0x4800), 0x33);
+    }
+
+    #[test]
+    fn sound_ram_address_holds_without_increment() {
+        let mut n163 = n163();
+        n163.cpu_write_byte(0xF800, 0x05);
+        n163.cpu_write_byte(0x4800, 0x11);
+        n163.cpu_write_byte(0x4800, 0x22);
+        assert_eq!(n163.cpu_read_byte(0x4800), 0x22);
+        assert_eq!(n163.sound_ram()[0x06], 0x00);
+    }
+
+    #[test]
+    fn irq_counter_counts_up_to_7fff() {
+        let mut n163 = n163();
+        n163.cpu_write_byte(0x5000, 0xFD);
+        n163.cpu_write_byte(0x5800, 0x7F);
+        n163.tick();
+        assert_eq!(n163.cpu_read_byte(0x5000), 0xFD);
+
+        n163.cpu_write_byte(0x5800, IRQ_ENABLE | 0x7F);
+        n163.tick();
+        assert!(!n163.irq());
+        n163.tick();
+        assert!(n163.irq());
+        n163.tick();
+        assert_eq!(n163.cpu_read_byte(0x5000), 0xFF);
+        assert_eq!(n163.cpu_read_byte(0x5800), 0xFF);
+
+        n163.cpu_write_byte(0x5000, 0x00);
+        assert!(!n163.irq());
+    }
+
+    #[test]
+    fn pattern_banks_from_e0_map_nametable_ram() {
+        let mut n163 = n163();
+        let mut vram = [0; 0x1000];
+        vram[0x0405] = 0x5A;
+        n163.cpu_write_byte(0x8000, 0xE1);
+        n163.cpu_write_byte(0xA000, 0xE0);
+        assert_eq!(n163.read_pattern_table(0x0005, &vram), 0x5A);
+        n163.write_pattern_table(0x1007, 0xA5, &mut vram);
+        assert_eq!(vram[0x0007], 0xA5);
+
+        n163.cpu_write_byte(0xE800, CHR_LOW_CIRAM_DISABLE);
+        assert_eq!(n163.read_pattern_table(0x0005, &vram), 0xE1);
+        assert_eq!(n163.read_pattern_table(0x1007, &vram), 0xA5);
+        n163.cpu_write_byte(0xE800, CHR_HIGH_CIRAM_DISABLE);
+        assert_eq!(n163.read_pattern_table(0x0005, &vram), 0x5A);
+        assert_eq!(n163.read_pattern_table(0x1007, &vram), 0xE0);
+    }
+
+    #[test]
+    fn nametable_banks_map_chr_rom_below_e0() {
+        let mut n163 = n163();
+        let vram = [0x11; 0x1000];
+        assert_eq!(n163.read_nametable(0x2000, &vram), 0x11);
+        n163.cpu_write_byte(0xC800, 0x42);
+        assert_eq!(n163.read_nametable(0x2400, &vram), 0x42);
+    }
+}
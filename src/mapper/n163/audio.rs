@@ -0,0 +1,87 @@
+/// Each channel in turn is updated for 15 CPU cycles.
+const CYCLES_PER_CHANNEL: u8 = 15;
+const CHANNEL_COUNT: usize = 8;
+/// The channel registers fill the top of the sound RAM, eight bytes each.
+const CHANNEL_REGISTERS_START: usize = 0x40;
+const CHANNEL_COUNT_REGISTER: usize = 0x7F;
+/// A lone channel at full volume is a little louder than a 2A03 pulse
+/// channel at full volume. Boards differ in how loud they mix it.
+const LEVEL_PER_STEP: f32 = 0.0011;
+
+/// The Namco 163's sound hardware: up to eight channels playing 4-bit
+/// waveforms out of the chip's 128 bytes of RAM. The channels' registers,
+/// including their phase, live in the same RAM, so it is passed in rather
+/// than owned.
+pub struct N163Audio {
+    cycle: u8,
+    channel: usize,
+    /// The latest output of each channel.
+    outputs: [i16; CHANNEL_COUNT],
+}
+
+impl N163Audio {
+    pub fn new() -> Self {
+        Self {
+            cycle: 0,
+            channel: CHANNEL_COUNT - 1,
+            outputs: [0; CHANNEL_COUNT],
+        }
+    }
+
+    /// Only the last channels run, from 1 up to all 8.
+    fn active_channels(ram: &[u8]) -> usize {
+        ((ram[CHANNEL_COUNT_REGISTER] >> 4) & 0x07) as usize + 1
+    }
+
+    /// Advances the channels by a single CPU cycle.
+    pub fn clock(&mut self, ram: &mut [u8]) {
+        self.cycle += 1;
+        if self.cycle < CYCLES_PER_CHANNEL {
+            return;
+        }
+        self.cycle = 0;
+
+        let active = Self::active_channels(ram);
+        self.channel = if self.channel <= CHANNEL_COUNT - active {
+            CHANNEL_COUNT - 1
+        } else {
+            self.channel - 1
+        };
+        self.update_channel(ram, self.channel);
+    }
+
+    /// Adds the channel's frequency to its phase and looks up the sample
+    /// the phase now points at.
+    fn update_channel(&mut self, ram: &mut [u8], channel: usize) {
+        let registers = CHANNEL_REGISTERS_START + channel * 8;
+        let frequency = ram[registers] as u32
+            | (ram[registers + 2] as u32) << 8
+            | ((ram[registers + 4] & 0x03) as u32) << 16;
+        let phase = ram[registers + 1] as u32
+            | (ram[registers + 3] as u32) << 8
+            | (ram[registers + 5] as u32) << 16;
+        let length = (256 - (ram[registers + 4] & 0xFC) as u32) << 16;
+        let phase = (phase + frequency) % length;
+        ram[registers + 1] = phase as u8;
+        ram[registers + 3] = (phase >> 8) as u8;
+        ram[registers + 5] = (phase >> 16) as u8;
+
+        let position = ((phase >> 16) as usize + ram[registers + 6] as usize) & 0xFF;
+        let byte = ram[position / 2];
+        let sample = if position & 0x01 == 0 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        };
+        let volume = ram[registers + 7] & 0x0F;
+        self.outputs[channel] = (sample as i16 - 8) * volume as i16;
+    }
+
+    /// Output scaled relative to the 2A03 channels. The chip plays the
+    /// channels one after another, so more of them make each quieter.
+    pub fn level(&self, ram: &[u8]) -> f32 {
+        let active = Self::active_channels(ram);
+        let sum: i16 = self.outputs[CHANNEL_COUNT - active..].iter().sum();
+        sum as f32 / active as f32 * LEVEL_PER_STEP
+    }
+}
@@ -0,0 +1,60 @@
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x8000; // 32KB
+const CHR_BANK_SIZE: usize = 0x2000; // 8KB
+
+/// Mapper 0: no bank switching. 16KB of PRG-ROM is mirrored into both
+/// halves of $8000-$FFFF, and the mirroring is soldered on the board.
+pub struct NROM {
+    memory: CartridgeMemory,
+    mirroring: Mirroring,
+}
+
+impl NROM {
+    pub(super) fn new(memory: CartridgeMemory, mirroring: Mirroring) -> NROM {
+        NROM { memory, mirroring }
+    }
+}
+
+impl Mapper for NROM {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory
+                .read_prg_rom(0, PRG_BANK_SIZE, (addr - 0x8000) as usize)
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.memory
+                .read_prg_ram((addr - PRG_RAM_START_ADDR) as usize)
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if (PRG_RAM_START_ADDR..0x8000).contains(&addr) {
+            self.memory
+                .write_prg_ram((addr - PRG_RAM_START_ADDR) as usize, value);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory.read_chr(0, CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(0, CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
@@ -0,0 +1,82 @@
+use super::{CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x4000; // 16KB
+const CHR_BANK_SIZE: usize = 0x2000; // 8KB
+
+/// Mapper 2, UNROM and UOROM: a switchable 16KB PRG bank at $8000-$BFFF
+/// and the last bank fixed at $C000-$FFFF.
+pub struct UxROM {
+    memory: CartridgeMemory,
+    mirroring: Mirroring,
+    bus_conflicts: bool,
+    prg_bank: u8,
+}
+
+impl UxROM {
+    pub(super) fn new(memory: CartridgeMemory, mirroring: Mirroring, bus_conflicts: bool) -> UxROM {
+        UxROM {
+            memory,
+            mirroring,
+            bus_conflicts,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for UxROM {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0xC000 {
+            let last_bank = self.memory.prg_bank_count(PRG_BANK_SIZE) - 1;
+            self.memory
+                .read_prg_rom(last_bank, PRG_BANK_SIZE, (addr - 0xC000) as usize)
+        } else if addr >= 0x8000 {
+            self.memory.read_prg_rom(
+                self.prg_bank as usize,
+                PRG_BANK_SIZE,
+                (addr - 0x8000) as usize,
+            )
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.memory
+                .read_prg_ram((addr - PRG_RAM_START_ADDR) as usize)
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            // With bus conflicts the ROM drives the data bus too, and the
+            // lower of the two values wins.
+            self.prg_bank = if self.bus_conflicts {
+                value & self.cpu_read_byte(addr)
+            } else {
+                value
+            };
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.memory
+                .write_prg_ram((addr - PRG_RAM_START_ADDR) as usize, value);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory.read_chr(0, CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(0, CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
@@ -0,0 +1,288 @@
+use super::{vrc_irq::VRCIRQ, CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x2000; // 8KB
+const CHR_BANK_SIZE: usize = 0x0400; // 1KB
+
+const SUBMAPPER_VRC2: u8 = 3;
+const PRG_SWAP_MODE: u8 = 0x02;
+/// Boards without PRG-RAM have a one-bit latch at $6000-$6FFF, which some
+/// games use as a copy protection check.
+const MICROWIRE_LATCH_END_ADDR: BusAddr = 0x6FFF;
+
+/// Mappers 21, 22, 23 and 25: the Konami VRC2 and VRC4. Boards connect
+/// different CPU address lines to the chip's two register select inputs,
+/// which the NES 2.0 submapper tells apart. Without one, both candidate
+/// lines are decoded so that either wiring works.
+pub struct VRC4 {
+    memory: CartridgeMemory,
+    vrc2: bool,
+    /// VRC2a ignores the lowest bit of the CHR bank numbers.
+    chr_shift: u8,
+    /// Masks of the address lines wired to register select bits 0 and 1.
+    select_lines: (BusAddr, BusAddr),
+    prg_banks: [u8; 2],
+    prg_swap_mode: bool,
+    chr_banks: [u16; 8],
+    mirroring: Mirroring,
+    microwire_latch: u8,
+    irq: VRCIRQ,
+}
+
+impl VRC4 {
+    pub(super) fn new(memory: CartridgeMemory, mapper: u16, submapper: u8) -> VRC4 {
+        let select_lines = match (mapper, submapper) {
+            // VRC4a and VRC4c.
+            (21, 1) => (0x02, 0x04),
+            (21, 2) => (0x40, 0x80),
+            (21, _) => (0x42, 0x84),
+            // VRC2a.
+            (22, _) => (0x02, 0x01),
+            // VRC4f and VRC2b, VRC4e.
+            (23, 1 | 3) => (0x01, 0x02),
+            (23, 2) => (0x04, 0x08),
+            (23, _) => (0x05, 0x0A),
+            // VRC4b and VRC2c, VRC4d.
+            (25, 1 | 3) => (0x02, 0x01),
+            (25, 2) => (0x08, 0x04),
+            _ => (0x0A, 0x05),
+        };
+        VRC4 {
+            memory,
+            vrc2: mapper == 22 || submapper == SUBMAPPER_VRC2,
+            chr_shift: (mapper == 22) as u8,
+            select_lines,
+            prg_banks: [0; 2],
+            prg_swap_mode: false,
+            chr_banks: [0; 8],
+            mirroring: Mirroring::Vertical,
+            microwire_latch: 0,
+            irq: VRCIRQ::new(),
+        }
+    }
+
+    /// Reduces an address to its register: $x000-$x003.
+    fn register(&self, addr: BusAddr) -> BusAddr {
+        let (low, high) = self.select_lines;
+        (addr & 0xF000) | (addr & low != 0) as BusAddr | ((addr & high != 0) as BusAddr) << 1
+    }
+
+    fn prg_bank(&self, addr: BusAddr) -> usize {
+        let second_last = self.memory.prg_bank_count(PRG_BANK_SIZE).saturating_sub(2);
+        match (addr - 0x8000) / PRG_BANK_SIZE as BusAddr {
+            0 if self.prg_swap_mode => second_last,
+            0 => self.prg_banks[0] as usize,
+            1 => self.prg_banks[1] as usize,
+            2 if self.prg_swap_mode => self.prg_banks[0] as usize,
+            2 => second_last,
+            _ => second_last + 1,
+        }
+    }
+
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        (self.chr_banks[addr as usize / CHR_BANK_SIZE] >> self.chr_shift) as usize
+    }
+
+    fn write_register(&mut self, addr: BusAddr, value: u8) {
+        match self.register(addr) {
+            0x8000..=0x8003 => self.prg_banks[0] = value & 0x1F,
+            0x9000..=0x9003 if self.vrc2 => {
+                self.mirroring = if value & 0x01 != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            0x9000 | 0x9001 => {
+                self.mirroring = match value & 0x03 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::SingleScreenLower,
+                    _ => Mirroring::SingleScreenUpper,
+                };
+            }
+            0x9002 => self.prg_swap_mode = value & PRG_SWAP_MODE != 0,
+            0xA000..=0xA003 => self.prg_banks[1] = value & 0x1F,
+            register @ 0xB000..=0xE003 => {
+                // Two registers per bank, holding the low and high bits.
+                let index =
+                    ((register - 0xB000) >> 12) as usize * 2 + ((register >> 1) & 0x01) as usize;
+                let bank = &mut self.chr_banks[index];
+                if register & 0x01 == 0 {
+                    *bank = (*bank & !0x0F) | (value & 0x0F) as u16;
+                } else {
+                    let high_bits = if self.vrc2 { 0x0F } else { 0x1F };
+                    *bank = (*bank & 0x0F) | ((value & high_bits) as u16) << 4;
+                }
+            }
+            _ if self.vrc2 => {}
+            0xF000 => self.irq.write_latch_low(value),
+            0xF001 => self.irq.write_latch_high(value),
+            0xF002 => self.irq.write_control(value),
+            0xF003 => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn has_microwire_latch(&self, addr: BusAddr) -> bool {
+        self.vrc2 && self.memory.prg_ram.is_empty() && addr <= MICROWIRE_LATCH_END_ADDR
+    }
+}
+
+impl Mapper for VRC4 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory
+                .read_prg_rom(self.prg_bank(addr), PRG_BANK_SIZE, (addr & 0x1FFF) as usize)
+        } else if addr >= PRG_RAM_START_ADDR {
+            if self.has_microwire_latch(addr) {
+                self.microwire_latch
+            } else {
+                self.memory
+                    .read_prg_ram((addr - PRG_RAM_START_ADDR) as usize)
+            }
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.write_register(addr, value);
+        } else if addr >= PRG_RAM_START_ADDR {
+            if self.has_microwire_latch(addr) {
+                self.microwire_latch = value & 0x01;
+            } else {
+                self.memory
+                    .write_prg_ram((addr - PRG_RAM_START_ADDR) as usize, value);
+            }
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.irq()
+    }
+
+    fn tick(&mut self) {
+        self.irq.clock();
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::tests::{memory, nes2};
+
+    #[test]
+    fn decodes_register_select_lines() {
+        // Addresses landing on $B001 and $B002 for each wiring.
+        let wirings: [(u16, u8, &[BusAddr], &[BusAddr]); 10] = [
+            (21, 1, &[0xB002], &[0xB004]),
+            (21, 2, &[0xB040], &[0xB080]),
+            (21, 0, &[0xB002, 0xB040], &[0xB004, 0xB080]),
+            (22, 0, &[0xB002], &[0xB001]),
+            (23, 1, &[0xB001], &[0xB002]),
+            (23, 2, &[0xB004], &[0xB008]),
+            (23, 0, &[0xB001, 0xB004], &[0xB002, 0xB008]),
+            (25, 1, &[0xB002], &[0xB001]),
+            (25, 2, &[0xB008], &[0xB004]),
+            (25, 0, &[0xB002, 0xB008], &[0xB001, 0xB004]),
+        ];
+        for (mapper, submapper, first, second) in wirings {
+            let vrc4 = VRC4::new(memory(0x20000, 0x40000), mapper, submapper);
+            for addr in first {
+                assert_eq!(
+                    vrc4.register(*addr),
+                    0xB001,
+                    "{} {} {:04X}",
+                    mapper,
+                    submapper,
+                    addr
+                );
+            }
+            for addr in second {
+                assert_eq!(
+                    vrc4.register(*addr),
+                    0xB002,
+                    "{} {} {:04X}",
+                    mapper,
+                    submapper,
+                    addr
+                );
+            }
+            assert_eq!(vrc4.register(0xB000), 0xB000);
+        }
+    }
+
+    #[test]
+    fn chr_bank_writes_follow_the_wiring() {
+        // VRC4d on mapper 25 submapper 2: A3 and A2 select the register.
+        let mut vrc4 = VRC4::new(memory(0x20000, 0x40000), 25, 2);
+        vrc4.cpu_write_byte(0xB004, 0x07);
+        vrc4.cpu_write_byte(0xB00C, 0x01);
+        vrc4.cpu_write_byte(0xB008, 0x03);
+        assert_eq!(vrc4.ppu_read_byte(0x0400), 0x17);
+        assert_eq!(vrc4.ppu_read_byte(0x0000), 0x30);
+
+        // VRC2a drops the lowest bit of the bank number.
+        let mut vrc2 = VRC4::new(memory(0x20000, 0x20000), 22, 0);
+        vrc2.cpu_write_byte(0xB000, 0x07);
+        assert_eq!(vrc2.ppu_read_byte(0x0000), 0x03);
+    }
+
+    #[test]
+    fn prg_swap_mode() {
+        let mut vrc4 = VRC4::new(memory(0x20000, 0x2000), 21, 1);
+        vrc4.cpu_write_byte(0x8000, 0x03);
+        vrc4.cpu_write_byte(0xA000, 0x05);
+        let banks =
+            |vrc4: &mut VRC4| [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| vrc4.cpu_read_byte(addr));
+        assert_eq!(banks(&mut vrc4), [3, 5, 14, 15]);
+        vrc4.cpu_write_byte(0x9004, PRG_SWAP_MODE);
+        assert_eq!(banks(&mut vrc4), [14, 5, 3, 15]);
+    }
+
+    #[test]
+    fn reads_8kb_prg_rom() {
+        let memory = CartridgeMemory {
+            prg_rom: vec![0xEA; PRG_BANK_SIZE],
+            ..memory(0x4000, 0x2000)
+        };
+        let mut vrc4 = VRC4::new(memory, 21, 0);
+        for addr in [0x8000, 0xA000, 0xC000, 0xE000] {
+            assert_eq!(vrc4.cpu_read_byte(addr), 0xEA);
+        }
+    }
+
+    #[test]
+    fn vrc2_without_prg_ram_has_microwire_latch() {
+        let mut ines = nes2(23, 3, 0x20000, 0x20000);
+        ines.header.prg_ram_size = 0;
+        let mut vrc2 = VRC4::new(CartridgeMemory::new(&ines), 23, 3);
+        vrc2.cpu_write_byte(0x6000, 0xFF);
+        assert_eq!(vrc2.cpu_read_byte(0x6000), 0x01);
+        assert_eq!(vrc2.cpu_read_byte(0x7000), 0x00);
+    }
+}
@@ -0,0 +1,167 @@
+mod audio;
+
+use self::audio::VRC6Audio;
+use super::{vrc_irq::VRCIRQ, CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_16KB_BANK_SIZE: usize = 0x4000;
+const PRG_8KB_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400; // 1KB
+
+const BANKING_PRG_RAM_ENABLE: u8 = 0x80;
+
+/// Mappers 24 and 26: the Konami VRC6. The two boards swap the address
+/// lines wired to the register select inputs.
+pub struct VRC6 {
+    memory: CartridgeMemory,
+    swapped_lines: bool,
+    audio: VRC6Audio,
+    prg_16kb_bank: u8,
+    prg_8kb_bank: u8,
+    chr_banks: [u8; 8],
+    /// $B003: CHR layout, mirroring and PRG-RAM enable.
+    banking: u8,
+    irq: VRCIRQ,
+}
+
+impl VRC6 {
+    pub(super) fn new(memory: CartridgeMemory, swapped_lines: bool) -> VRC6 {
+        VRC6 {
+            memory,
+            swapped_lines,
+            audio: VRC6Audio::new(),
+            prg_16kb_bank: 0,
+            prg_8kb_bank: 0,
+            chr_banks: [0; 8],
+            banking: 0,
+            irq: VRCIRQ::new(),
+        }
+    }
+
+    /// Reduces an address to its register: $x000-$x003.
+    fn register(&self, addr: BusAddr) -> BusAddr {
+        let select = if self.swapped_lines {
+            (addr & 0x01) << 1 | (addr & 0x02) >> 1
+        } else {
+            addr & 0x03
+        };
+        (addr & 0xF000) | select
+    }
+
+    /// $B003 lays the pattern tables out as eight 1KB windows, four 2KB
+    /// windows, or four 1KB windows followed by two 2KB ones. In the 2KB
+    /// windows PPU A10 replaces the lowest bit of the register.
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        let slot = addr as usize / CHR_BANK_SIZE;
+        let (register, two_kb) = match (self.banking & 0x03, slot) {
+            (0, _) => (slot, false),
+            (1, _) => (slot / 2, true),
+            (_, 0..=3) => (slot, false),
+            (_, _) => (2 + slot / 2, true),
+        };
+        let bank = self.chr_banks[register] as usize;
+        if two_kb {
+            (bank & !0x01) | (slot & 0x01)
+        } else {
+            bank
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.banking & BANKING_PRG_RAM_ENABLE != 0
+    }
+
+    fn write_register(&mut self, addr: BusAddr, value: u8) {
+        match self.register(addr) {
+            0x8000..=0x8003 => self.prg_16kb_bank = value & 0x0F,
+            register @ (0x9000..=0x9003 | 0xA000..=0xA002 | 0xB000..=0xB002) => {
+                self.audio.write_register(register, value)
+            }
+            0xB003 => self.banking = value,
+            0xC000..=0xC003 => self.prg_8kb_bank = value & 0x1F,
+            register @ 0xD000..=0xE003 => {
+                let index = ((register - 0xD000) >> 12) as usize * 4 + (register & 0x03) as usize;
+                self.chr_banks[index] = value;
+            }
+            0xF000 => self.irq.write_latch(value),
+            0xF001 => self.irq.write_control(value),
+            0xF002 => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for VRC6 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        match addr {
+            0x8000..=0xBFFF => self.memory.read_prg_rom(
+                self.prg_16kb_bank as usize,
+                PRG_16KB_BANK_SIZE,
+                (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xDFFF => self.memory.read_prg_rom(
+                self.prg_8kb_bank as usize,
+                PRG_8KB_BANK_SIZE,
+                (addr & 0x1FFF) as usize,
+            ),
+            0xE000..=0xFFFF => self.memory.read_prg_rom(
+                self.memory.prg_bank_count(PRG_8KB_BANK_SIZE) - 1,
+                PRG_8KB_BANK_SIZE,
+                (addr & 0x1FFF) as usize,
+            ),
+            PRG_RAM_START_ADDR.. if self.prg_ram_enabled() => self
+                .memory
+                .read_prg_ram((addr - PRG_RAM_START_ADDR) as usize),
+            _ => 0,
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.write_register(addr, value);
+        } else if addr >= PRG_RAM_START_ADDR && self.prg_ram_enabled() {
+            self.memory
+                .write_prg_ram((addr - PRG_RAM_START_ADDR) as usize, value);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match (self.banking >> 2) & 0x03 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.irq()
+    }
+
+    fn tick(&mut self) {
+        self.irq.clock();
+        self.audio.clock();
+    }
+
+    fn audio_level(&self) -> f32 {
+        self.audio.level()
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
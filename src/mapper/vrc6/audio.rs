@@ -0,0 +1,210 @@
+use crate::bus::BusAddr;
+
+const CHANNEL_ENABLE: u8 = 0x80;
+const PULSE_IGNORE_DUTY: u8 = 0x80;
+const HALT: u8 = 0x01;
+const FREQUENCY_SHIFT_4: u8 = 0x02;
+const FREQUENCY_SHIFT_8: u8 = 0x04;
+const SAW_STEPS: u8 = 14;
+/// One step of the 6-bit output is about as loud as one volume step of a
+/// 2A03 pulse channel.
+const LEVEL_PER_STEP: f32 = 0.00996;
+
+/// A 12-bit period divider, clocked every CPU cycle.
+struct Timer {
+    period: u16,
+    counter: u16,
+    enabled: bool,
+}
+
+impl Timer {
+    fn new() -> Self {
+        Self {
+            period: 0,
+            counter: 0,
+            enabled: false,
+        }
+    }
+
+    fn write_period_low(&mut self, value: u8) {
+        self.period = (self.period & 0x0F00) | value as u16;
+    }
+
+    /// Also holds the enable bit.
+    fn write_period_high(&mut self, value: u8) {
+        self.period = (self.period & 0x00FF) | ((value & 0x0F) as u16) << 8;
+        self.enabled = value & CHANNEL_ENABLE != 0;
+    }
+
+    /// Returns whether the divider expired.
+    fn clock(&mut self, shift: u8) -> bool {
+        if self.counter == 0 {
+            self.counter = self.period >> shift;
+            true
+        } else {
+            self.counter -= 1;
+            false
+        }
+    }
+}
+
+struct Pulse {
+    timer: Timer,
+    volume: u8,
+    duty: u8,
+    ignore_duty: bool,
+    step: u8,
+}
+
+impl Pulse {
+    fn new() -> Self {
+        Self {
+            timer: Timer::new(),
+            volume: 0,
+            duty: 0,
+            ignore_duty: false,
+            step: 0,
+        }
+    }
+
+    fn write_register(&mut self, register: BusAddr, value: u8) {
+        match register {
+            0 => {
+                self.ignore_duty = value & PULSE_IGNORE_DUTY != 0;
+                self.duty = (value >> 4) & 0x07;
+                self.volume = value & 0x0F;
+            }
+            1 => self.timer.write_period_low(value),
+            _ => {
+                self.timer.write_period_high(value);
+                if !self.timer.enabled {
+                    self.step = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if self.timer.enabled && self.timer.clock(shift) {
+            self.step = self.step.wrapping_sub(1) & 0x0F;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.timer.enabled && (self.ignore_duty || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+/// Adds the rate to an accumulator on every other clock and resets it
+/// after seven additions. The top five bits are the output.
+struct Sawtooth {
+    timer: Timer,
+    rate: u8,
+    accumulator: u8,
+    step: u8,
+}
+
+impl Sawtooth {
+    fn new() -> Self {
+        Self {
+            timer: Timer::new(),
+            rate: 0,
+            accumulator: 0,
+            step: 0,
+        }
+    }
+
+    fn write_register(&mut self, register: BusAddr, value: u8) {
+        match register {
+            0 => self.rate = value & 0x3F,
+            1 => self.timer.write_period_low(value),
+            _ => {
+                self.timer.write_period_high(value);
+                if !self.timer.enabled {
+                    self.accumulator = 0;
+                    self.step = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.timer.enabled || !self.timer.clock(shift) {
+            return;
+        }
+        self.step += 1;
+        if self.step == SAW_STEPS {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 0x01 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.timer.enabled {
+            self.accumulator >> 3
+        } else {
+            0
+        }
+    }
+}
+
+/// The VRC6's sound hardware: two pulse channels with eight duty cycles
+/// and a sawtooth channel.
+pub struct VRC6Audio {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    sawtooth: Sawtooth,
+    control: u8,
+}
+
+impl VRC6Audio {
+    pub fn new() -> Self {
+        Self {
+            pulse1: Pulse::new(),
+            pulse2: Pulse::new(),
+            sawtooth: Sawtooth::new(),
+            control: 0,
+        }
+    }
+
+    /// Advances the channels by a single CPU cycle.
+    pub fn clock(&mut self) {
+        if self.control & HALT != 0 {
+            return;
+        }
+        let shift = if self.control & FREQUENCY_SHIFT_8 != 0 {
+            8
+        } else if self.control & FREQUENCY_SHIFT_4 != 0 {
+            4
+        } else {
+            0
+        };
+        self.pulse1.clock(shift);
+        self.pulse2.clock(shift);
+        self.sawtooth.clock(shift);
+    }
+
+    /// Output scaled relative to the 2A03 channels.
+    pub fn level(&self) -> f32 {
+        let sum = self.pulse1.output() + self.pulse2.output() + self.sawtooth.output();
+        sum as f32 * LEVEL_PER_STEP
+    }
+
+    /// Handles $9000-$9003, $A000-$A002 and $B000-$B002, with the address
+    /// lines already put in order.
+    pub fn write_register(&mut self, register: BusAddr, value: u8) {
+        match register {
+            0x9003 => self.control = value,
+            0x9000..=0x9002 => self.pulse1.write_register(register - 0x9000, value),
+            0xA000..=0xA002 => self.pulse2.write_register(register - 0xA000, value),
+            0xB000..=0xB002 => self.sawtooth.write_register(register - 0xB000, value),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,151 @@
+mod audio;
+
+use self::audio::VRC7Audio;
+use super::{vrc_irq::VRCIRQ, CartridgeMemory, Mapper, PRG_RAM_START_ADDR};
+use crate::{bus::BusAddr, ppu::Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x2000; // 8KB
+const CHR_BANK_SIZE: usize = 0x0400; // 1KB
+
+/// VRC7b boards select the second register of each pair with A3, VRC7a
+/// boards with A4.
+const SUBMAPPER_VRC7B: u8 = 1;
+const SUBMAPPER_VRC7A: u8 = 2;
+/// A5 tells the audio register select and data ports apart.
+const AUDIO_DATA_LINE: BusAddr = 0x20;
+
+const CONTROL_AUDIO_SILENCE: u8 = 0x40;
+const CONTROL_PRG_RAM_ENABLE: u8 = 0x80;
+
+/// Mapper 85: the Konami VRC7, with 8KB PRG banks, 1KB CHR banks, the VRC
+/// IRQ counter and an FM synthesizer.
+pub struct VRC7 {
+    memory: CartridgeMemory,
+    /// Mask of the address lines that select the second register of a
+    /// pair, both candidates when the submapper does not say.
+    select_lines: BusAddr,
+    audio: VRC7Audio,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    control: u8,
+    irq: VRCIRQ,
+}
+
+impl VRC7 {
+    pub(super) fn new(memory: CartridgeMemory, submapper: u8) -> VRC7 {
+        let select_lines = match submapper {
+            SUBMAPPER_VRC7B => 0x08,
+            SUBMAPPER_VRC7A => 0x10,
+            _ => 0x18,
+        };
+        VRC7 {
+            memory,
+            select_lines,
+            audio: VRC7Audio::new(),
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            control: 0,
+            irq: VRCIRQ::new(),
+        }
+    }
+
+    fn prg_bank(&self, addr: BusAddr) -> usize {
+        match (addr - 0x8000) as usize / PRG_BANK_SIZE {
+            3 => self.memory.prg_bank_count(PRG_BANK_SIZE) - 1,
+            slot => self.prg_banks[slot] as usize,
+        }
+    }
+
+    fn chr_bank(&self, addr: BusAddr) -> usize {
+        self.chr_banks[addr as usize / CHR_BANK_SIZE] as usize
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.control & CONTROL_PRG_RAM_ENABLE != 0
+    }
+
+    fn write_register(&mut self, addr: BusAddr, value: u8) {
+        let second = addr & self.select_lines != 0;
+        match (addr & 0xF000, second) {
+            (0x8000, false) => self.prg_banks[0] = value & 0x3F,
+            (0x8000, true) => self.prg_banks[1] = value & 0x3F,
+            (0x9000, false) => self.prg_banks[2] = value & 0x3F,
+            (0x9000, true) if addr & AUDIO_DATA_LINE != 0 => self.audio.write_register(value),
+            (0x9000, true) => self.audio.select_register(value),
+            (0xA000..=0xD000, _) => {
+                let index = ((addr - 0xA000) >> 12) as usize * 2 + second as usize;
+                self.chr_banks[index] = value;
+            }
+            (0xE000, false) => {
+                self.control = value;
+                self.audio.set_silenced(value & CONTROL_AUDIO_SILENCE != 0);
+            }
+            (0xE000, true) => self.irq.write_latch(value),
+            (_, false) => self.irq.write_control(value),
+            (_, true) => self.irq.acknowledge(),
+        }
+    }
+}
+
+impl Mapper for VRC7 {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= 0x8000 {
+            self.memory
+                .read_prg_rom(self.prg_bank(addr), PRG_BANK_SIZE, (addr & 0x1FFF) as usize)
+        } else if addr >= PRG_RAM_START_ADDR && self.prg_ram_enabled() {
+            self.memory
+                .read_prg_ram((addr - PRG_RAM_START_ADDR) as usize)
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if addr >= 0x8000 {
+            self.write_register(addr, value);
+        } else if addr >= PRG_RAM_START_ADDR && self.prg_ram_enabled() {
+            self.memory
+                .write_prg_ram((addr - PRG_RAM_START_ADDR) as usize, value);
+        }
+    }
+
+    fn ppu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        self.memory
+            .read_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize)
+    }
+
+    fn ppu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        self.memory
+            .write_chr(self.chr_bank(addr), CHR_BANK_SIZE, addr as usize, value);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.irq()
+    }
+
+    fn tick(&mut self) {
+        self.irq.clock();
+        self.audio.clock();
+    }
+
+    fn audio_level(&self) -> f32 {
+        self.audio.level()
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.memory.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.memory.load_save_data(data);
+    }
+}
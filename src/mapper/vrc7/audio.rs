@@ -0,0 +1,420 @@
+use std::f32::consts::PI;
+
+const CHANNELS: usize = 6;
+/// The synthesizer produces one sample every 36 CPU cycles, 49716Hz.
+const SAMPLE_PERIOD: u8 = 36;
+const SAMPLE_RATE: f32 = 49716.0;
+
+/// The built-in instruments 1-15, in the custom instrument's register
+/// layout. Instrument 0 is the custom one at registers $00-$07.
+const PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+/// Frequency multipliers, doubled so that the 1/2 of setting 0 stays an
+/// integer.
+const MULTIPLIERS_X2: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+/// Key scale attenuation in dB by the top four F-number bits, for octave 7.
+const KEY_SCALE_LEVELS: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25,
+    42.0,
+];
+/// Fraction of the key scale attenuation applied by each KSL setting: 0,
+/// 1.5, 3 and 6dB per octave.
+const KEY_SCALE_FACTORS: [f32; 4] = [0.0, 0.25, 0.5, 1.0];
+
+const MAX_ATTENUATION: f32 = 48.0; // dB
+const ENVELOPE_STEP: f32 = 0.375; // dB
+const SUSTAIN_LEVEL_STEP: f32 = 3.0; // dB
+const TOTAL_LEVEL_STEP: f32 = 0.75; // dB
+const VOLUME_STEP: f32 = 3.0; // dB
+/// Release rate used after key off while the channel's sustain bit is set,
+/// and for percussive instruments without it.
+const SUSTAIN_RELEASE_RATE: u8 = 5;
+const PERCUSSIVE_RELEASE_RATE: u8 = 7;
+
+const TREMOLO_FREQUENCY: f32 = 3.7; // Hz
+const TREMOLO_DEPTH: f32 = 4.8; // dB
+const VIBRATO_FREQUENCY: f32 = 6.4; // Hz
+/// About 14 cents either way.
+const VIBRATO_DEPTH: f32 = 0.008;
+
+/// Modulator feedback at setting 7, and modulation of the carrier at full
+/// modulator output, in radians.
+const MAX_FEEDBACK: f32 = 4.0 * PI;
+const MAX_MODULATION: f32 = 8.0 * PI;
+
+/// A channel at full volume swings about as far as a 2A03 pulse channel
+/// at full volume.
+const CHANNEL_FULL_LEVEL: f32 = 0.075;
+
+const OPERATOR_AM: u8 = 0x80;
+const OPERATOR_VIBRATO: u8 = 0x40;
+const OPERATOR_SUSTAINED: u8 = 0x20;
+const OPERATOR_KEY_SCALE_RATE: u8 = 0x10;
+const CARRIER_RECTIFIED: u8 = 0x10;
+const MODULATOR_RECTIFIED: u8 = 0x08;
+const CHANNEL_SUSTAIN: u8 = 0x20;
+const CHANNEL_KEY_ON: u8 = 0x10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// The settings one operator takes from an instrument.
+struct OperatorPatch {
+    flags: u8,
+    key_scale_level: u8,
+    attack_rate: u8,
+    decay_rate: u8,
+    sustain_level: u8,
+    release_rate: u8,
+    rectified: bool,
+}
+
+impl OperatorPatch {
+    /// `operator` is 0 for the modulator and 1 for the carrier.
+    fn new(patch: &[u8; 8], operator: usize) -> Self {
+        let rectified_bit = if operator == 0 {
+            MODULATOR_RECTIFIED
+        } else {
+            CARRIER_RECTIFIED
+        };
+        Self {
+            flags: patch[operator],
+            key_scale_level: patch[2 + operator] >> 6,
+            attack_rate: patch[4 + operator] >> 4,
+            decay_rate: patch[4 + operator] & 0x0F,
+            sustain_level: patch[6 + operator] >> 4,
+            release_rate: patch[6 + operator] & 0x0F,
+            rectified: patch[3] & rectified_bit != 0,
+        }
+    }
+}
+
+struct Operator {
+    /// Position in the waveform, in cycles.
+    phase: f32,
+    state: EnvelopeState,
+    /// Envelope attenuation in dB.
+    envelope: f32,
+    /// The last two outputs, fed back into the modulator.
+    outputs: [f32; 2],
+}
+
+impl Operator {
+    fn new() -> Self {
+        Self {
+            phase: 0.0,
+            state: EnvelopeState::Release,
+            envelope: MAX_ATTENUATION,
+            outputs: [0.0; 2],
+        }
+    }
+
+    fn key_on(&mut self) {
+        self.phase = 0.0;
+        self.state = EnvelopeState::Attack;
+    }
+
+    fn key_off(&mut self) {
+        self.state = EnvelopeState::Release;
+    }
+
+    /// Advances the envelope by one sample. `key_scale` is the rate
+    /// offset from the pitch, `sustain` the channel's sustain bit.
+    fn clock_envelope(&mut self, patch: &OperatorPatch, key_scale: u8, sustain: bool) {
+        let sustained = patch.flags & OPERATOR_SUSTAINED != 0;
+        let rate = match self.state {
+            EnvelopeState::Attack => patch.attack_rate,
+            EnvelopeState::Decay => patch.decay_rate,
+            EnvelopeState::Sustain if sustained => 0,
+            EnvelopeState::Sustain => patch.release_rate,
+            EnvelopeState::Release if sustain => SUSTAIN_RELEASE_RATE,
+            EnvelopeState::Release if sustained => patch.release_rate,
+            EnvelopeState::Release => PERCUSSIVE_RELEASE_RATE,
+        };
+        let key_scale = if patch.flags & OPERATOR_KEY_SCALE_RATE != 0 {
+            key_scale
+        } else {
+            key_scale >> 2
+        };
+
+        let speed = envelope_speed(rate, key_scale);
+        match self.state {
+            EnvelopeState::Attack => {
+                if rate == 15 {
+                    self.envelope = 0.0;
+                } else {
+                    // The attack curve is exponential, fast at first.
+                    self.envelope -= (self.envelope / 4.0 + ENVELOPE_STEP) * speed;
+                }
+                if self.envelope <= 0.0 {
+                    self.envelope = 0.0;
+                    self.state = EnvelopeState::Decay;
+                }
+            }
+            EnvelopeState::Decay => {
+                self.envelope += ENVELOPE_STEP * speed;
+                let sustain_level = patch.sustain_level as f32 * SUSTAIN_LEVEL_STEP;
+                if self.envelope >= sustain_level {
+                    self.envelope = sustain_level;
+                    self.state = EnvelopeState::Sustain;
+                }
+            }
+            EnvelopeState::Sustain | EnvelopeState::Release => {
+                self.envelope = (self.envelope + ENVELOPE_STEP * speed).min(MAX_ATTENUATION);
+            }
+        }
+    }
+
+    /// Advances the phase and returns the output for the given phase
+    /// modulation in radians and total attenuation in dB.
+    fn output(
+        &mut self,
+        increment: f32,
+        modulation: f32,
+        attenuation: f32,
+        rectified: bool,
+    ) -> f32 {
+        self.phase = (self.phase + increment).fract();
+        let mut wave = (self.phase * 2.0 * PI + modulation).sin();
+        if rectified && wave < 0.0 {
+            wave = 0.0;
+        }
+        let output = if attenuation >= MAX_ATTENUATION {
+            0.0
+        } else {
+            wave * 10f32.powf(-attenuation / 20.0)
+        };
+        self.outputs = [self.outputs[1], output];
+        output
+    }
+}
+
+/// Envelope steps per sample for a 4-bit rate, 0 for a rate of 0. Every
+/// four steps of the effective rate double the speed.
+fn envelope_speed(rate: u8, key_scale: u8) -> f32 {
+    if rate == 0 {
+        return 0.0;
+    }
+    let effective = (rate * 4 + key_scale).min(63);
+    2f32.powi(effective as i32 / 4 - 12) * (1.0 + (effective % 4) as f32 / 4.0)
+}
+
+struct Channel {
+    f_number: u16,
+    block: u8,
+    sustain: bool,
+    key_on: bool,
+    instrument: u8,
+    volume: u8,
+    modulator: Operator,
+    carrier: Operator,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            f_number: 0,
+            block: 0,
+            sustain: false,
+            key_on: false,
+            instrument: 0,
+            volume: 0,
+            modulator: Operator::new(),
+            carrier: Operator::new(),
+        }
+    }
+
+    fn set_key_on(&mut self, key_on: bool) {
+        if key_on && !self.key_on {
+            self.modulator.key_on();
+            self.carrier.key_on();
+        } else if !key_on && self.key_on {
+            self.modulator.key_off();
+            self.carrier.key_off();
+        }
+        self.key_on = key_on;
+    }
+
+    fn key_scale_attenuation(&self, patch: &OperatorPatch) -> f32 {
+        let level = KEY_SCALE_LEVELS[(self.f_number >> 5) as usize] - 6.0 * (7 - self.block) as f32;
+        level.max(0.0) * KEY_SCALE_FACTORS[patch.key_scale_level as usize]
+    }
+
+    /// Produces the next sample of the carrier, in -1.0..=1.0.
+    fn sample(&mut self, patch: &[u8; 8], tremolo: f32, vibrato: f32) -> f32 {
+        let modulator_patch = OperatorPatch::new(patch, 0);
+        let carrier_patch = OperatorPatch::new(patch, 1);
+        let key_scale = self.block << 1 | (self.f_number >> 8) as u8;
+        self.modulator
+            .clock_envelope(&modulator_patch, key_scale, self.sustain);
+        self.carrier
+            .clock_envelope(&carrier_patch, key_scale, self.sustain);
+
+        let increment = |flags: u8| {
+            let base = (self.f_number as u32) << self.block;
+            let increment =
+                (base * MULTIPLIERS_X2[(flags & 0x0F) as usize]) as f32 / (1 << 20) as f32;
+            if flags & OPERATOR_VIBRATO != 0 {
+                increment * (1.0 + vibrato * VIBRATO_DEPTH)
+            } else {
+                increment
+            }
+        };
+        let attenuation = |operator: &Operator, patch: &OperatorPatch, level: f32| {
+            let tremolo = if patch.flags & OPERATOR_AM != 0 {
+                tremolo * TREMOLO_DEPTH
+            } else {
+                0.0
+            };
+            operator.envelope + level + self.key_scale_attenuation(patch) + tremolo
+        };
+
+        let feedback = match patch[3] & 0x07 {
+            0 => 0.0,
+            amount => {
+                let [previous, last] = self.modulator.outputs;
+                (previous + last) / 2.0 * MAX_FEEDBACK / (1 << (7 - amount)) as f32
+            }
+        };
+        let total_level = (patch[2] & 0x3F) as f32 * TOTAL_LEVEL_STEP;
+        let modulator_increment = increment(modulator_patch.flags);
+        let modulator_attenuation = attenuation(&self.modulator, &modulator_patch, total_level);
+        let volume = self.volume as f32 * VOLUME_STEP;
+        let carrier_increment = increment(carrier_patch.flags);
+        let carrier_attenuation = attenuation(&self.carrier, &carrier_patch, volume);
+
+        let modulation = self.modulator.output(
+            modulator_increment,
+            feedback,
+            modulator_attenuation,
+            modulator_patch.rectified,
+        );
+        self.carrier.output(
+            carrier_increment,
+            modulation * MAX_MODULATION,
+            carrier_attenuation,
+            carrier_patch.rectified,
+        )
+    }
+}
+
+/// The VRC7's FM synthesizer, a cut-down YM2413 with six two-operator
+/// channels, fifteen built-in instruments and one custom instrument.
+pub struct VRC7Audio {
+    custom_patch: [u8; 8],
+    channels: [Channel; CHANNELS],
+    register: u8,
+    silenced: bool,
+    cycle: u8,
+    /// Samples produced since power on, for the LFOs.
+    samples: u64,
+    output: f32,
+}
+
+impl VRC7Audio {
+    pub fn new() -> Self {
+        Self {
+            custom_patch: [0; 8],
+            channels: std::array::from_fn(|_| Channel::new()),
+            register: 0,
+            silenced: false,
+            cycle: 0,
+            samples: 0,
+            output: 0.0,
+        }
+    }
+
+    /// Advances the synthesizer by a single CPU cycle.
+    pub fn clock(&mut self) {
+        self.cycle += 1;
+        if self.cycle < SAMPLE_PERIOD {
+            return;
+        }
+        self.cycle = 0;
+        if self.silenced {
+            self.output = 0.0;
+            return;
+        }
+
+        let time = self.samples as f32 / SAMPLE_RATE;
+        self.samples += 1;
+        let tremolo = (1.0 - (2.0 * PI * TREMOLO_FREQUENCY * time).cos()) / 2.0;
+        let vibrato = (2.0 * PI * VIBRATO_FREQUENCY * time).sin();
+        let custom_patch = self.custom_patch;
+        self.output = self
+            .channels
+            .iter_mut()
+            .map(|channel| {
+                let patch = match channel.instrument {
+                    0 => &custom_patch,
+                    instrument => &PATCHES[instrument as usize - 1],
+                };
+                channel.sample(patch, tremolo, vibrato)
+            })
+            .sum();
+    }
+
+    /// Output scaled relative to the 2A03 channels.
+    pub fn level(&self) -> f32 {
+        self.output * CHANNEL_FULL_LEVEL
+    }
+
+    /// Holding the synthesizer in reset silences it and clears all
+    /// channels.
+    pub fn set_silenced(&mut self, silenced: bool) {
+        if silenced && !self.silenced {
+            self.channels = std::array::from_fn(|_| Channel::new());
+        }
+        self.silenced = silenced;
+    }
+
+    pub fn select_register(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    pub fn write_register(&mut self, value: u8) {
+        let register = self.register;
+        let channel = (register & 0x0F) as usize;
+        match register {
+            0x00..=0x07 => self.custom_patch[register as usize] = value,
+            0x10..=0x15 => {
+                let channel = &mut self.channels[channel];
+                channel.f_number = (channel.f_number & 0x100) | value as u16;
+            }
+            0x20..=0x25 => {
+                let channel = &mut self.channels[channel];
+                channel.f_number = (channel.f_number & 0xFF) | ((value & 0x01) as u16) << 8;
+                channel.block = (value >> 1) & 0x07;
+                channel.sustain = value & CHANNEL_SUSTAIN != 0;
+                channel.set_key_on(value & CHANNEL_KEY_ON != 0);
+            }
+            0x30..=0x35 => {
+                let channel = &mut self.channels[channel];
+                channel.instrument = value >> 4;
+                channel.volume = value & 0x0F;
+            }
+            _ => {}
+        }
+    }
+}
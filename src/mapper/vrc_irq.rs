@@ -0,0 +1,164 @@
+/// The prescaler divides the CPU clock by 113.667, close to one scanline,
+/// by subtracting 3 from 341 every cycle.
+const PRESCALER_PERIOD: i16 = 341;
+const PRESCALER_STEP: i16 = 3;
+
+const CONTROL_ENABLE_AFTER_ACK: u8 = 0x01;
+const CONTROL_ENABLE: u8 = 0x02;
+const CONTROL_CYCLE_MODE: u8 = 0x04;
+
+/// The IRQ counter shared by the Konami VRC4, VRC6 and VRC7. It counts up
+/// from the latch on CPU cycles, or on approximate scanlines through a
+/// prescaler, and raises the IRQ when it overflows. It does not watch the
+/// PPU at all.
+pub struct VRCIRQ {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    enable_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VRCIRQ {
+    pub fn new() -> Self {
+        Self {
+            latch: 0,
+            counter: 0,
+            prescaler: PRESCALER_PERIOD,
+            enabled: false,
+            enable_after_ack: false,
+            cycle_mode: false,
+            pending: false,
+        }
+    }
+
+    pub fn write_latch(&mut self, value: u8) {
+        self.latch = value;
+    }
+
+    /// The VRC4 takes the latch a nibble at a time.
+    pub fn write_latch_low(&mut self, value: u8) {
+        self.latch = (self.latch & 0xF0) | (value & 0x0F);
+    }
+
+    pub fn write_latch_high(&mut self, value: u8) {
+        self.latch = (self.latch & 0x0F) | (value << 4);
+    }
+
+    pub fn write_control(&mut self, value: u8) {
+        self.pending = false;
+        self.enable_after_ack = value & CONTROL_ENABLE_AFTER_ACK != 0;
+        self.enabled = value & CONTROL_ENABLE != 0;
+        self.cycle_mode = value & CONTROL_CYCLE_MODE != 0;
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = PRESCALER_PERIOD;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enable_after_ack;
+    }
+
+    /// Advances the counter by a single CPU cycle.
+    pub fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        if self.cycle_mode {
+            self.clock_counter();
+            return;
+        }
+        self.prescaler -= PRESCALER_STEP;
+        if self.prescaler <= 0 {
+            self.prescaler += PRESCALER_PERIOD;
+            self.clock_counter();
+        }
+    }
+
+    pub fn irq(&self) -> bool {
+        self.pending
+    }
+
+    fn clock_counter(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(latch: u8, control: u8) -> VRCIRQ {
+        let mut irq = VRCIRQ::new();
+        irq.write_latch(latch);
+        irq.write_control(control);
+        irq
+    }
+
+    fn clock(irq: &mut VRCIRQ, cycles: usize) {
+        for _ in 0..cycles {
+            irq.clock();
+        }
+    }
+
+    #[test]
+    fn cycle_mode_counts_cpu_cycles() {
+        let mut irq = enabled(0xFD, CONTROL_ENABLE | CONTROL_CYCLE_MODE);
+        clock(&mut irq, 2);
+        assert!(!irq.irq());
+        clock(&mut irq, 1);
+        assert!(irq.irq());
+        assert_eq!(irq.counter, 0xFD);
+    }
+
+    #[test]
+    fn scanline_mode_counts_through_prescaler() {
+        // Three scanlines of 341 / 3 CPU cycles each.
+        let mut irq = enabled(0xFD, CONTROL_ENABLE);
+        clock(&mut irq, 113);
+        assert_eq!(irq.counter, 0xFD);
+        clock(&mut irq, 1);
+        assert_eq!(irq.counter, 0xFE);
+        clock(&mut irq, 340 - 114);
+        assert!(!irq.irq());
+        clock(&mut irq, 1);
+        assert!(irq.irq());
+    }
+
+    #[test]
+    fn acknowledge_restores_enable_after_ack() {
+        let mut irq = enabled(
+            0xFF,
+            CONTROL_ENABLE | CONTROL_ENABLE_AFTER_ACK | CONTROL_CYCLE_MODE,
+        );
+        clock(&mut irq, 1);
+        assert!(irq.irq());
+        irq.acknowledge();
+        assert!(!irq.irq());
+        clock(&mut irq, 1);
+        assert!(irq.irq());
+
+        let mut irq = enabled(0xFF, CONTROL_ENABLE | CONTROL_CYCLE_MODE);
+        clock(&mut irq, 1);
+        irq.acknowledge();
+        clock(&mut irq, 1);
+        assert!(!irq.irq());
+    }
+
+    #[test]
+    fn nibble_latch_writes() {
+        let mut irq = VRCIRQ::new();
+        irq.write_latch_low(0x1A);
+        irq.write_latch_high(0x2B);
+        assert_eq!(irq.latch, 0xBA);
+    }
+}
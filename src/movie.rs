@@ -0,0 +1,431 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    hash,
+    ines::{iNES, Timing},
+    pad::{Multitap, Pad, Player},
+};
+
+const FM2_VERSION: u32 = 3;
+/// Button characters of an FM2 gamepad field, from bit 7 (Right) down to
+/// bit 0 (A), which matches `Button::mask`.
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+const FM2_PORT_NONE: u8 = 0;
+const FM2_PORT_GAMEPAD: u8 = 1;
+const BASE64_PREFIX: &str = "base64:";
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Input for a single frame of a movie.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MovieFrame {
+    /// Bit field of `SOFT_RESET` and `HARD_RESET`, applied before the frame
+    /// runs.
+    pub commands: u8,
+    /// Held buttons of players 1-4, in `Button::mask` layout.
+    pub buttons: [u8; 4],
+}
+
+impl MovieFrame {
+    pub const SOFT_RESET: u8 = 0x01;
+    pub const HARD_RESET: u8 = 0x02;
+
+    /// Takes a snapshot of the buttons currently held on `pad`.
+    pub fn capture(pad: &Pad) -> Self {
+        Self {
+            commands: 0,
+            buttons: [
+                pad.buttons(Player::One),
+                pad.buttons(Player::Two),
+                pad.buttons(Player::Three),
+                pad.buttons(Player::Four),
+            ],
+        }
+    }
+
+    /// Holds this frame's buttons on `pad`.
+    pub fn apply(&self, pad: &mut Pad) {
+        for (player, buttons) in [Player::One, Player::Two, Player::Three, Player::Four]
+            .into_iter()
+            .zip(self.buttons)
+        {
+            pad.set_buttons(player, buttons);
+        }
+    }
+
+    pub fn is_soft_reset(&self) -> bool {
+        self.commands & Self::SOFT_RESET != 0
+    }
+}
+
+/// Per-frame controller input from power on, stored as an FCEUX .fm2 movie.
+#[derive(Debug, Clone)]
+pub struct Movie {
+    pub rom_filename: String,
+    /// MD5 of the PRG-ROM followed by the CHR-ROM, see `rom_checksum`.
+    pub rom_checksum: [u8; 16],
+    pub guid: String,
+    pub pal: bool,
+    pub fourscore: bool,
+    pub rerecord_count: u32,
+    pub comments: Vec<String>,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    /// An empty movie for recording on `ines`, flagged as PAL if its
+    /// header says so.
+    pub fn new(rom_filename: &str, ines: &iNES) -> Self {
+        let rom_checksum = rom_checksum(ines);
+        Self {
+            rom_filename: rom_filename.to_string(),
+            rom_checksum,
+            guid: new_guid(&rom_checksum),
+            pal: ines.header.timing == Timing::PAL,
+            fourscore: false,
+            rerecord_count: 0,
+            comments: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Appends the input `pad` is holding as the next frame, along with
+    /// the `MovieFrame` commands issued before it.
+    pub fn record_frame(&mut self, pad: &Pad, commands: u8) {
+        self.fourscore |= pad.multitap() == Some(Multitap::FourScore);
+        self.frames.push(MovieFrame {
+            commands,
+            ..MovieFrame::capture(pad)
+        });
+    }
+
+    /// Fails unless the movie was recorded on the same ROM as `ines`.
+    pub fn verify_rom(&self, ines: &iNES) -> Result<(), String> {
+        let actual = rom_checksum(ines);
+        if actual != self.rom_checksum {
+            return Err(format!(
+                "ROM checksum mismatch: movie was recorded on {}{} ({}), but this ROM is {}{}",
+                BASE64_PREFIX,
+                base64_encode(&self.rom_checksum),
+                self.rom_filename,
+                BASE64_PREFIX,
+                base64_encode(&actual)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, String> {
+        let mut movie = Movie {
+            rom_filename: String::new(),
+            rom_checksum: [0; 16],
+            guid: String::new(),
+            pal: false,
+            fourscore: false,
+            rerecord_count: 0,
+            comments: Vec::new(),
+            frames: Vec::new(),
+        };
+        let mut version = None;
+        let mut rom_checksum = None;
+        let mut ports = [FM2_PORT_GAMEPAD, FM2_PORT_GAMEPAD];
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('|') {
+                let frame = parse_frame(line, movie.fourscore, ports)
+                    .map_err(|message| format!("Line {}: {}", line_number + 1, message))?;
+                movie.frames.push(frame);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" => version = Some(parse_number::<u32>(key, value)?),
+                "rerecordCount" => movie.rerecord_count = parse_number(key, value)?,
+                "palFlag" => movie.pal = parse_number::<u8>(key, value)? != 0,
+                "romFilename" => movie.rom_filename = value.to_string(),
+                "romChecksum" => rom_checksum = Some(parse_checksum(value)?),
+                "guid" => movie.guid = value.to_string(),
+                "fourscore" => movie.fourscore = parse_number::<u8>(key, value)? != 0,
+                "port0" => ports[0] = parse_number(key, value)?,
+                "port1" => ports[1] = parse_number(key, value)?,
+                "comment" => movie.comments.push(value.to_string()),
+                _ => { /* Not needed for playback */ }
+            }
+        }
+
+        match version {
+            Some(FM2_VERSION) => {}
+            Some(version) => return Err(format!("Unsupported FM2 version: {}", version)),
+            None => return Err("FM2 movie must start with a version line".to_string()),
+        }
+        if !movie.fourscore {
+            for port in ports {
+                if port != FM2_PORT_NONE && port != FM2_PORT_GAMEPAD {
+                    return Err(format!("Unsupported FM2 port device: {}", port));
+                }
+            }
+        }
+        movie.rom_checksum = rom_checksum.ok_or("FM2 movie has no romChecksum")?;
+
+        Ok(movie)
+    }
+
+    pub fn to_fm2(&self) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("version {}\n", FM2_VERSION));
+        text.push_str("emuVersion 22020\n");
+        text.push_str(&format!("rerecordCount {}\n", self.rerecord_count));
+        text.push_str(&format!("palFlag {}\n", self.pal as u8));
+        text.push_str(&format!("romFilename {}\n", self.rom_filename));
+        text.push_str(&format!(
+            "romChecksum {}{}\n",
+            BASE64_PREFIX,
+            base64_encode(&self.rom_checksum)
+        ));
+        text.push_str(&format!("guid {}\n", self.guid));
+        text.push_str(&format!("fourscore {}\n", self.fourscore as u8));
+        text.push_str("microphone 0\n");
+        let port = if self.fourscore {
+            FM2_PORT_NONE
+        } else {
+            FM2_PORT_GAMEPAD
+        };
+        text.push_str(&format!("port0 {}\nport1 {}\nport2 0\n", port, port));
+        text.push_str("FDS 0\nNewPPU 0\n");
+        for comment in self.comments.iter() {
+            text.push_str(&format!("comment {}\n", comment));
+        }
+
+        let players = if self.fourscore { 4 } else { 2 };
+        for frame in self.frames.iter() {
+            text.push_str(&format!("|{}|", frame.commands));
+            for buttons in frame.buttons[..players].iter() {
+                text.push_str(&format_buttons(*buttons));
+                text.push('|');
+            }
+            if !self.fourscore {
+                text.push('|');
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn load(path: &str) -> Result<Movie, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Movie::parse(&text).map_err(|message| format!("{}: {}", path, message))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_fm2()).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// The checksum FCEUX stores in movies: MD5 over the PRG-ROM and then the
+/// CHR-ROM, without the header.
+pub fn rom_checksum(ines: &iNES) -> [u8; 16] {
+    let mut data = ines.programROM.data.clone();
+    data.extend_from_slice(ines.characterROM.as_bytes());
+    hash::md5(&data)
+}
+
+fn parse_frame(line: &str, fourscore: bool, ports: [u8; 2]) -> Result<MovieFrame, String> {
+    let fields: Vec<&str> = line.split('|').collect();
+    // A leading and a trailing empty field surround the real ones.
+    let players = if fourscore { 4 } else { 2 };
+    if fields.len() < players + 2 {
+        return Err(format!("Malformed input line: {}", line));
+    }
+
+    let mut frame = MovieFrame {
+        commands: fields[1]
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid command field: {}", fields[1]))?,
+        ..Default::default()
+    };
+    // Power cycling the console mid-movie is not emulated, and treating it
+    // as a soft reset would desync.
+    if frame.commands & MovieFrame::HARD_RESET != 0 {
+        return Err("Hard reset commands are not supported".to_string());
+    }
+    for player in 0..players {
+        let field = fields[player + 2];
+        if !fourscore && ports[player] == FM2_PORT_NONE {
+            continue;
+        }
+        frame.buttons[player] = parse_buttons(field)?;
+    }
+    Ok(frame)
+}
+
+fn parse_buttons(field: &str) -> Result<u8, String> {
+    if field.len() != FM2_BUTTONS.len() {
+        return Err(format!("Invalid gamepad field: {}", field));
+    }
+    // Anything other than a space or '.' counts as pressed.
+    Ok(field
+        .bytes()
+        .enumerate()
+        .filter(|(_, c)| *c != b'.' && *c != b' ')
+        .fold(0, |buttons, (index, _)| buttons | 0x80 >> index))
+}
+
+fn format_buttons(buttons: u8) -> String {
+    FM2_BUTTONS
+        .iter()
+        .enumerate()
+        .map(|(index, c)| {
+            if buttons & (0x80 >> index) != 0 {
+                *c as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", key, value))
+}
+
+fn parse_checksum(value: &str) -> Result<[u8; 16], String> {
+    let encoded = value
+        .strip_prefix(BASE64_PREFIX)
+        .ok_or_else(|| format!("Unsupported romChecksum: {}", value))?;
+    let bytes = base64_decode(encoded.trim())?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("romChecksum must be 16 bytes: {}", value))
+}
+
+fn new_guid(seed: &[u8; 16]) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    let mut data = seed.to_vec();
+    data.extend_from_slice(&nanos.to_le_bytes());
+    let hex: String = hash::md5(&data)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bits = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(BASE64_ALPHABET[(bits >> (18 - index * 6)) as usize & 0x3F] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for c in text.bytes().take_while(|c| *c != b'=') {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|a| *a == c)
+            .ok_or_else(|| format!("Invalid base64: {}", text))?;
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            data.push((bits >> bit_count) as u8);
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie(fourscore: bool) -> Movie {
+        Movie {
+            rom_filename: "game".to_string(),
+            rom_checksum: *b"0123456789abcdef",
+            guid: "01234567-89AB-CDEF-0123-456789ABCDEF".to_string(),
+            pal: true,
+            fourscore,
+            rerecord_count: 7,
+            comments: vec!["author me".to_string()],
+            frames: vec![
+                MovieFrame {
+                    commands: 0,
+                    buttons: [0x81, 0x42, 0, 0],
+                },
+                MovieFrame {
+                    commands: MovieFrame::SOFT_RESET,
+                    buttons: [0x00, 0xFF, 0, 0],
+                },
+            ],
+        }
+    }
+
+    fn assert_round_trip(movie: &Movie) {
+        let parsed = Movie::parse(&movie.to_fm2()).unwrap();
+        assert_eq!(parsed.rom_filename, movie.rom_filename);
+        assert_eq!(parsed.rom_checksum, movie.rom_checksum);
+        assert_eq!(parsed.guid, movie.guid);
+        assert_eq!(parsed.pal, movie.pal);
+        assert_eq!(parsed.fourscore, movie.fourscore);
+        assert_eq!(parsed.rerecord_count, movie.rerecord_count);
+        assert_eq!(parsed.comments, movie.comments);
+        assert_eq!(parsed.frames, movie.frames);
+    }
+
+    #[test]
+    fn fm2_round_trips() {
+        assert_round_trip(&movie(false));
+    }
+
+    #[test]
+    fn fourscore_fm2_round_trips() {
+        let mut movie = movie(true);
+        movie.frames[0].buttons = [0x01, 0x02, 0x04, 0x80];
+        assert_round_trip(&movie);
+    }
+
+    #[test]
+    fn writes_buttons_in_fm2_order() {
+        let fm2 = movie(false).to_fm2();
+        assert!(fm2.contains("\n|0|R......A|.L....B.||\n"), "{}", fm2);
+        assert!(fm2.contains("palFlag 1\n"));
+    }
+
+    #[test]
+    fn rejects_hard_reset() {
+        let mut movie = movie(false);
+        movie.frames[1].commands = MovieFrame::HARD_RESET;
+        let error = Movie::parse(&movie.to_fm2()).unwrap_err();
+        assert!(error.contains("Hard reset"), "{}", error);
+    }
+}
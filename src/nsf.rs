@@ -0,0 +1,149 @@
+mod memory;
+mod nsfe;
+mod player;
+
+pub use memory::NSFMemory;
+pub use player::NSFPlayer;
+
+use crate::region::Region;
+
+const HEADER_BYTES: usize = 0x80;
+const NSF_HEADER_START: [u8; 5] = [0x4E, 0x45, 0x53, 0x4D, 0x1A]; // "NESM" followed by MS-DOS EOF
+const NSFE_HEADER_START: [u8; 4] = [0x4E, 0x53, 0x46, 0x45]; // "NSFE"
+
+pub const DEFAULT_NTSC_PLAY_SPEED: u16 = 16639; // ~60.1 Hz
+pub const DEFAULT_PAL_PLAY_SPEED: u16 = 19997; // ~50.0 Hz
+
+/// A parsed .nsf or .nsfe music file.
+#[derive(Debug, Clone)]
+pub struct NSF {
+    pub total_songs: u8,
+    /// 1-based, like the track numbers accepted by `NSFPlayer::init`.
+    pub starting_song: u8,
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub song_name: String,
+    pub artist: String,
+    pub copyright: String,
+    /// Period between PLAY calls in microseconds.
+    pub ntsc_play_speed: u16,
+    pub pal_play_speed: u16,
+    /// Initial values for $5FF8-$5FFF. All zero when the tune is not
+    /// bankswitched.
+    pub bankswitch_init: [u8; 8],
+    pub pal: bool,
+    pub dual_region: bool,
+    /// Bit field of expansion sound chips (VRC6, VRC7, FDS, MMC5, N163, 5B).
+    pub expansion_chips: u8,
+    pub data: Vec<u8>,
+}
+
+impl NSF {
+    pub fn parse(data: &[u8]) -> Result<NSF, String> {
+        if data.starts_with(&NSFE_HEADER_START) {
+            return nsfe::parse(data);
+        }
+
+        if data.len() < HEADER_BYTES {
+            return Err(format!(
+                "NSF header must be {} bytes long, but was {} bytes long",
+                HEADER_BYTES,
+                data.len()
+            ));
+        }
+        if !data.starts_with(&NSF_HEADER_START) {
+            return Err("NSF header must start with 'NESM'".to_string());
+        }
+
+        let program_length = u32::from_le_bytes([data[0x7D], data[0x7E], data[0x7F], 0]) as usize;
+        let data_end = if program_length == 0 {
+            data.len()
+        } else {
+            (HEADER_BYTES + program_length).min(data.len())
+        };
+
+        let mut bankswitch_init = [0; 8];
+        bankswitch_init.copy_from_slice(&data[0x70..0x78]);
+
+        let nsf = NSF {
+            total_songs: data[0x06],
+            starting_song: data[0x07],
+            load_address: read_word(data, 0x08),
+            init_address: read_word(data, 0x0A),
+            play_address: read_word(data, 0x0C),
+            song_name: read_string(&data[0x0E..0x2E]),
+            artist: read_string(&data[0x2E..0x4E]),
+            copyright: read_string(&data[0x4E..0x6E]),
+            ntsc_play_speed: read_word(data, 0x6E),
+            pal_play_speed: read_word(data, 0x78),
+            bankswitch_init,
+            pal: data[0x7A] & 0x01 != 0,
+            dual_region: data[0x7A] & 0x02 != 0,
+            expansion_chips: data[0x7B],
+            data: data[HEADER_BYTES..data_end].to_vec(),
+        };
+        nsf.validate()?;
+
+        Ok(nsf)
+    }
+
+    pub fn is_bankswitched(&self) -> bool {
+        self.bankswitch_init.iter().any(|bank| *bank != 0)
+    }
+
+    /// The region the tune was written for. Dual-region tunes prefer NTSC.
+    pub fn preferred_region(&self) -> Region {
+        if self.pal && !self.dual_region {
+            Region::PAL
+        } else {
+            Region::NTSC
+        }
+    }
+
+    /// Period between PLAY calls in microseconds, falling back to the
+    /// standard vertical blank rate when the header leaves it unset.
+    pub fn play_speed(&self, region: Region) -> u16 {
+        let (speed, default) = match region {
+            Region::NTSC => (self.ntsc_play_speed, DEFAULT_NTSC_PLAY_SPEED),
+            Region::PAL => (self.pal_play_speed, DEFAULT_PAL_PLAY_SPEED),
+        };
+        if speed == 0 {
+            default
+        } else {
+            speed
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.total_songs == 0 {
+            return Err("NSF must contain at least one song".to_string());
+        }
+        if self.starting_song == 0 || self.starting_song > self.total_songs {
+            return Err(format!(
+                "NSF starting song {} is out of range 1-{}",
+                self.starting_song, self.total_songs
+            ));
+        }
+        if self.load_address < 0x8000 {
+            return Err(format!(
+                "NSF load address 0x{:04X} below 0x8000 is not supported",
+                self.load_address
+            ));
+        }
+        if self.data.is_empty() {
+            return Err("NSF does not contain any program data".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+fn read_word(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
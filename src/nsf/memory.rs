@@ -0,0 +1,98 @@
+use super::NSF;
+use crate::{bus::BusAddr, mapper::Mapper, ppu::Mirroring};
+
+const BANK_SIZE: usize = 0x1000; // 4KB
+const BANK_COUNT: usize = 8;
+const BANK_REGISTERS_START_ADDR: BusAddr = 0x5FF8;
+const PRG_RAM_START_ADDR: BusAddr = 0x6000;
+const PRG_RAM_SIZE: usize = 0x2000;
+const PROGRAM_START_ADDR: BusAddr = 0x8000;
+
+/// The cartridge side of an NSF player: 8KB of RAM at $6000-$7FFF and the
+/// program data at $8000-$FFFF, mapped in 4KB banks selected by writes to
+/// $5FF8-$5FFF when the tune is bankswitched.
+pub struct NSFMemory {
+    image: Vec<u8>,
+    bankswitched: bool,
+    bank_registers: [u8; BANK_COUNT],
+    initial_bank_registers: [u8; BANK_COUNT],
+    prg_ram: Vec<u8>,
+}
+
+impl NSFMemory {
+    pub fn new(nsf: &NSF) -> NSFMemory {
+        let bankswitched = nsf.is_bankswitched();
+        let (image, initial_bank_registers) = if bankswitched {
+            // Bankswitched data is padded so that the load address keeps its
+            // offset within the first bank.
+            let padding = (nsf.load_address as usize) & (BANK_SIZE - 1);
+            let mut image = vec![0; padding];
+            image.extend_from_slice(&nsf.data);
+            let banks = image.len().div_ceil(BANK_SIZE);
+            image.resize(banks * BANK_SIZE, 0);
+            (image, nsf.bankswitch_init)
+        } else {
+            let mut image = vec![0; BANK_SIZE * BANK_COUNT];
+            let start = (nsf.load_address - PROGRAM_START_ADDR) as usize;
+            let end = (start + nsf.data.len()).min(image.len());
+            image[start..end].copy_from_slice(&nsf.data[..end - start]);
+            (image, [0, 1, 2, 3, 4, 5, 6, 7])
+        };
+
+        NSFMemory {
+            image,
+            bankswitched,
+            bank_registers: initial_bank_registers,
+            initial_bank_registers,
+            prg_ram: vec![0; PRG_RAM_SIZE],
+        }
+    }
+
+    /// Clears PRG-RAM and restores the initial banks, as done before INIT.
+    pub fn reset(&mut self) {
+        self.prg_ram.iter_mut().for_each(|byte| *byte = 0);
+        self.bank_registers = self.initial_bank_registers;
+    }
+
+    fn read_program(&self, addr: BusAddr) -> u8 {
+        let offset = (addr - PROGRAM_START_ADDR) as usize;
+        let bank = self.bank_registers[offset / BANK_SIZE] as usize;
+        self.image
+            .get(bank * BANK_SIZE + offset % BANK_SIZE)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// NSF players draw nothing, so the PPU side is left unconnected.
+impl Mapper for NSFMemory {
+    fn cpu_read_byte(&mut self, addr: BusAddr) -> u8 {
+        if addr >= PROGRAM_START_ADDR {
+            self.read_program(addr)
+        } else if addr >= PRG_RAM_START_ADDR {
+            self.prg_ram[(addr - PRG_RAM_START_ADDR) as usize]
+        } else {
+            0
+        }
+    }
+
+    fn cpu_write_byte(&mut self, addr: BusAddr, value: u8) {
+        if (BANK_REGISTERS_START_ADDR..PRG_RAM_START_ADDR).contains(&addr) {
+            if self.bankswitched {
+                self.bank_registers[(addr - BANK_REGISTERS_START_ADDR) as usize] = value;
+            }
+        } else if (PRG_RAM_START_ADDR..PROGRAM_START_ADDR).contains(&addr) {
+            self.prg_ram[(addr - PRG_RAM_START_ADDR) as usize] = value;
+        }
+    }
+
+    fn ppu_read_byte(&mut self, _addr: BusAddr) -> u8 {
+        0
+    }
+
+    fn ppu_write_byte(&mut self, _addr: BusAddr, _value: u8) {}
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+}
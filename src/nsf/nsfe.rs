@@ -0,0 +1,112 @@
+use super::{read_string, read_word, NSF, NSFE_HEADER_START};
+
+const CHUNK_HEADER_BYTES: usize = 8;
+
+/// Parses the chunk based NSFe container. INFO, DATA and NEND are required;
+/// unknown chunks are skipped unless their ID starts with an upper case
+/// letter, which marks them as required for correct playback.
+pub fn parse(data: &[u8]) -> Result<NSF, String> {
+    let mut info: Option<&[u8]> = None;
+    let mut program: Option<&[u8]> = None;
+    let mut bank: Option<&[u8]> = None;
+    let mut rate: Option<&[u8]> = None;
+    let mut auth: Option<&[u8]> = None;
+    let mut found_end = false;
+
+    let mut offset = NSFE_HEADER_START.len();
+    while offset < data.len() {
+        if offset + CHUNK_HEADER_BYTES > data.len() {
+            return Err(format!("NSFe chunk header at 0x{:X} is truncated", offset));
+        }
+        let length = u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as usize;
+        let id = &data[offset + 4..offset + CHUNK_HEADER_BYTES];
+        let body_start = offset + CHUNK_HEADER_BYTES;
+        let body_end = body_start
+            .checked_add(length)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| {
+                format!(
+                    "NSFe chunk '{}' at 0x{:X} is truncated",
+                    String::from_utf8_lossy(id),
+                    offset
+                )
+            })?;
+        let body = &data[body_start..body_end];
+
+        match id {
+            b"INFO" => info = Some(body),
+            b"DATA" => program = Some(body),
+            b"BANK" => bank = Some(body),
+            b"RATE" => rate = Some(body),
+            b"auth" => auth = Some(body),
+            b"NEND" => {
+                found_end = true;
+                break;
+            }
+            _ if id[0].is_ascii_uppercase() => {
+                return Err(format!(
+                    "NSFe requires unsupported chunk '{}'",
+                    String::from_utf8_lossy(id)
+                ));
+            }
+            _ => { /* Optional metadata we don't use */ }
+        }
+        offset = body_end;
+    }
+
+    if !found_end {
+        return Err("NSFe is missing the NEND chunk".to_string());
+    }
+    let info = info.ok_or("NSFe is missing the INFO chunk")?;
+    let program = program.ok_or("NSFe is missing the DATA chunk")?;
+    if info.len() < 8 {
+        return Err(format!(
+            "NSFe INFO chunk must be at least 8 bytes long, but was {} bytes long",
+            info.len()
+        ));
+    }
+
+    let mut bankswitch_init = [0; 8];
+    if let Some(bank) = bank {
+        let len = bank.len().min(8);
+        bankswitch_init[..len].copy_from_slice(&bank[..len]);
+    }
+
+    let (ntsc_play_speed, pal_play_speed) = match rate {
+        Some(rate) if rate.len() >= 4 => (read_word(rate, 0), read_word(rate, 2)),
+        Some(rate) if rate.len() >= 2 => (read_word(rate, 0), 0),
+        _ => (0, 0),
+    };
+
+    let mut strings = auth
+        .map(|auth| auth.split(|b| *b == 0).map(read_string).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter();
+
+    let nsf = NSF {
+        total_songs: info.get(8).copied().unwrap_or(1),
+        // NSFe stores the starting song 0-based.
+        starting_song: info.get(9).copied().unwrap_or(0).wrapping_add(1),
+        load_address: read_word(info, 0),
+        init_address: read_word(info, 2),
+        play_address: read_word(info, 4),
+        song_name: strings.next().unwrap_or_default(),
+        artist: strings.next().unwrap_or_default(),
+        copyright: strings.next().unwrap_or_default(),
+        ntsc_play_speed,
+        pal_play_speed,
+        bankswitch_init,
+        pal: info[6] & 0x01 != 0,
+        dual_region: info[6] & 0x02 != 0,
+        expansion_chips: info[7],
+        data: program.to_vec(),
+    };
+    nsf.validate()?;
+
+    Ok(nsf)
+}
@@ -0,0 +1,109 @@
+use super::NSF;
+use crate::{bus::ByteWritable, cpu::CPU, region::Region};
+
+/// INIT and PLAY are called as if from this address. Nothing is mapped
+/// there, so reaching it can only mean the routine has returned.
+const RETURN_TRAP_ADDR: u16 = 0x4100;
+const MICROSECONDS_PER_SECOND: u64 = 1_000_000;
+
+/// Drives a CPU whose bus was built with `Bus::new_nsf`, calling the tune's
+/// INIT routine once and then PLAY at the rate given in the header.
+pub struct NSFPlayer {
+    init_address: u16,
+    play_address: u16,
+    total_songs: u8,
+    region: Region,
+    /// Time between PLAY calls, in CPU cycles scaled by 10^6.
+    play_period: u64,
+    next_play_at: u64,
+    in_play: bool,
+}
+
+impl NSFPlayer {
+    pub fn new(nsf: &NSF, region: Region) -> Self {
+        Self {
+            init_address: nsf.init_address,
+            play_address: nsf.play_address,
+            total_songs: nsf.total_songs,
+            region,
+            play_period: nsf.play_speed(region) as u64 * region.cpu_clock_rate() as u64,
+            next_play_at: 0,
+            in_play: false,
+        }
+    }
+
+    /// Resets memory and the APU and runs INIT for `track` (1-based).
+    /// INIT must return within `max_cycles` CPU cycles.
+    pub fn init(&mut self, cpu: &mut CPU, track: u8, max_cycles: u64) -> Result<(), String> {
+        if track == 0 || track > self.total_songs {
+            return Err(format!(
+                "Track {} is out of range 1-{}",
+                track, self.total_songs
+            ));
+        }
+
+        let bus = cpu.bus_mut();
+        for addr in 0x0000..0x0800 {
+            bus.write_byte(addr, 0);
+        }
+        if let Some(nsf_memory) = bus.nsf_memory_mut() {
+            nsf_memory.reset();
+        }
+        for addr in 0x4000..0x4014 {
+            bus.write_byte(addr, 0);
+        }
+        bus.write_byte(0x4015, 0x00);
+        bus.write_byte(0x4015, 0x0F);
+        bus.write_byte(0x4017, 0x40);
+
+        let x = match self.region {
+            Region::NTSC => 0,
+            Region::PAL => 1,
+        };
+        cpu.call_subroutine(self.init_address, RETURN_TRAP_ADDR, track - 1, x);
+        let deadline = cpu.cycles() + max_cycles;
+        while cpu.pc() != RETURN_TRAP_ADDR {
+            if cpu.cycles() >= deadline {
+                return Err(format!("INIT did not return within {} cycles", max_cycles));
+            }
+            cpu.run_single_cycle()?;
+        }
+
+        self.next_play_at = cpu.cycles() * MICROSECONDS_PER_SECOND;
+        self.in_play = false;
+
+        Ok(())
+    }
+
+    /// Plays for `cycles` CPU cycles. A PLAY call that is still running when
+    /// the next one is due delays it rather than being re-entered.
+    pub fn run(&mut self, cpu: &mut CPU, cycles: u64) -> Result<(), String> {
+        let end = cpu.cycles() + cycles;
+        while cpu.cycles() < end {
+            if self.in_play {
+                cpu.run_single_cycle()?;
+                if cpu.pc() == RETURN_TRAP_ADDR {
+                    self.in_play = false;
+                }
+                continue;
+            }
+
+            let now = cpu.cycles() * MICROSECONDS_PER_SECOND;
+            if now >= self.next_play_at {
+                cpu.call_subroutine(self.play_address, RETURN_TRAP_ADDR, 0, 0);
+                self.in_play = true;
+                self.next_play_at += self.play_period;
+            } else {
+                let until_play = (self.next_play_at - now).div_ceil(MICROSECONDS_PER_SECOND);
+                let idle = until_play.min(end - cpu.cycles());
+                cpu.idle(idle as usize);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+}
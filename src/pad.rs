@@ -1,21 +1,328 @@
-use crate::bus::{BusAddr, ByteReadable, ByteWritable};
+mod family_keyboard;
+mod peripheral;
+mod power_pad;
+mod vaus;
+mod zapper;
 
-pub struct Pad;
+pub use self::{
+    family_keyboard::FamilyBasicKeyboard,
+    peripheral::{Peripheral, Port},
+    power_pad::PowerPad,
+    vaus::Vaus,
+    zapper::Zapper,
+};
+use crate::{
+    bus::{BusAddr, ByteReadable, ByteWritable},
+    ppu::PPU,
+};
+
+/// Bits 5-7 of $4016/$4017 are not driven by the controller port, so they
+/// keep the high byte of the address last put on the bus.
+const OPEN_BUS_BITS: u8 = 0x40;
+
+const PLAYERS: usize = 4;
+const BUTTONS_PER_CONTROLLER: u8 = 8;
+
+/// Four Score ID bytes, sent after the two controllers of each port. Like
+/// the buttons they are shifted out LSB first, so port 1 reads 0,0,0,1 and
+/// port 2 reads 0,0,1,0 before the remaining zeros.
+const FOUR_SCORE_SIGNATURES: [u8; 2] = [0x08, 0x04];
+/// The Hori adapter sends the Four Score IDs swapped between the ports.
+const HORI_SIGNATURES: [u8; 2] = [0x04, 0x08];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Player {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+/// Standard controller buttons, in the order they are shifted out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Button {
+    pub fn mask(&self) -> u8 {
+        1 << (*self as u8)
+    }
+}
+
+/// Four-player adapters. Without one, players 3 and 4 are not connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multitap {
+    /// NES Four Score: each port sends 8 bits for its first player, 8 bits
+    /// for its second player, then an 8-bit signature, all on D0.
+    FourScore,
+    /// Famicom Hori 4 Players Adapter in 4P mode: players 1 and 2 stay on
+    /// D0, while players 3 and 4 are sent on D1 followed by a signature.
+    Hori,
+}
+
+/// A serial data line of a controller port. Bits are shifted out LSB first,
+/// and `fill` is returned once all of them have been read.
+#[derive(Debug, Clone, Copy)]
+struct SerialLine {
+    bits: u32,
+    remaining: u8,
+    fill: u8,
+}
+
+impl SerialLine {
+    fn new() -> Self {
+        Self {
+            bits: 0,
+            remaining: 0,
+            fill: 0,
+        }
+    }
+
+    fn load(&mut self, bits: u32, length: u8, fill: u8) {
+        self.bits = bits;
+        self.remaining = length;
+        self.fill = fill;
+    }
+
+    fn read_bit(&mut self) -> u8 {
+        if self.remaining == 0 {
+            return self.fill;
+        }
+        let bit = (self.bits & 0x01) as u8;
+        self.bits >>= 1;
+        self.remaining -= 1;
+        bit
+    }
+}
+
+/// The two controller ports behind $4016/$4017, plus an optional
+/// four-player adapter. A controller port without a connected peripheral
+/// holds a standard controller.
+pub struct Pad {
+    buttons: [u8; PLAYERS],
+    multitap: Option<Multitap>,
+    /// D0 and D1 of each port.
+    lines: [[SerialLine; 2]; 2],
+    peripherals: [Option<Box<dyn Peripheral>>; 3],
+    strobe: bool,
+}
 
 impl Pad {
     pub fn new() -> Self {
-        Self
+        Self {
+            buttons: [0; PLAYERS],
+            multitap: None,
+            lines: [[SerialLine::new(); 2]; 2],
+            peripherals: [None, None, None],
+            strobe: false,
+        }
+    }
+
+    /// Replaces the held buttons of `player`. Bit 0 is A and bit 7 is Right,
+    /// matching `Button::mask`.
+    pub fn set_buttons(&mut self, player: Player, buttons: u8) {
+        self.buttons[player as usize] = buttons;
+    }
+
+    pub fn buttons(&self, player: Player) -> u8 {
+        self.buttons[player as usize]
+    }
+
+    pub fn set_button(&mut self, player: Player, button: Button, pressed: bool) {
+        let buttons = &mut self.buttons[player as usize];
+        if pressed {
+            *buttons |= button.mask();
+        } else {
+            *buttons &= !button.mask();
+        }
+    }
+
+    /// Plugs in (or removes, with `None`) a four-player adapter. Takes
+    /// effect at the next strobe.
+    pub fn set_multitap(&mut self, multitap: Option<Multitap>) {
+        self.multitap = multitap;
+    }
+
+    pub fn multitap(&self) -> Option<Multitap> {
+        self.multitap
+    }
+
+    /// Plugs `peripheral` into `port`, replacing whatever was there.
+    pub fn connect(&mut self, port: Port, peripheral: Box<dyn Peripheral>) {
+        self.peripherals[port as usize] = Some(peripheral);
+    }
+
+    /// Unplugs the peripheral on `port`. Controller ports fall back to a
+    /// standard controller.
+    pub fn disconnect(&mut self, port: Port) -> Option<Box<dyn Peripheral>> {
+        self.peripherals[port as usize].take()
+    }
+
+    /// The peripheral on `port`, if there is one of type `T`.
+    pub fn peripheral_mut<T: Peripheral>(&mut self, port: Port) -> Option<&mut T> {
+        self.peripherals[port as usize]
+            .as_mut()
+            .and_then(|peripheral| peripheral.as_any_mut().downcast_mut::<T>())
+    }
+
+    /// Plugs a Zapper into port 2 in place of the second controller.
+    pub fn connect_zapper(&mut self) {
+        if self.peripheral_mut::<Zapper>(Port::Two).is_none() {
+            self.connect(Port::Two, Box::new(Zapper::new()));
+        }
+    }
+
+    /// Points the Zapper at screen pixel (x, y), or `None` to aim off
+    /// screen. Does nothing unless a Zapper is connected.
+    pub fn set_zapper_aim(&mut self, aim: Option<(u8, u8)>) {
+        if let Some(zapper) = self.peripheral_mut::<Zapper>(Port::Two) {
+            zapper.set_aim(aim);
+        }
+    }
+
+    pub fn set_zapper_trigger(&mut self, pulled: bool) {
+        if let Some(zapper) = self.peripheral_mut::<Zapper>(Port::Two) {
+            zapper.set_trigger(pulled);
+        }
+    }
+
+    /// Lets connected peripherals look at the picture. Called by the bus
+    /// before every read of $4016/$4017.
+    pub fn sense_light(&mut self, ppu: &PPU) {
+        for peripheral in self.peripherals.iter_mut().flatten() {
+            peripheral.sense_light(ppu);
+        }
+    }
+
+    fn latch(&mut self) {
+        for port in 0..self.lines.len() {
+            let first = self.buttons[port] as u32;
+            let second = self.buttons[port + 2] as u32;
+            let [d0, d1] = &mut self.lines[port];
+            match self.multitap {
+                None => {
+                    // Official controllers shift in 1s, so every read past
+                    // the eighth returns 1.
+                    d0.load(first, BUTTONS_PER_CONTROLLER, 1);
+                    d1.load(0, 0, 0);
+                }
+                Some(Multitap::FourScore) => {
+                    let signature = FOUR_SCORE_SIGNATURES[port] as u32;
+                    d0.load(
+                        first | second << 8 | signature << 16,
+                        BUTTONS_PER_CONTROLLER * 3,
+                        1,
+                    );
+                    d1.load(0, 0, 0);
+                }
+                Some(Multitap::Hori) => {
+                    let signature = HORI_SIGNATURES[port] as u32;
+                    d0.load(first, BUTTONS_PER_CONTROLLER, 1);
+                    d1.load(second | signature << 8, BUTTONS_PER_CONTROLLER * 2, 1);
+                }
+            }
+        }
     }
 }
 
 impl ByteReadable for Pad {
-    fn read_byte(&self, _addr: BusAddr) -> u8 {
-        todo!()
+    fn read_byte(&mut self, addr: BusAddr) -> u8 {
+        let port = match addr {
+            0x4016 => Port::One,
+            0x4017 => Port::Two,
+            _ => return 0,
+        };
+
+        let mut value = OPEN_BUS_BITS;
+        match self.peripherals[port as usize].as_mut() {
+            Some(peripheral) => value |= peripheral.read(addr),
+            None => {
+                // While the strobe is high the ports keep reloading, so
+                // every read returns the first bit.
+                if self.strobe {
+                    self.latch();
+                }
+                let [d0, d1] = &mut self.lines[port as usize];
+                value |= d0.read_bit() | d1.read_bit() << 1;
+            }
+        }
+        if let Some(expansion) = self.peripherals[Port::Expansion as usize].as_mut() {
+            value |= expansion.read(addr);
+        }
+        value
     }
 }
 
 impl ByteWritable for Pad {
-    fn write_byte(&mut self, _addr: BusAddr, _value: u8) {
-        todo!()
+    fn write_byte(&mut self, _addr: BusAddr, value: u8) {
+        // The shift registers keep reloading while the strobe is high, so
+        // they hold the button state from the moment it falls.
+        let was_strobing = self.strobe;
+        self.strobe = value & 0x01 != 0;
+        if self.strobe || was_strobing {
+            self.latch();
+        }
+        for peripheral in self.peripherals.iter_mut().flatten() {
+            peripheral.write(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Strobes the ports and reads `count` bits from `line` of each.
+    fn read_bits(pad: &mut Pad, line: u8, count: usize) -> [Vec<u8>; 2] {
+        pad.write_byte(0x4016, 1);
+        pad.write_byte(0x4016, 0);
+        [0x4016, 0x4017].map(|addr| {
+            (0..count)
+                .map(|_| (pad.read_byte(addr) >> line) & 0x01)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn four_score_sends_signatures_after_the_controllers() {
+        let mut pad = Pad::new();
+        pad.set_multitap(Some(Multitap::FourScore));
+        let [port1, port2] = read_bits(&mut pad, 0, 24);
+        assert_eq!(port1[..16], [0; 16]);
+        assert_eq!(port1[16..], [0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(port2[..16], [0; 16]);
+        assert_eq!(port2[16..], [0, 0, 1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn four_score_sends_each_ports_controllers_in_order() {
+        let mut pad = Pad::new();
+        pad.set_multitap(Some(Multitap::FourScore));
+        pad.set_buttons(Player::One, Button::A.mask());
+        pad.set_buttons(Player::Two, Button::B.mask());
+        pad.set_buttons(Player::Three, Button::Right.mask());
+        pad.set_buttons(Player::Four, Button::Start.mask());
+        let [port1, port2] = read_bits(&mut pad, 0, 16);
+        assert_eq!(port1, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(port2, [0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn hori_sends_swapped_signatures_on_d1() {
+        let mut pad = Pad::new();
+        pad.set_multitap(Some(Multitap::Hori));
+        let [port1, port2] = read_bits(&mut pad, 1, 16);
+        assert_eq!(port1[..8], [0; 8]);
+        assert_eq!(port1[8..], [0, 0, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(port2[..8], [0; 8]);
+        assert_eq!(port2[8..], [0, 0, 0, 1, 0, 0, 0, 0]);
     }
 }
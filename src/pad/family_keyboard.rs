@@ -0,0 +1,79 @@
+use std::any::Any;
+
+use super::peripheral::Peripheral;
+use crate::bus::BusAddr;
+
+pub const KEYBOARD_ROWS: usize = 9;
+pub const KEYBOARD_COLUMNS: usize = 2;
+pub const KEYS_PER_COLUMN: usize = 4;
+
+const RESET: u8 = 0x01;
+const COLUMN_SELECT: u8 = 0x02;
+const ENABLE: u8 = 0x04;
+
+/// Family BASIC keyboard on the Famicom expansion port. The program picks a
+/// row and column of the key matrix through $4016 writes and reads four keys
+/// at a time from bits 1-4 of $4017, where 0 means pressed.
+#[derive(Debug)]
+pub struct FamilyBasicKeyboard {
+    keys: [[u8; KEYBOARD_COLUMNS]; KEYBOARD_ROWS],
+    row: usize,
+    column: usize,
+    enabled: bool,
+}
+
+impl FamilyBasicKeyboard {
+    pub fn new() -> Self {
+        Self {
+            keys: [[0; KEYBOARD_COLUMNS]; KEYBOARD_ROWS],
+            row: 0,
+            column: 0,
+            enabled: false,
+        }
+    }
+
+    /// Presses or releases the key at `row` (0-8), `column` (0-1) and
+    /// `key` (0-3, reported on bit `key + 1`) of the matrix.
+    pub fn set_key(&mut self, row: usize, column: usize, key: usize, pressed: bool) {
+        assert!(key < KEYS_PER_COLUMN, "Invalid keyboard key {}", key);
+        let keys = &mut self.keys[row][column];
+        if pressed {
+            *keys |= 1 << key;
+        } else {
+            *keys &= !(1 << key);
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        self.keys = [[0; KEYBOARD_COLUMNS]; KEYBOARD_ROWS];
+    }
+}
+
+impl Peripheral for FamilyBasicKeyboard {
+    fn write(&mut self, value: u8) {
+        self.enabled = value & ENABLE != 0;
+        let column = ((value & COLUMN_SELECT) >> 1) as usize;
+        if value & RESET != 0 {
+            self.row = 0;
+        } else if self.column == 1 && column == 0 {
+            // Switching back from column 1 to column 0 selects the next row.
+            self.row += 1;
+        }
+        self.column = column;
+    }
+
+    fn read(&mut self, addr: BusAddr) -> u8 {
+        if addr != 0x4017 || !self.enabled {
+            return 0;
+        }
+        let keys = match self.keys.get(self.row) {
+            Some(row) => row[self.column],
+            None => 0,
+        };
+        (!keys & 0x0F) << 1
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,29 @@
+use std::any::Any;
+
+use crate::{bus::BusAddr, ppu::PPU};
+
+/// Where a peripheral is plugged in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    /// Controller port 1, read through $4016.
+    One,
+    /// Controller port 2, read through $4017.
+    Two,
+    /// The Famicom expansion port, which sees reads of both registers.
+    Expansion,
+}
+
+/// A device behind $4016/$4017 other than a standard controller.
+pub trait Peripheral: Any {
+    /// Receives every write to $4016. Bits 0-2 are the OUT0-OUT2 lines.
+    fn write(&mut self, value: u8);
+
+    /// Returns the data lines (bits 0-4) the device drives for a read of
+    /// `addr`. Undriven lines must be 0.
+    fn read(&mut self, addr: BusAddr) -> u8;
+
+    /// Called before every read so light guns can look at the picture.
+    fn sense_light(&mut self, _ppu: &PPU) {}
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
@@ -0,0 +1,102 @@
+use std::any::Any;
+
+use super::peripheral::Peripheral;
+use crate::bus::BusAddr;
+
+const D3: u8 = 0x08;
+const D4: u8 = 0x10;
+
+/// Order in which buttons (numbered 1-12 as printed on side B) are shifted
+/// out on D4 and D3.
+const D4_BUTTONS: [u8; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+const D3_BUTTONS: [u8; 4] = [4, 3, 12, 8];
+
+/// Power Pad / Family Trainer mat with 12 buttons.
+#[derive(Debug)]
+pub struct PowerPad {
+    buttons: u16,
+    d3_shift_register: u8,
+    d4_shift_register: u8,
+    strobe: bool,
+}
+
+impl PowerPad {
+    pub fn new() -> Self {
+        Self {
+            buttons: 0,
+            d3_shift_register: 0,
+            d4_shift_register: 0,
+            strobe: false,
+        }
+    }
+
+    /// Presses or releases button `number` (1-12).
+    pub fn set_button(&mut self, number: u8, pressed: bool) {
+        let mask = button_mask(number);
+        if pressed {
+            self.buttons |= mask;
+        } else {
+            self.buttons &= !mask;
+        }
+    }
+
+    /// Replaces all buttons at once. Bit 0 is button 1 and bit 11 is
+    /// button 12.
+    pub fn set_buttons(&mut self, buttons: u16) {
+        self.buttons = buttons & 0x0FFF;
+    }
+
+    fn latch(&mut self) {
+        self.d4_shift_register = self.collect(&D4_BUTTONS);
+        // Only four buttons are wired to D3; the rest of the byte reads
+        // back as 1s.
+        self.d3_shift_register = self.collect(&D3_BUTTONS) | 0xF0;
+    }
+
+    fn collect(&self, order: &[u8]) -> u8 {
+        order
+            .iter()
+            .enumerate()
+            .filter(|(_, number)| self.buttons & button_mask(**number) != 0)
+            .fold(0, |bits, (index, _)| bits | 1 << index)
+    }
+}
+
+fn button_mask(number: u8) -> u16 {
+    assert!(
+        (1..=12).contains(&number),
+        "Invalid Power Pad button {}",
+        number
+    );
+    1 << (number - 1)
+}
+
+impl Peripheral for PowerPad {
+    fn write(&mut self, value: u8) {
+        let was_strobing = self.strobe;
+        self.strobe = value & 0x01 != 0;
+        if self.strobe || was_strobing {
+            self.latch();
+        }
+    }
+
+    fn read(&mut self, _addr: BusAddr) -> u8 {
+        if self.strobe {
+            self.latch();
+        }
+        let mut value = 0;
+        if self.d3_shift_register & 0x01 != 0 {
+            value |= D3;
+        }
+        if self.d4_shift_register & 0x01 != 0 {
+            value |= D4;
+        }
+        self.d3_shift_register = (self.d3_shift_register >> 1) | 0x80;
+        self.d4_shift_register = (self.d4_shift_register >> 1) | 0x80;
+        value
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
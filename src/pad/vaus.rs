@@ -0,0 +1,92 @@
+use std::any::Any;
+
+use super::peripheral::Peripheral;
+use crate::bus::BusAddr;
+
+const POSITION_BIT: u8 = 0x10;
+const FIRE_BUTTON: u8 = 0x08;
+
+/// Arkanoid "Vaus" paddle for the NES port. The potentiometer value is
+/// latched on strobe and shifted out MSB first, inverted, on D4. The fire
+/// button is reported on D3.
+#[derive(Debug)]
+pub struct Vaus {
+    position: u8,
+    fire: bool,
+    shift_register: u8,
+}
+
+impl Vaus {
+    pub fn new() -> Self {
+        Self {
+            position: 0,
+            fire: false,
+            shift_register: 0,
+        }
+    }
+
+    /// Sets the raw potentiometer reading. Arkanoid expects roughly $62
+    /// (far left) to $F2 (far right).
+    pub fn set_position(&mut self, position: u8) {
+        self.position = position;
+    }
+
+    pub fn set_fire(&mut self, pressed: bool) {
+        self.fire = pressed;
+    }
+}
+
+impl Peripheral for Vaus {
+    fn write(&mut self, value: u8) {
+        if value & 0x01 != 0 {
+            self.shift_register = !self.position;
+        }
+    }
+
+    fn read(&mut self, _addr: BusAddr) -> u8 {
+        let mut value = 0;
+        if self.shift_register & 0x80 != 0 {
+            value |= POSITION_BIT;
+        }
+        self.shift_register <<= 1;
+        if self.fire {
+            value |= FIRE_BUTTON;
+        }
+        value
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_out_inverted_position_on_d4_and_fire_on_d3() {
+        let mut vaus = Vaus::new();
+        vaus.set_position(0xA5);
+        vaus.set_fire(true);
+        vaus.write(0x01);
+        vaus.write(0x00);
+
+        let mut position = 0;
+        for _ in 0..8 {
+            let value = vaus.read(0x4017);
+            assert_eq!(value & FIRE_BUTTON, 0x08);
+            position = position << 1 | (value & POSITION_BIT != 0) as u8;
+        }
+        assert_eq!(position, !0xA5);
+    }
+
+    #[test]
+    fn fire_released_reads_zero_on_d3() {
+        let mut vaus = Vaus::new();
+        vaus.set_position(0xFF);
+        vaus.write(0x01);
+        vaus.write(0x00);
+        assert_eq!(vaus.read(0x4017), 0x00);
+    }
+}
@@ -0,0 +1,100 @@
+use std::any::Any;
+
+use super::peripheral::Peripheral;
+use crate::{
+    bus::BusAddr,
+    ppu::{luminance, PPU, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+const LIGHT_NOT_DETECTED: u8 = 0x08;
+const TRIGGER_PULLED: u8 = 0x10;
+
+/// How many scanlines the photodiode keeps reporting light after the beam
+/// has passed the aimed pixel.
+const LIGHT_SENSE_SCANLINES: u16 = 20;
+/// The sensor sees a small area around the aim point, not a single pixel.
+const SENSE_RADIUS: i32 = 2;
+const BRIGHTNESS_THRESHOLD: f32 = 0.5;
+
+/// Zapper light gun. Unlike controllers it has no shift register; both bits
+/// are reported on every read of the port.
+#[derive(Debug)]
+pub struct Zapper {
+    aim: Option<(u8, u8)>,
+    trigger: bool,
+    light_detected: bool,
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Self {
+            aim: None,
+            trigger: false,
+            light_detected: false,
+        }
+    }
+
+    /// Points the gun at screen pixel (x, y), or away from the screen.
+    pub fn set_aim(&mut self, aim: Option<(u8, u8)>) {
+        self.aim = aim;
+    }
+
+    pub fn set_trigger(&mut self, pulled: bool) {
+        self.trigger = pulled;
+    }
+}
+
+impl Peripheral for Zapper {
+    fn write(&mut self, _value: u8) {}
+
+    fn read(&mut self, _addr: BusAddr) -> u8 {
+        let mut bits = 0;
+        if !self.light_detected {
+            bits |= LIGHT_NOT_DETECTED;
+        }
+        if self.trigger {
+            bits |= TRIGGER_PULLED;
+        }
+        bits
+    }
+
+    /// Samples the photodiode against what the PPU has drawn so far this
+    /// frame.
+    fn sense_light(&mut self, ppu: &PPU) {
+        self.light_detected = match self.aim {
+            Some((x, y)) => is_lit(ppu, x as i32, y as i32),
+            None => false,
+        };
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn is_lit(ppu: &PPU, x: i32, y: i32) -> bool {
+    if y >= SCREEN_HEIGHT as i32 {
+        return false;
+    }
+    // The beam must have drawn the aimed pixel recently, during this frame.
+    let scanline = ppu.scanline() as i32;
+    let beam_passed = scanline > y || (scanline == y && ppu.dot() as i32 > x + 1);
+    if !beam_passed || scanline - y > LIGHT_SENSE_SCANLINES as i32 {
+        return false;
+    }
+
+    for sense_y in (y - SENSE_RADIUS)..=(y + SENSE_RADIUS) {
+        for sense_x in (x - SENSE_RADIUS)..=(x + SENSE_RADIUS) {
+            if !(0..SCREEN_WIDTH as i32).contains(&sense_x)
+                || !(0..SCREEN_HEIGHT as i32).contains(&sense_y)
+            {
+                continue;
+            }
+            let color = ppu.pixel(sense_x as usize, sense_y as usize);
+            if luminance(color) >= BRIGHTNESS_THRESHOLD {
+                return true;
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,504 @@
+use crate::hash;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const IPS_MAX_RECORD_BYTES: usize = 0xFFFF;
+/// A record at this offset would read as the end marker.
+const IPS_EOF_OFFSET: usize = 0x454F46;
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// UPS and BPS patches end with the CRC-32s of the source, the target and
+/// the patch itself.
+const FOOTER_BYTES: usize = 12;
+/// Largest image a UPS or BPS patch may produce, far above any cartridge,
+/// so that a corrupt size does not allocate gigabytes.
+const MAX_TARGET_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    IPS,
+    UPS,
+    BPS,
+}
+
+/// A ROM patch, applied to the raw file image before it is parsed.
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub format: PatchFormat,
+    data: Vec<u8>,
+}
+
+impl Patch {
+    pub fn load(path: &str) -> Result<Patch, String> {
+        let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Patch::parse(data).map_err(|message| format!("{}: {}", path, message))
+    }
+
+    /// Detects the format from the magic number. The records themselves are
+    /// only checked when the patch is applied.
+    pub fn parse(data: Vec<u8>) -> Result<Patch, String> {
+        let format = if data.starts_with(IPS_MAGIC) {
+            PatchFormat::IPS
+        } else if data.starts_with(UPS_MAGIC) {
+            PatchFormat::UPS
+        } else if data.starts_with(BPS_MAGIC) {
+            PatchFormat::BPS
+        } else {
+            return Err("Not an IPS, UPS or BPS patch".to_string());
+        };
+        if format != PatchFormat::IPS {
+            check_crc("Patch", &data[..data.len().saturating_sub(4)], &data, 8)?;
+        }
+        Ok(Patch { format, data })
+    }
+
+    /// An IPS patch turning `source` into `target`.
+    pub fn ips_diff(source: &[u8], target: &[u8]) -> Patch {
+        let mut data = IPS_MAGIC.to_vec();
+        let mut offset = 0;
+        while offset < target.len() {
+            if source.get(offset) == Some(&target[offset]) {
+                offset += 1;
+                continue;
+            }
+            let mut start = offset;
+            if start == IPS_EOF_OFFSET {
+                start -= 1;
+            }
+            let mut end = offset;
+            while end < target.len()
+                && end - start < IPS_MAX_RECORD_BYTES
+                && source.get(end) != Some(&target[end])
+            {
+                end += 1;
+            }
+            data.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+            data.extend_from_slice(&((end - start) as u16).to_be_bytes());
+            data.extend_from_slice(&target[start..end]);
+            offset = end;
+        }
+        data.extend_from_slice(IPS_EOF);
+        if target.len() < source.len() {
+            data.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+        }
+        Patch {
+            format: PatchFormat::IPS,
+            data,
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, &self.data).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Returns the patched image. UPS and BPS patches fail unless `source`
+    /// and the result have the CRC-32s recorded in the patch.
+    pub fn apply(&self, source: &[u8]) -> Result<Vec<u8>, String> {
+        match self.format {
+            PatchFormat::IPS => self.apply_ips(source),
+            PatchFormat::UPS => {
+                check_crc("Source", source, &self.data, 0)?;
+                let target = self.apply_ups(source)?;
+                check_crc("Target", &target, &self.data, 4)?;
+                Ok(target)
+            }
+            PatchFormat::BPS => {
+                check_crc("Source", source, &self.data, 0)?;
+                let target = self.apply_bps(source)?;
+                check_crc("Target", &target, &self.data, 4)?;
+                Ok(target)
+            }
+        }
+    }
+
+    /// Records are a 24-bit offset and a 16-bit length followed by that many
+    /// bytes, or by a 16-bit count and a fill byte when the length is zero.
+    /// An optional 24-bit size after `EOF` truncates the image.
+    fn apply_ips(&self, source: &[u8]) -> Result<Vec<u8>, String> {
+        let mut target = source.to_vec();
+        let mut reader = PatchReader::new(&self.data, IPS_MAGIC.len(), self.data.len());
+        loop {
+            if reader.rest().starts_with(IPS_EOF) {
+                reader.bytes(IPS_EOF.len())?;
+                break;
+            }
+            let offset = reader.be(3)?;
+            let length = reader.be(2)?;
+            let (length, bytes) = if length == 0 {
+                let count = reader.be(2)?;
+                (count, vec![reader.bytes(1)?[0]; count])
+            } else {
+                (length, reader.bytes(length)?.to_vec())
+            };
+            if target.len() < offset + length {
+                target.resize(offset + length, 0);
+            }
+            target[offset..offset + length].copy_from_slice(&bytes);
+        }
+        if reader.rest().len() >= 3 {
+            let size = reader.be(3)?;
+            target.truncate(size);
+        }
+        Ok(target)
+    }
+
+    /// After the sizes come runs of target bytes XORed with the source, each
+    /// preceded by the distance from the end of the previous run.
+    fn apply_ups(&self, source: &[u8]) -> Result<Vec<u8>, String> {
+        let end = self.footer_start()?;
+        let mut reader = PatchReader::new(&self.data, UPS_MAGIC.len(), end);
+        let _source_size = reader.varint()?;
+        let target_size = check_target_size("UPS", reader.varint()?)?;
+
+        let mut target = source.to_vec();
+        target.resize(target_size, 0);
+        let mut position: usize = 0;
+        while reader.offset < end {
+            position = position.saturating_add(reader.varint()?);
+            loop {
+                let value = reader.bytes(1)?[0];
+                if value == 0 {
+                    position = position.saturating_add(1);
+                    break;
+                }
+                if let Some(byte) = target.get_mut(position) {
+                    *byte ^= value;
+                }
+                position = position.saturating_add(1);
+            }
+        }
+        Ok(target)
+    }
+
+    /// After the sizes and metadata come actions that build the target from
+    /// the source at the same offset, literal bytes in the patch, or runs
+    /// copied from elsewhere in the source or the target.
+    fn apply_bps(&self, source: &[u8]) -> Result<Vec<u8>, String> {
+        let end = self.footer_start()?;
+        let mut reader = PatchReader::new(&self.data, BPS_MAGIC.len(), end);
+        let _source_size = reader.varint()?;
+        let target_size = check_target_size("BPS", reader.varint()?)?;
+        let metadata_size = reader.varint()?;
+        reader.bytes(metadata_size)?;
+
+        let mut target = Vec::with_capacity(target_size);
+        let mut source_offset = 0;
+        let mut target_offset = 0;
+        while reader.offset < end {
+            let action = reader.varint()?;
+            let length = (action >> 2) + 1;
+            if target.len().saturating_add(length) > target_size {
+                return Err("BPS patch writes past the end of the target".to_string());
+            }
+            match action & 0x03 {
+                0 => {
+                    let start = target.len();
+                    let bytes = source
+                        .get(start..start.saturating_add(length))
+                        .ok_or("BPS source read past the end of the source")?;
+                    target.extend_from_slice(bytes);
+                }
+                1 => target.extend_from_slice(reader.bytes(length)?),
+                2 => {
+                    source_offset = reader.relative_offset(source_offset)?;
+                    let bytes = source
+                        .get(source_offset..source_offset.saturating_add(length))
+                        .ok_or("BPS source copy past the end of the source")?;
+                    target.extend_from_slice(bytes);
+                    source_offset += length;
+                }
+                _ => {
+                    target_offset = reader.relative_offset(target_offset)?;
+                    // The copy may overlap the bytes it is producing.
+                    for _ in 0..length {
+                        let byte = *target
+                            .get(target_offset)
+                            .ok_or("BPS target copy past the end of the target")?;
+                        target.push(byte);
+                        target_offset += 1;
+                    }
+                }
+            }
+        }
+        if target.len() != target_size {
+            return Err(format!(
+                "BPS patch produced {} bytes, expected {}",
+                target.len(),
+                target_size
+            ));
+        }
+        Ok(target)
+    }
+
+    fn footer_start(&self) -> Result<usize, String> {
+        self.data
+            .len()
+            .checked_sub(FOOTER_BYTES)
+            .filter(|end| *end >= UPS_MAGIC.len())
+            .ok_or_else(|| "Patch is too short".to_string())
+    }
+}
+
+fn check_target_size(name: &str, size: usize) -> Result<usize, String> {
+    if size > MAX_TARGET_BYTES {
+        return Err(format!(
+            "{} target size of {} bytes is larger than {} bytes",
+            name, size, MAX_TARGET_BYTES
+        ));
+    }
+    Ok(size)
+}
+
+/// Compares the CRC-32 of `data` with the one stored `offset` bytes into
+/// the footer of `patch`.
+fn check_crc(name: &str, data: &[u8], patch: &[u8], offset: usize) -> Result<(), String> {
+    let start = patch
+        .len()
+        .checked_sub(FOOTER_BYTES)
+        .ok_or("Patch is too short")?
+        + offset;
+    let expected = u32::from_le_bytes(patch[start..start + 4].try_into().unwrap());
+    let actual = hash::crc32(data);
+    if actual != expected {
+        return Err(format!(
+            "{} CRC-32 mismatch: expected {:08X}, got {:08X}",
+            name, expected, actual
+        ));
+    }
+    Ok(())
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    end: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], offset: usize, end: usize) -> Self {
+        Self { data, offset, end }
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.data[self.offset..self.end]
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .rest()
+            .get(..length)
+            .ok_or("Patch ends in the middle of a record")?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn be(&mut self, length: usize) -> Result<usize, String> {
+        Ok(self
+            .bytes(length)?
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as usize))
+    }
+
+    /// The variable-length numbers of UPS and BPS: seven bits per byte,
+    /// least significant first, with the top bit marking the last byte.
+    fn varint(&mut self) -> Result<usize, String> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.bytes(1)?[0];
+            value = value
+                .checked_add((byte & 0x7F) as usize * shift)
+                .ok_or("Patch number is too large")?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or("Patch number is too large")?;
+            value = value
+                .checked_add(shift)
+                .ok_or("Patch number is too large")?;
+        }
+    }
+
+    /// A signed BPS copy offset: bit 0 is the sign, the rest the distance.
+    fn relative_offset(&mut self, offset: usize) -> Result<usize, String> {
+        let value = self.varint()?;
+        let distance = value >> 1;
+        if value & 0x01 != 0 {
+            offset.checked_sub(distance)
+        } else {
+            offset.checked_add(distance)
+        }
+        .ok_or_else(|| "BPS copy offset out of range".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A UPS or BPS patch of an empty source, with `body` after the magic
+    /// number and a valid footer apart from the target CRC-32.
+    fn patch(magic: &[u8], body: &[u8]) -> Patch {
+        let mut data = magic.to_vec();
+        data.extend_from_slice(body);
+        data.extend_from_slice(&hash::crc32(&[]).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        let crc = hash::crc32(&data);
+        data.extend_from_slice(&crc.to_le_bytes());
+        Patch::parse(data).unwrap()
+    }
+
+    fn varint(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    /// A UPS or BPS patch with `body` after the magic number and a footer
+    /// matching `source` and `target`.
+    fn checked_patch(magic: &[u8], body: &[u8], source: &[u8], target: &[u8]) -> Patch {
+        let mut data = magic.to_vec();
+        data.extend_from_slice(body);
+        data.extend_from_slice(&hash::crc32(source).to_le_bytes());
+        data.extend_from_slice(&hash::crc32(target).to_le_bytes());
+        let crc = hash::crc32(&data);
+        data.extend_from_slice(&crc.to_le_bytes());
+        Patch::parse(data).unwrap()
+    }
+
+    #[test]
+    fn applies_ips_records_and_rle() {
+        let mut data = IPS_MAGIC.to_vec();
+        // Two bytes at 1, three $CC at 4, and one byte past the end.
+        data.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        data.extend_from_slice(&[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0xCC]);
+        data.extend_from_slice(&[0x00, 0x00, 0x09, 0x00, 0x01, 0xEE]);
+        data.extend_from_slice(IPS_EOF);
+        let patch = Patch::parse(data).unwrap();
+        assert_eq!(patch.format, PatchFormat::IPS);
+        assert_eq!(
+            patch.apply(&[0; 8]).unwrap(),
+            [0x00, 0xAA, 0xBB, 0x00, 0xCC, 0xCC, 0xCC, 0x00, 0x00, 0xEE]
+        );
+    }
+
+    #[test]
+    fn applies_ips_truncation() {
+        let mut data = IPS_MAGIC.to_vec();
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x01, 0x11]);
+        data.extend_from_slice(IPS_EOF);
+        data.extend_from_slice(&[0x00, 0x00, 0x03]);
+        let patch = Patch::parse(data).unwrap();
+        assert_eq!(patch.apply(&[1, 2, 3, 4, 5]).unwrap(), [0x11, 2, 3]);
+    }
+
+    #[test]
+    fn applies_ups() {
+        let source = [1, 2, 3, 4];
+        let target = [1, 9, 3, 4, 5];
+        let mut body = varint(source.len());
+        body.extend(varint(target.len()));
+        // Skip one byte, XOR one, then skip one more and XOR past the source.
+        body.extend(varint(1));
+        body.extend_from_slice(&[2 ^ 9, 0x00]);
+        body.extend(varint(1));
+        body.extend_from_slice(&[5, 0x00]);
+        let patch = checked_patch(UPS_MAGIC, &body, &source, &target);
+        assert_eq!(patch.apply(&source).unwrap(), target);
+        assert!(patch.apply(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn ups_skip_does_not_overflow_position() {
+        let mut body = varint(0);
+        body.extend(varint(1));
+        body.extend(varint(usize::MAX - 1));
+        body.extend_from_slice(&[0xFF, 0xFF, 0x00]);
+        let patch = checked_patch(UPS_MAGIC, &body, &[], &[0]);
+        assert_eq!(patch.apply(&[]).unwrap(), [0]);
+    }
+
+    #[test]
+    fn applies_bps() {
+        let source = [1, 2, 3, 4, 5, 6];
+        let target = [1, 2, 0xAA, 0xAA, 0xAA, 5, 6, 1, 2];
+        let mut body = varint(source.len());
+        body.extend(varint(target.len()));
+        body.extend(varint(0));
+        // Source read of 2.
+        body.extend(varint(1 << 2));
+        // Target read of 1.
+        body.extend(varint(1));
+        body.push(0xAA);
+        // Overlapping target copy of 2 from offset 2.
+        body.extend(varint(1 << 2 | 3));
+        body.extend(varint(2 << 1));
+        // Source copy of 2 from offset 4, then of 2 from offset 0.
+        body.extend(varint(1 << 2 | 2));
+        body.extend(varint(4 << 1));
+        body.extend(varint(1 << 2 | 2));
+        body.extend(varint(6 << 1 | 1));
+        let patch = checked_patch(BPS_MAGIC, &body, &source, &target);
+        assert_eq!(patch.format, PatchFormat::BPS);
+        assert_eq!(patch.apply(&source).unwrap(), target);
+    }
+
+    #[test]
+    fn ips_diff_round_trips() {
+        let source: Vec<u8> = (0..=255).cycle().take(0x30000).collect();
+        let mut target = source.clone();
+        target[0] = 0xFF;
+        for byte in &mut target[0x100..0x20000] {
+            *byte ^= 0x5A;
+        }
+        target.extend_from_slice(&[1, 2, 3]);
+        let patch = Patch::ips_diff(&source, &target);
+        assert_eq!(patch.apply(&source).unwrap(), target);
+
+        let shorter = &target[..0x1000];
+        let patch = Patch::ips_diff(&source, shorter);
+        assert_eq!(patch.apply(&source).unwrap(), shorter);
+    }
+
+    #[test]
+    fn ips_diff_avoids_eof_offset() {
+        let source = vec![0; IPS_EOF_OFFSET + 2];
+        let mut target = source.clone();
+        target[IPS_EOF_OFFSET] = 1;
+        let patch = Patch::ips_diff(&source, &target);
+        assert_eq!(patch.apply(&source).unwrap(), target);
+    }
+
+    /// A source size of zero followed by a target size of 2^28 - 1.
+    const HUGE_SIZES: &[u8] = &[0x80, 0x7F, 0x7E, 0x7E, 0xFE];
+
+    #[test]
+    fn ups_rejects_huge_target_size() {
+        let error = patch(UPS_MAGIC, HUGE_SIZES).apply(&[]).unwrap_err();
+        assert!(error.contains("UPS target size"), "{}", error);
+    }
+
+    #[test]
+    fn bps_rejects_huge_target_size() {
+        let mut body = HUGE_SIZES.to_vec();
+        body.push(0x80);
+        let error = patch(BPS_MAGIC, &body).apply(&[]).unwrap_err();
+        assert!(error.contains("BPS target size"), "{}", error);
+    }
+
+    #[test]
+    fn bps_rejects_writes_past_target_size() {
+        // Target size 1, no metadata, then a long copy from the target.
+        let body = [0x80, 0x81, 0x80, 0x7F, 0x7F, 0x7F, 0x80, 0x80];
+        let error = patch(BPS_MAGIC, &body).apply(&[]).unwrap_err();
+        assert!(error.contains("past the end of the target"), "{}", error);
+    }
+}
@@ -1,46 +1,764 @@
+mod palette;
 mod registers;
 
+pub use self::palette::{luminance, rgb};
 use self::registers::Registers;
-use crate::bus::{BusAddr, ByteReadable, ByteWritable};
+use crate::{bus::BusAddr, mapper::Mapper, region::Region};
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+const DOTS_PER_SCANLINE: u16 = 341;
+const NTSC_SCANLINES_PER_FRAME: u16 = 262;
+/// The PAL PPU has a longer vblank, and runs 16 dots every 5 CPU cycles
+/// instead of 3 per cycle.
+const PAL_SCANLINES_PER_FRAME: u16 = 312;
+const PAL_DOT_CYCLE_LENGTH: u8 = 5;
+const VBLANK_SCANLINE: u16 = 241;
+
+const OAM_SIZE: usize = 0x100;
+const MAX_SPRITES_PER_SCANLINE: usize = 8;
+const NAMETABLE_SIZE: u16 = 0x0400;
+const PALETTE_RAM_SIZE: usize = 0x20;
+
+const NAMETABLES_START_ADDR: u16 = 0x2000;
+const PALETTE_START_ADDR: u16 = 0x3F00;
+
+const CTRL_VRAM_INCREMENT_32: u8 = 0x04;
+const CTRL_SPRITE_PATTERN_TABLE: u8 = 0x08;
+const CTRL_BACKGROUND_PATTERN_TABLE: u8 = 0x10;
+const CTRL_SPRITE_SIZE_8X16: u8 = 0x20;
+const CTRL_NMI_ENABLE: u8 = 0x80;
+
+const MASK_GREYSCALE: u8 = 0x01;
+const MASK_SHOW_BACKGROUND_LEFT: u8 = 0x02;
+const MASK_SHOW_SPRITES_LEFT: u8 = 0x04;
+const MASK_SHOW_BACKGROUND: u8 = 0x08;
+const MASK_SHOW_SPRITES: u8 = 0x10;
+
+const STATUS_SPRITE_OVERFLOW: u8 = 0x20;
+const STATUS_SPRITE_ZERO_HIT: u8 = 0x40;
+const STATUS_VBLANK: u8 = 0x80;
+
+const SPRITE_ATTRIBUTE_PALETTE: u8 = 0x03;
+const SPRITE_ATTRIBUTE_BEHIND_BACKGROUND: u8 = 0x20;
+const SPRITE_ATTRIBUTE_FLIP_HORIZONTAL: u8 = 0x40;
+const SPRITE_ATTRIBUTE_FLIP_VERTICAL: u8 = 0x80;
+
+/// How the four logical nametables map onto the console's 2KB of VRAM (or
+/// the cartridge's extra 2KB for four-screen boards).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen,
+}
+
+impl Mirroring {
+    pub(crate) fn nametable_offset(&self, addr: u16) -> usize {
+        let addr = (addr - NAMETABLES_START_ADDR) % (NAMETABLE_SIZE * 4);
+        let table = addr / NAMETABLE_SIZE;
+        let offset = addr % NAMETABLE_SIZE;
+        let physical_table = match self {
+            Mirroring::Horizontal => table / 2,
+            Mirroring::Vertical => table % 2,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => table,
+        };
+        (physical_table * NAMETABLE_SIZE + offset) as usize
+    }
+}
+
+/// A sprite selected for the scanline being drawn, with its pattern already
+/// fetched (and flipped horizontally if requested).
+#[derive(Debug, Clone, Copy, Default)]
+struct SpriteSlot {
+    x: u8,
+    attribute: u8,
+    pattern_low: u8,
+    pattern_high: u8,
+    is_sprite_zero: bool,
+}
 
 pub struct PPU {
     registers: Registers,
+    oam: [u8; OAM_SIZE],
+    nametables: [u8; (NAMETABLE_SIZE * 4) as usize],
+    palette_ram: [u8; PALETTE_RAM_SIZE],
+    region: Region,
+    /// Position in the PAL PPU's 5 CPU cycle pattern of dots.
+    pal_dot_cycle: u8,
+    dot: u16,
+    scanline: u16,
+    frame: u64,
+    nmi_pending: bool,
+
+    next_tile_id: u8,
+    next_tile_attribute: u8,
+    next_tile_pattern_low: u8,
+    next_tile_pattern_high: u8,
+    background_pattern_low: u16,
+    background_pattern_high: u16,
+    background_attribute_low: u16,
+    background_attribute_high: u16,
+
+    /// OAM indices found during evaluation, for the scanline after the
+    /// current one.
+    evaluated_sprites: [u8; MAX_SPRITES_PER_SCANLINE],
+    evaluated_sprite_count: usize,
+    next_sprites: [SpriteSlot; MAX_SPRITES_PER_SCANLINE],
+    sprites: [SpriteSlot; MAX_SPRITES_PER_SCANLINE],
+    sprite_count: usize,
+
+    frame_buffer: Vec<u8>,
 }
 
 impl PPU {
+    /// A PPU at power on. The pattern tables and the nametable mirroring
+    /// come from the cartridge's mapper.
     pub fn new() -> Self {
+        Self::with_region(Region::NTSC)
+    }
+
+    pub fn with_region(region: Region) -> Self {
         Self {
             registers: Registers::new(),
+            oam: [0; OAM_SIZE],
+            nametables: [0; (NAMETABLE_SIZE * 4) as usize],
+            palette_ram: [0; PALETTE_RAM_SIZE],
+            region,
+            pal_dot_cycle: 0,
+            dot: 0,
+            scanline: 0,
+            frame: 0,
+            nmi_pending: false,
+            next_tile_id: 0,
+            next_tile_attribute: 0,
+            next_tile_pattern_low: 0,
+            next_tile_pattern_high: 0,
+            background_pattern_low: 0,
+            background_pattern_high: 0,
+            background_attribute_low: 0,
+            background_attribute_high: 0,
+            evaluated_sprites: [0; MAX_SPRITES_PER_SCANLINE],
+            evaluated_sprite_count: 0,
+            next_sprites: [SpriteSlot::default(); MAX_SPRITES_PER_SCANLINE],
+            sprites: [SpriteSlot::default(); MAX_SPRITES_PER_SCANLINE],
+            sprite_count: 0,
+            frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    /// Number of dots to run for the next CPU cycle.
+    pub fn dots_for_cpu_cycle(&mut self) -> usize {
+        match self.region {
+            Region::NTSC => 3,
+            Region::PAL => {
+                self.pal_dot_cycle = (self.pal_dot_cycle + 1) % PAL_DOT_CYCLE_LENGTH;
+                if self.pal_dot_cycle == 0 {
+                    4
+                } else {
+                    3
+                }
+            }
+        }
+    }
+
+    fn scanlines_per_frame(&self) -> u16 {
+        match self.region {
+            Region::NTSC => NTSC_SCANLINES_PER_FRAME,
+            Region::PAL => PAL_SCANLINES_PER_FRAME,
+        }
+    }
+
+    /// Advances the PPU by a single dot. The CPU bus calls this as many
+    /// times per CPU cycle as `dots_for_cpu_cycle` says.
+    pub fn tick(&mut self, mapper: &mut dyn Mapper) {
+        let visible_scanline = self.scanline < SCREEN_HEIGHT as u16;
+        let pre_render_scanline = self.scanline == self.scanlines_per_frame() - 1;
+
+        if self.dot == 0 {
+            mapper.scanline(
+                self.scanline,
+                pre_render_scanline,
+                self.is_rendering_enabled(),
+            );
+        }
+        if self.is_rendering_enabled() && (visible_scanline || pre_render_scanline) {
+            self.run_background_pipeline(mapper, pre_render_scanline);
+            self.run_sprite_pipeline(mapper, visible_scanline);
+        }
+        if visible_scanline && (1..=SCREEN_WIDTH as u16).contains(&self.dot) {
+            self.render_pixel();
+        }
+
+        if self.dot == 1 {
+            if self.scanline == VBLANK_SCANLINE {
+                self.registers.ppu_status |= STATUS_VBLANK;
+                if self.registers.ppu_ctrl & CTRL_NMI_ENABLE != 0 {
+                    self.nmi_pending = true;
+                }
+            } else if pre_render_scanline {
+                self.registers.ppu_status &=
+                    !(STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW);
+            }
+        }
+
+        self.dot += 1;
+        // On NTSC, the pre-render scanline is one dot shorter on odd frames
+        // while rendering is enabled.
+        let skip_last_dot = self.region == Region::NTSC
+            && pre_render_scanline
+            && self.frame % 2 == 1
+            && self.is_rendering_enabled();
+        if self.dot >= DOTS_PER_SCANLINE || (skip_last_dot && self.dot == DOTS_PER_SCANLINE - 1) {
+            self.dot = 0;
+            self.sprites = self.next_sprites;
+            self.sprite_count = self.evaluated_sprite_count;
+            self.scanline += 1;
+            if self.scanline >= self.scanlines_per_frame() {
+                self.scanline = 0;
+                self.frame += 1;
+            }
+        }
+    }
+
+    /// Number of frames completed since power on.
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    /// The picture as 6-bit palette colors, `SCREEN_WIDTH` per row. Rows at
+    /// or below the current scanline still hold the previous frame.
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.frame_buffer
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.frame_buffer[y * SCREEN_WIDTH + x]
+    }
+
+    /// Returns whether the PPU raised an NMI since the last call.
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_pending)
+    }
+
+    fn is_rendering_enabled(&self) -> bool {
+        self.registers.ppu_mask & (MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES) != 0
+    }
+
+    fn run_background_pipeline(&mut self, mapper: &mut dyn Mapper, pre_render_scanline: bool) {
+        let dot = self.dot;
+        if (2..=257).contains(&dot) || (322..=337).contains(&dot) {
+            self.shift_background();
+        }
+        if (1..=256).contains(&dot) || (321..=336).contains(&dot) {
+            match (dot - 1) % 8 {
+                0 => {
+                    self.load_background_shifters();
+                    self.next_tile_id =
+                        self.read_vram(mapper, 0x2000 | (self.registers.vram_addr & 0x0FFF));
+                }
+                2 => {
+                    let v = self.registers.vram_addr;
+                    let addr = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+                    let mut attribute = self.read_vram(mapper, addr);
+                    if v & 0x40 != 0 {
+                        attribute >>= 4;
+                    }
+                    if v & 0x02 != 0 {
+                        attribute >>= 2;
+                    }
+                    self.next_tile_attribute = attribute & 0x03;
+                }
+                4 => {
+                    let addr = self.background_pattern_addr();
+                    self.next_tile_pattern_low = self.read_vram(mapper, addr);
+                }
+                6 => {
+                    let addr = self.background_pattern_addr() + 8;
+                    self.next_tile_pattern_high = self.read_vram(mapper, addr);
+                }
+                7 => self.increment_coarse_x(),
+                _ => {}
+            }
+        }
+        if dot == 256 {
+            self.increment_y();
+        }
+        if dot == 257 {
+            self.load_background_shifters();
+            // Copy the horizontal position from t to v.
+            self.registers.vram_addr =
+                (self.registers.vram_addr & !0x041F) | (self.registers.temp_vram_addr & 0x041F);
+        }
+        if pre_render_scanline && (280..=304).contains(&dot) {
+            // Copy the vertical position from t to v.
+            self.registers.vram_addr =
+                (self.registers.vram_addr & !0x7BE0) | (self.registers.temp_vram_addr & 0x7BE0);
+        }
+        if dot == 338 || dot == 340 {
+            // Unused nametable fetches, visible to mappers watching the bus.
+            self.read_vram(mapper, 0x2000 | (self.registers.vram_addr & 0x0FFF));
+        }
+    }
+
+    fn background_pattern_addr(&self) -> u16 {
+        let table = if self.registers.ppu_ctrl & CTRL_BACKGROUND_PATTERN_TABLE != 0 {
+            0x1000
+        } else {
+            0x0000
+        };
+        let fine_y = (self.registers.vram_addr >> 12) & 0x07;
+        table + self.next_tile_id as u16 * 16 + fine_y
+    }
+
+    fn shift_background(&mut self) {
+        self.background_pattern_low <<= 1;
+        self.background_pattern_high <<= 1;
+        self.background_attribute_low <<= 1;
+        self.background_attribute_high <<= 1;
+    }
+
+    fn load_background_shifters(&mut self) {
+        self.background_pattern_low =
+            (self.background_pattern_low & 0xFF00) | self.next_tile_pattern_low as u16;
+        self.background_pattern_high =
+            (self.background_pattern_high & 0xFF00) | self.next_tile_pattern_high as u16;
+        let attribute_low = if self.next_tile_attribute & 0x01 != 0 {
+            0xFF
+        } else {
+            0x00
+        };
+        let attribute_high = if self.next_tile_attribute & 0x02 != 0 {
+            0xFF
+        } else {
+            0x00
+        };
+        self.background_attribute_low = (self.background_attribute_low & 0xFF00) | attribute_low;
+        self.background_attribute_high = (self.background_attribute_high & 0xFF00) | attribute_high;
+    }
+
+    fn increment_coarse_x(&mut self) {
+        let v = &mut self.registers.vram_addr;
+        if *v & 0x001F == 31 {
+            *v &= !0x001F;
+            *v ^= 0x0400;
+        } else {
+            *v += 1;
+        }
+    }
+
+    fn increment_y(&mut self) {
+        let v = &mut self.registers.vram_addr;
+        if *v & 0x7000 != 0x7000 {
+            *v += 0x1000;
+            return;
+        }
+        *v &= !0x7000;
+        let mut coarse_y = (*v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            *v ^= 0x0800;
+        } else if coarse_y == 31 {
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
         }
+        *v = (*v & !0x03E0) | (coarse_y << 5);
+    }
+
+    fn run_sprite_pipeline(&mut self, mapper: &mut dyn Mapper, visible_scanline: bool) {
+        if self.dot == 257 {
+            if visible_scanline {
+                self.evaluate_sprites();
+            } else {
+                self.evaluated_sprite_count = 0;
+            }
+        }
+        // Each of the eight slots gets its pattern fetched over 8 dots;
+        // unused slots fetch tile $FF like the real PPU does.
+        if (257..=320).contains(&self.dot) && (self.dot - 257) % 8 == 7 {
+            let slot = ((self.dot - 257) / 8) as usize;
+            self.fetch_sprite(mapper, slot);
+        }
+    }
+
+    fn sprite_height(&self) -> u16 {
+        if self.registers.ppu_ctrl & CTRL_SPRITE_SIZE_8X16 != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn evaluate_sprites(&mut self) {
+        let height = self.sprite_height();
+        self.evaluated_sprite_count = 0;
+        for index in 0..OAM_SIZE / 4 {
+            let y = self.oam[index * 4] as u16;
+            if self.scanline < y || self.scanline - y >= height {
+                continue;
+            }
+            if self.evaluated_sprite_count == MAX_SPRITES_PER_SCANLINE {
+                self.registers.ppu_status |= STATUS_SPRITE_OVERFLOW;
+                break;
+            }
+            self.evaluated_sprites[self.evaluated_sprite_count] = index as u8;
+            self.evaluated_sprite_count += 1;
+        }
+    }
+
+    fn fetch_sprite(&mut self, mapper: &mut dyn Mapper, slot: usize) {
+        let height = self.sprite_height();
+        let (index, row) = if slot < self.evaluated_sprite_count {
+            let index = self.evaluated_sprites[slot] as usize;
+            (Some(index), self.scanline - self.oam[index * 4] as u16)
+        } else {
+            (None, 0)
+        };
+        let (tile, attribute, x) = match index {
+            Some(index) => (
+                self.oam[index * 4 + 1],
+                self.oam[index * 4 + 2],
+                self.oam[index * 4 + 3],
+            ),
+            None => (0xFF, 0, 0xFF),
+        };
+
+        let row = if attribute & SPRITE_ATTRIBUTE_FLIP_VERTICAL != 0 {
+            height - 1 - row
+        } else {
+            row
+        };
+        let addr = if height == 16 {
+            let table = (tile as u16 & 0x01) * 0x1000;
+            let tile = (tile & 0xFE) as u16 + row / 8;
+            table + tile * 16 + row % 8
+        } else {
+            let table = if self.registers.ppu_ctrl & CTRL_SPRITE_PATTERN_TABLE != 0 {
+                0x1000
+            } else {
+                0x0000
+            };
+            table + tile as u16 * 16 + row
+        };
+
+        let mut pattern_low = self.read_vram(mapper, addr);
+        let mut pattern_high = self.read_vram(mapper, addr + 8);
+        if attribute & SPRITE_ATTRIBUTE_FLIP_HORIZONTAL != 0 {
+            pattern_low = pattern_low.reverse_bits();
+            pattern_high = pattern_high.reverse_bits();
+        }
+
+        self.next_sprites[slot] = SpriteSlot {
+            x,
+            attribute,
+            pattern_low,
+            pattern_high,
+            is_sprite_zero: index == Some(0),
+        };
+    }
+
+    fn render_pixel(&mut self) {
+        let x = (self.dot - 1) as usize;
+        let y = self.scanline as usize;
+        let mask = self.registers.ppu_mask;
+
+        let mut background_pixel = 0;
+        let mut background_palette = 0;
+        if mask & MASK_SHOW_BACKGROUND != 0 && (x >= 8 || mask & MASK_SHOW_BACKGROUND_LEFT != 0) {
+            let bit = 0x8000 >> self.registers.fine_x_scroll;
+            background_pixel = ((self.background_pattern_high & bit != 0) as u8) << 1
+                | (self.background_pattern_low & bit != 0) as u8;
+            background_palette = ((self.background_attribute_high & bit != 0) as u8) << 1
+                | (self.background_attribute_low & bit != 0) as u8;
+        }
+
+        let mut sprite_pixel = 0;
+        let mut sprite_attribute = 0;
+        let mut sprite_zero = false;
+        if mask & MASK_SHOW_SPRITES != 0 && (x >= 8 || mask & MASK_SHOW_SPRITES_LEFT != 0) {
+            for sprite in self.sprites[..self.sprite_count].iter() {
+                let offset = x as i32 - sprite.x as i32;
+                if !(0..8).contains(&offset) {
+                    continue;
+                }
+                let shift = 7 - offset;
+                let pixel = ((sprite.pattern_high >> shift) & 0x01) << 1
+                    | ((sprite.pattern_low >> shift) & 0x01);
+                if pixel != 0 {
+                    sprite_pixel = pixel;
+                    sprite_attribute = sprite.attribute;
+                    sprite_zero = sprite.is_sprite_zero;
+                    break;
+                }
+            }
+        }
+
+        if sprite_zero && background_pixel != 0 && sprite_pixel != 0 && x != 255 {
+            self.registers.ppu_status |= STATUS_SPRITE_ZERO_HIT;
+        }
+
+        let palette_addr = match (background_pixel, sprite_pixel) {
+            (0, 0) => 0,
+            (0, _) => 0x10 | (sprite_attribute & SPRITE_ATTRIBUTE_PALETTE) << 2 | sprite_pixel,
+            (_, 0) => background_palette << 2 | background_pixel,
+            _ if sprite_attribute & SPRITE_ATTRIBUTE_BEHIND_BACKGROUND != 0 => {
+                background_palette << 2 | background_pixel
+            }
+            _ => 0x10 | (sprite_attribute & SPRITE_ATTRIBUTE_PALETTE) << 2 | sprite_pixel,
+        };
+        let mut color = self.palette_ram[palette_offset(palette_addr as u16)];
+        if mask & MASK_GREYSCALE != 0 {
+            color &= 0x30;
+        }
+        self.frame_buffer[y * SCREEN_WIDTH + x] = color & 0x3F;
+    }
+
+    fn read_vram(&mut self, mapper: &mut dyn Mapper, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr < PALETTE_START_ADDR {
+            mapper.ppu_address(addr);
+        }
+        if addr < NAMETABLES_START_ADDR {
+            mapper.read_pattern_table(addr, &self.nametables)
+        } else if addr < PALETTE_START_ADDR {
+            mapper.read_nametable(addr, &self.nametables)
+        } else {
+            self.palette_ram[palette_offset(addr)]
+        }
+    }
+
+    fn write_vram(&mut self, mapper: &mut dyn Mapper, addr: u16, value: u8) {
+        let addr = addr & 0x3FFF;
+        if addr < PALETTE_START_ADDR {
+            mapper.ppu_address(addr);
+        }
+        if addr < NAMETABLES_START_ADDR {
+            mapper.write_pattern_table(addr, value, &mut self.nametables);
+        } else if addr < PALETTE_START_ADDR {
+            mapper.write_nametable(addr, value, &mut self.nametables);
+        } else {
+            self.palette_ram[palette_offset(addr)] = value;
+        }
+    }
+
+    fn increment_vram_addr(&mut self) {
+        let increment = if self.registers.ppu_ctrl & CTRL_VRAM_INCREMENT_32 != 0 {
+            32
+        } else {
+            1
+        };
+        self.registers.vram_addr = (self.registers.vram_addr + increment) & 0x7FFF;
+    }
+}
+
+/// $3F10/$3F14/$3F18/$3F1C mirror the backdrop entries below them.
+fn palette_offset(addr: u16) -> usize {
+    let offset = (addr as usize) % PALETTE_RAM_SIZE;
+    if offset >= 0x10 && offset & 0x03 == 0 {
+        offset - 0x10
+    } else {
+        offset
     }
 }
 
-impl ByteReadable for PPU {
-    fn read_byte(&self, addr: BusAddr) -> u8 {
+impl PPU {
+    /// Reads register `addr` ($2000-$2007 relative to $2000). $2007 goes
+    /// through `mapper` for the pattern tables and nametable mirroring.
+    pub fn read_register(&mut self, mapper: &mut dyn Mapper, addr: BusAddr) -> u8 {
         match addr {
-            0x0002 => self.registers.ppu_status,
-            0x0004 => self.registers.oam_data,
-            0x0007 => self.registers.ppu_data,
+            0x0002 => {
+                let status = self.registers.ppu_status;
+                self.registers.ppu_status &= !STATUS_VBLANK;
+                self.registers.write_toggle = false;
+                status
+            }
+            0x0004 => self.oam[self.registers.oam_addr as usize],
+            0x0007 => {
+                let vram_addr = self.registers.vram_addr & 0x3FFF;
+                let value = if vram_addr >= PALETTE_START_ADDR {
+                    // Palette reads are immediate, but still refill the
+                    // buffer with the nametable byte "underneath".
+                    self.registers.read_buffer = self.read_vram(mapper, vram_addr - 0x1000);
+                    self.read_vram(mapper, vram_addr)
+                } else {
+                    let buffered = self.registers.read_buffer;
+                    self.registers.read_buffer = self.read_vram(mapper, vram_addr);
+                    buffered
+                };
+                self.increment_vram_addr();
+                value
+            }
             _ => {
-                panic!("PPU {} is not readable", addr);
+                // Write-only registers read back as open bus.
+                0
             }
         }
     }
-}
 
-impl ByteWritable for PPU {
-    fn write_byte(&mut self, addr: BusAddr, value: u8) {
+    /// Writes register `addr` ($2000-$2007 relative to $2000).
+    pub fn write_register(&mut self, mapper: &mut dyn Mapper, addr: BusAddr, value: u8) {
         match addr {
-            0x0000 => self.registers.ppu_ctrl = value,
+            0x0000 => {
+                let nmi_was_enabled = self.registers.ppu_ctrl & CTRL_NMI_ENABLE != 0;
+                self.registers.ppu_ctrl = value;
+                self.registers.temp_vram_addr =
+                    (self.registers.temp_vram_addr & !0x0C00) | ((value as u16 & 0x03) << 10);
+                // Enabling NMI during vertical blank raises one immediately.
+                if !nmi_was_enabled
+                    && value & CTRL_NMI_ENABLE != 0
+                    && self.registers.ppu_status & STATUS_VBLANK != 0
+                {
+                    self.nmi_pending = true;
+                }
+            }
             0x0001 => self.registers.ppu_mask = value,
+            0x0002 => { /* Read only */ }
             0x0003 => self.registers.oam_addr = value,
-            0x0004 => self.registers.oam_data = value,
-            0x0005 => self.registers.ppu_scroll = value,
-            0x0006 => self.registers.ppu_addr = value,
-            0x0007 => self.registers.ppu_data = value,
+            0x0004 => {
+                self.oam[self.registers.oam_addr as usize] = value;
+                self.registers.oam_addr = self.registers.oam_addr.wrapping_add(1);
+            }
+            0x0005 => {
+                if !self.registers.write_toggle {
+                    self.registers.temp_vram_addr =
+                        (self.registers.temp_vram_addr & !0x001F) | (value as u16 >> 3);
+                    self.registers.fine_x_scroll = value & 0x07;
+                } else {
+                    self.registers.temp_vram_addr = (self.registers.temp_vram_addr & !0x73E0)
+                        | ((value as u16 & 0x07) << 12)
+                        | ((value as u16 & 0xF8) << 2);
+                }
+                self.registers.write_toggle = !self.registers.write_toggle;
+            }
+            0x0006 => {
+                if !self.registers.write_toggle {
+                    self.registers.temp_vram_addr =
+                        (self.registers.temp_vram_addr & 0x00FF) | ((value as u16 & 0x3F) << 8);
+                } else {
+                    self.registers.temp_vram_addr =
+                        (self.registers.temp_vram_addr & 0xFF00) | value as u16;
+                    self.registers.vram_addr = self.registers.temp_vram_addr;
+                    // The new address shows up on the PPU's bus right away.
+                    mapper.ppu_address(self.registers.vram_addr & 0x3FFF);
+                }
+                self.registers.write_toggle = !self.registers.write_toggle;
+            }
+            0x0007 => {
+                self.write_vram(mapper, self.registers.vram_addr, value);
+                self.increment_vram_addr();
+            }
             _ => {
                 panic!("PPU {} is not writable", addr);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{iNES, mapper};
+
+    /// An NROM cartridge with 8KB of CHR-RAM and horizontal mirroring.
+    fn cartridge() -> Box<dyn Mapper> {
+        let mut image = b"NES\x1A\x01\x00\x00\x00".to_vec();
+        image.resize(16 + 0x4000, 0);
+        mapper::from_ines(&iNES::parse(&image).unwrap()).unwrap()
+    }
+
+    fn write_vram(ppu: &mut PPU, mapper: &mut dyn Mapper, addr: u16, data: &[u8]) {
+        ppu.write_register(mapper, 0x0006, (addr >> 8) as u8);
+        ppu.write_register(mapper, 0x0006, addr as u8);
+        for value in data {
+            ppu.write_register(mapper, 0x0007, *value);
+        }
+    }
+
+    fn run_until(ppu: &mut PPU, mapper: &mut dyn Mapper, done: impl Fn(&PPU) -> bool) -> u64 {
+        let mut dots = 0;
+        while !done(ppu) {
+            ppu.tick(mapper);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn frame_has_262_scanlines_of_341_dots() {
+        let mut mapper = cartridge();
+        let mut ppu = PPU::new();
+        let dots = run_until(&mut ppu, mapper.as_mut(), |ppu| ppu.frame_count() == 1);
+        assert_eq!(dots, 262 * 341);
+    }
+
+    #[test]
+    fn vblank_starts_at_dot_1_of_scanline_241_with_nmi() {
+        let mut mapper = cartridge();
+        let mut ppu = PPU::new();
+        ppu.write_register(mapper.as_mut(), 0x0000, CTRL_NMI_ENABLE);
+
+        run_until(&mut ppu, mapper.as_mut(), |ppu| {
+            ppu.scanline() == VBLANK_SCANLINE && ppu.dot() == 1
+        });
+        assert!(!ppu.take_nmi());
+        assert_eq!(
+            ppu.read_register(mapper.as_mut(), 0x0002) & STATUS_VBLANK,
+            0
+        );
+
+        ppu.tick(mapper.as_mut());
+        assert!(ppu.take_nmi());
+        assert!(!ppu.take_nmi());
+        assert_ne!(
+            ppu.read_register(mapper.as_mut(), 0x0002) & STATUS_VBLANK,
+            0
+        );
+        // Reading the status clears the flag.
+        assert_eq!(
+            ppu.read_register(mapper.as_mut(), 0x0002) & STATUS_VBLANK,
+            0
+        );
+    }
+
+    #[test]
+    fn renders_background_tile_from_chr_ram() {
+        let mut mapper = cartridge();
+        let mapper = mapper.as_mut();
+        let mut ppu = PPU::new();
+        // Tile 1 is solid color 1, tile 0 is blank.
+        write_vram(&mut ppu, mapper, 0x0010, &[0xFF; 8]);
+        write_vram(&mut ppu, mapper, 0x2000, &[0x01]);
+        write_vram(&mut ppu, mapper, 0x3F00, &[0x0F, 0x30]);
+        ppu.write_register(mapper, 0x0000, 0x00);
+        ppu.write_register(mapper, 0x0005, 0);
+        ppu.write_register(mapper, 0x0005, 0);
+        ppu.write_register(
+            mapper,
+            0x0001,
+            MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT,
+        );
+
+        run_until(&mut ppu, mapper, |ppu| ppu.frame_count() == 2);
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(ppu.pixel(x, y), 0x30, "pixel ({}, {})", x, y);
+            }
+            assert_eq!(ppu.pixel(8, y), 0x0F);
+        }
+        assert_eq!(ppu.pixel(0, 8), 0x0F);
+    }
+}
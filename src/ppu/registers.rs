@@ -4,10 +4,16 @@ pub struct Registers {
     pub ppu_mask: u8,
     pub ppu_status: u8,
     pub oam_addr: u8,
-    pub oam_data: u8,
-    pub ppu_scroll: u8,
-    pub ppu_addr: u8,
-    pub ppu_data: u8,
+    /// Current VRAM address ("v" in the NESdev wiki's scrolling notes).
+    pub vram_addr: u16,
+    /// Temporary VRAM address ("t"), which also holds the scroll position
+    /// of the top left onscreen tile.
+    pub temp_vram_addr: u16,
+    pub fine_x_scroll: u8,
+    /// Shared first/second write toggle of PPUSCROLL and PPUADDR ("w").
+    pub write_toggle: bool,
+    /// PPUDATA reads outside the palette return this and then refill it.
+    pub read_buffer: u8,
 }
 
 impl Registers {
@@ -17,10 +23,11 @@ impl Registers {
             ppu_mask: 0,
             ppu_status: 0,
             oam_addr: 0,
-            oam_data: 0,
-            ppu_scroll: 0,
-            ppu_addr: 0,
-            ppu_data: 0,
+            vram_addr: 0,
+            temp_vram_addr: 0,
+            fine_x_scroll: 0,
+            write_toggle: false,
+            read_buffer: 0,
         }
     }
 }
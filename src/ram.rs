@@ -15,7 +15,7 @@ impl RAM {
 }
 
 impl ByteReadable for RAM {
-    fn read_byte(&self, addr: BusAddr) -> u8 {
+    fn read_byte(&mut self, addr: BusAddr) -> u8 {
         self.data[addr as usize]
     }
 }
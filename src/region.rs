@@ -0,0 +1,36 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    NTSC,
+    PAL,
+}
+
+impl Region {
+    pub fn cpu_clock_rate(&self) -> u32 {
+        match self {
+            Region::NTSC => 1_789_773,
+            Region::PAL => 1_662_607,
+        }
+    }
+
+    /// PPU frames per second.
+    pub fn frame_rate(&self) -> f64 {
+        match self {
+            Region::NTSC => 60.0988,
+            Region::PAL => 50.007,
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::NTSC),
+            "pal" => Ok(Region::PAL),
+            _ => Err(format!("Unknown region: {}", s)),
+        }
+    }
+}